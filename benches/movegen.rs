@@ -0,0 +1,30 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use rust_chess::chess::{generate_moves, perft, GameData};
+use rust_chess::fen::from_fen;
+
+const MIDDLEGAME: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+fn bench_generate_moves(c: &mut Criterion) {
+    let start = GameData::default();
+    let middlegame = from_fen(MIDDLEGAME).unwrap();
+    let mut group = c.benchmark_group("generate_moves");
+    group.bench_function("start", |b| b.iter(|| generate_moves(black_box(&start))));
+    group.bench_function("middlegame", |b| {
+        b.iter(|| generate_moves(black_box(&middlegame)))
+    });
+    group.finish();
+}
+
+fn bench_perft(c: &mut Criterion) {
+    let start = GameData::default();
+    let nodes = perft(&start, 4);
+    let mut group = c.benchmark_group("perft");
+    // reported as elements/s, i.e. nodes per second
+    group.throughput(Throughput::Elements(nodes));
+    group.sample_size(10);
+    group.bench_function("start_depth_4", |b| b.iter(|| perft(black_box(&start), 4)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_generate_moves, bench_perft);
+criterion_main!(benches);
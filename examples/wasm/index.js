@@ -0,0 +1,10 @@
+// Applying a move from the browser. Build the bindings first with
+//   wasm-pack build --target web -- --no-default-features --features wasm
+// and serve this file next to the generated `pkg/` directory.
+import init, { wasm_generate_moves, wasm_apply_move } from "./pkg/rust_chess.js";
+
+await init();
+const start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+console.log(wasm_generate_moves(start)["e2"]); // ["e3", "e4"]
+console.log(wasm_apply_move(start, "e2e4"));
+// rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1
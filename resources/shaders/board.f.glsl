@@ -7,17 +7,20 @@ uniform vec3 white_color;
 uniform vec3 black_color;
 uniform float opacity;
 
-uniform int side_size;
+// on-screen size of a square and the board's lower left corner, both in
+// window pixels
+uniform float side_size;
+uniform vec2 origin;
 
-int map_to_grid_mod2(float current)
+int map_to_grid_mod2(float current, float start)
 {
-    return int(mod(int(current) / side_size, 2));
+    return int(mod(floor((current - start) / side_size), 2.0));
 }
 
 void main()
 {
-    int x_t = map_to_grid_mod2(gl_FragCoord.x);
-    int y_t = map_to_grid_mod2(gl_FragCoord.y);
+    int x_t = map_to_grid_mod2(gl_FragCoord.x, origin.x);
+    int y_t = map_to_grid_mod2(gl_FragCoord.y, origin.y);
     if (bool(x_t ^ y_t ^ int(black_view)))
     {
         gl_FragColor = vec4(black_color, opacity);
@@ -26,4 +29,4 @@ void main()
     {
         gl_FragColor = vec4(white_color, opacity);
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,12 @@
+//! Board representation and legal move generation.
+//!
+//! Move lists (`Moves` values, `piece_moves`, `legal_moves_for`) are plain
+//! `Vec`s. The module still depends on `std` for `Board` and the `GameData`
+//! bookkeeping (castling rights, pawns that may double-push), which are
+//! `HashMap`/`HashSet`s, and for the pin and check-evasion lookups built
+//! during generation.
 #![allow(dead_code, unused_variables)]
+use crate::zobrist;
 use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
@@ -16,6 +24,30 @@ impl PieceColor {
     }
 }
 
+/// A side-to-move field that is neither `w` nor `b`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseColorError(pub String);
+
+impl std::fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected 'w' or 'b', found '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+/// Parses the FEN side to move, `"w"` or `"b"`.
+impl std::str::FromStr for PieceColor {
+    type Err = ParseColorError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "w" => Ok(PieceColor::White),
+            "b" => Ok(PieceColor::Black),
+            _ => Err(ParseColorError(s.to_string())),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum PieceType {
     King(PieceColor),
@@ -26,6 +58,8 @@ pub enum PieceType {
     Pawn(PieceColor),
 }
 
+/// The Unicode chess glyph, for pretty printing. Use [`PieceType::fen_char`]
+/// for text that has to be read back.
 impl std::fmt::Display for PieceType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -56,6 +90,59 @@ impl PieceType {
             | PieceType::Pawn(color) => color,
         }
     }
+
+    /// Material value in centipawns. The king, which is never traded, is 0.
+    pub fn value(&self) -> i32 {
+        match self {
+            PieceType::King(_) => 0,
+            PieceType::Queen(_) => 900,
+            PieceType::Bishop(_) => 330,
+            PieceType::Knight(_) => 320,
+            PieceType::Rook(_) => 500,
+            PieceType::Pawn(_) => 100,
+        }
+    }
+
+    /// The notation letter, uppercase for White and lowercase for Black as
+    /// in FEN.
+    pub fn letter(&self) -> char {
+        let c = match self {
+            PieceType::King(_) => 'k',
+            PieceType::Queen(_) => 'q',
+            PieceType::Bishop(_) => 'b',
+            PieceType::Knight(_) => 'n',
+            PieceType::Rook(_) => 'r',
+            PieceType::Pawn(_) => 'p',
+        };
+        match self.get_color() {
+            PieceColor::White => c.to_ascii_uppercase(),
+            PieceColor::Black => c,
+        }
+    }
+
+    /// The piece's FEN character, the same as [`PieceType::letter`]; unlike
+    /// `Display` it parses back with [`PieceType::from_letter`].
+    pub fn fen_char(&self) -> char {
+        self.letter()
+    }
+
+    /// The piece a `letter` stands for, taking the color from its case.
+    pub fn from_letter(c: char) -> Option<PieceType> {
+        let color = if c.is_ascii_uppercase() {
+            PieceColor::White
+        } else {
+            PieceColor::Black
+        };
+        match c.to_ascii_lowercase() {
+            'k' => Some(PieceType::King(color)),
+            'q' => Some(PieceType::Queen(color)),
+            'b' => Some(PieceType::Bishop(color)),
+            'n' => Some(PieceType::Knight(color)),
+            'r' => Some(PieceType::Rook(color)),
+            'p' => Some(PieceType::Pawn(color)),
+            _ => None,
+        }
+    }
 }
 const BOARD_SIZE: std::ops::Range<i8> = 0..8;
 
@@ -68,20 +155,53 @@ pub struct Castling {
     pub king_side: bool,
     pub queen_side: bool,
 }
-type Board = HashMap<Position, PieceType>;
+pub type Board = HashMap<Position, PieceType>;
 #[derive(Debug, Clone)]
 pub struct GameData {
     pub board: Board,
     pub castling: HashMap<PieceColor, Castling>,
     pub can_move_2_squares: HashSet<Position>,
     pub to_move: PieceColor,
+    /// The square a pawn landed on with a double push on the previous move.
+    /// FEN and UCI name the square it skipped instead, see
+    /// [`GameData::en_passant_target`].
     pub moved_2_squares: Option<Position>,
+    /// Zobrist hash of the position, kept up to date by `postprocess_move`.
+    /// Code that edits the other fields directly must recompute it with
+    /// [`zobrist::hash`].
+    pub hash: u64,
+    /// Plies since the last capture or pawn move, for the fifty-move rule.
+    pub halfmove_clock: u32,
+    /// Starts at 1 and goes up after each Black move, as in FEN.
+    pub fullmove_number: u32,
+}
+impl GameData {
+    /// The square behind a pawn that just moved two squares, where an en
+    /// passant capture would land. This is what the FEN en passant field holds.
+    pub fn en_passant_target(&self) -> Option<Position> {
+        self.moved_2_squares.map(|landing| Position {
+            y: if landing.y == 3 { 2 } else { 5 },
+            ..landing
+        })
+    }
+    /// Sets the en passant state from a target square (on the third or sixth
+    /// rank) as written in FEN.
+    pub fn set_en_passant_target(&mut self, target: Option<Position>) {
+        self.moved_2_squares = target.map(|target| Position {
+            y: if target.y == 2 { 3 } else { 4 },
+            ..target
+        });
+        self.hash = zobrist::hash(self);
+    }
 }
 impl std::fmt::Display for GameData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "To Move: {:?}", self.to_move)?;
         writeln!(f, "Castling: {:?}", self.castling)?;
-        writeln!(f, "En passant: {:?}", self.moved_2_squares)?;
+        match self.en_passant_target() {
+            Some(target) => writeln!(f, "En passant: {}", target.to_algebraic())?,
+            None => writeln!(f, "En passant: -")?,
+        }
         write!(f, " ")?;
         for i in BOARD_SIZE {
             write!(f, "{} ", i)?;
@@ -138,13 +258,18 @@ impl Default for GameData {
                 queen_side: true,
             },
         );
-        GameData {
+        let mut game_data = GameData {
             board,
             castling,
             can_move_2_squares,
             to_move: PieceColor::White,
             moved_2_squares: None,
-        }
+            hash: 0,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        };
+        game_data.hash = zobrist::hash(&game_data);
+        game_data
     }
 }
 fn generate_en_passant_moves(game_data: &GameData, moves: &mut Moves) {
@@ -173,8 +298,9 @@ fn generate_en_passant_moves(game_data: &GameData, moves: &mut Moves) {
             if !is_valid_chess_position(pawn_that_might_capture) {
                 continue;
             }
-            if let Some(&PieceType::Pawn(color)) = game_data.board.get(&pawn_that_might_capture) {
-                if opposite == game_data.to_move {
+            if let Some(&PieceType::Pawn(capturing)) = game_data.board.get(&pawn_that_might_capture)
+            {
+                if capturing == opposite && opposite == game_data.to_move {
                     let move_pos = Position {
                         x: moved_2_squares.x,
                         y: pawn_that_might_capture.y + y_modifier,
@@ -186,13 +312,10 @@ fn generate_en_passant_moves(game_data: &GameData, moves: &mut Moves) {
                     if !verify_board(game_data.to_move, &new_board) {
                         continue;
                     }
-                    if let Some(pawn_moves) = moves.get_mut(&pawn_that_might_capture) {
-                        pawn_moves.insert(move_pos);
-                    } else {
-                        let mut pawn_moves = HashSet::<Position>::new();
-                        pawn_moves.insert(move_pos);
-                        moves.insert(pawn_that_might_capture, pawn_moves);
-                    }
+                    moves
+                        .entry(pawn_that_might_capture)
+                        .or_default()
+                        .push(move_pos);
                 }
             }
         }
@@ -202,7 +325,7 @@ fn generate_en_passant_moves(game_data: &GameData, moves: &mut Moves) {
 fn generate_from_points(
     position: Position,
     board: &Board,
-    out: &mut HashSet<Position>,
+    out: &mut Vec<Position>,
     attack_positions: &[Position],
 ) {
     for &attack_position in attack_positions {
@@ -215,13 +338,13 @@ fn generate_from_points(
                 continue;
             }
         }
-        out.insert(attack_position);
+        out.push(attack_position);
     }
 }
 fn generate_generic_chunk(
     position: Position,
     board: &Board,
-    out: &mut HashSet<Position>,
+    out: &mut Vec<Position>,
     generator: impl Fn(Position, i8) -> Position,
 ) {
     for i in BOARD_SIZE {
@@ -231,15 +354,15 @@ fn generate_generic_chunk(
         }
         if let Some(&piece) = board.get(&attack_pos) {
             if piece.get_color() != board.get(&position).unwrap().get_color() {
-                out.insert(attack_pos);
+                out.push(attack_pos);
             }
             return;
         } else {
-            out.insert(attack_pos);
+            out.push(attack_pos);
         }
     }
 }
-fn generate_vertical_horizontal(position: Position, board: &Board, out: &mut HashSet<Position>) {
+fn generate_vertical_horizontal(position: Position, board: &Board, out: &mut Vec<Position>) {
     generate_generic_chunk(position, board, out, |pos, x| Position {
         x: pos.x - x - 1,
         ..pos
@@ -258,7 +381,7 @@ fn generate_vertical_horizontal(position: Position, board: &Board, out: &mut Has
         ..pos
     });
 }
-fn generate_cross(position: Position, board: &Board, out: &mut HashSet<Position>) {
+fn generate_cross(position: Position, board: &Board, out: &mut Vec<Position>) {
     generate_generic_chunk(position, board, out, |pos, x| Position {
         x: pos.x - x - 1,
         y: pos.y - x - 1,
@@ -278,11 +401,7 @@ fn generate_cross(position: Position, board: &Board, out: &mut HashSet<Position>
     });
 }
 
-fn generate_squares_under_attack_king(
-    board: &Board,
-    position: Position,
-    out: &mut HashSet<Position>,
-) {
+fn generate_squares_under_attack_king(board: &Board, position: Position, out: &mut Vec<Position>) {
     let king_color = board.get(&position).unwrap().get_color();
     for i in -1i8..2 {
         for j in -1i8..2 {
@@ -301,29 +420,25 @@ fn generate_squares_under_attack_king(
                     continue;
                 }
             }
-            out.insert(attack_position);
+            out.push(attack_position);
         }
     }
 }
-fn generate_squares_under_attack_queen(
-    board: &Board,
-    position: Position,
-    out: &mut HashSet<Position>,
-) {
+fn generate_squares_under_attack_queen(board: &Board, position: Position, out: &mut Vec<Position>) {
     generate_cross(position, board, out);
     generate_vertical_horizontal(position, board, out);
 }
 fn generate_squares_under_attack_bishop(
     board: &Board,
     position: Position,
-    out: &mut HashSet<Position>,
+    out: &mut Vec<Position>,
 ) {
     generate_cross(position, board, out);
 }
 fn generate_squares_under_attack_knight(
     board: &Board,
     position: Position,
-    out: &mut HashSet<Position>,
+    out: &mut Vec<Position>,
 ) {
     generate_from_points(
         position,
@@ -367,15 +482,16 @@ fn generate_squares_under_attack_knight(
         ],
     );
 }
-fn generate_squares_under_attack_rook(
+fn generate_squares_under_attack_rook(board: &Board, position: Position, out: &mut Vec<Position>) {
+    generate_vertical_horizontal(position, board, out);
+}
+fn generate_moves_pawn(
     board: &Board,
     position: Position,
-    out: &mut HashSet<Position>,
+    can_move_2_squares: bool,
+    out: &mut Vec<Position>,
 ) {
-    generate_vertical_horizontal(position, board, out);
-}
-fn generate_moves_pawn(game_data: &GameData, position: Position, out: &mut HashSet<Position>) {
-    let (move_pos, two_squares) = match game_data.board.get(&position).unwrap().get_color() {
+    let (move_pos, two_squares) = match board.get(&position).unwrap().get_color() {
         PieceColor::White => (
             Position {
                 y: position.y + 1,
@@ -397,28 +513,21 @@ fn generate_moves_pawn(game_data: &GameData, position: Position, out: &mut HashS
             },
         ),
     };
-    if !game_data.board.contains_key(&move_pos) {
-        out.insert(move_pos);
+    if !board.contains_key(&move_pos) {
+        out.push(move_pos);
     }
-    if game_data.can_move_2_squares.contains(&position)
-        && !game_data.board.contains_key(&two_squares)
-        && !game_data.board.contains_key(&move_pos)
-    {
-        out.insert(two_squares);
+    if can_move_2_squares && !board.contains_key(&two_squares) && !board.contains_key(&move_pos) {
+        out.push(two_squares);
     }
-    let mut attack_squares = HashSet::<Position>::new();
-    generate_squares_under_attack_pawn(&game_data.board, position, &mut attack_squares);
+    let mut attack_squares = Vec::new();
+    generate_squares_under_attack_pawn(board, position, &mut attack_squares);
     for attack_square in attack_squares {
-        if game_data.board.contains_key(&attack_square) {
-            out.insert(attack_square);
+        if board.contains_key(&attack_square) {
+            out.push(attack_square);
         }
     }
 }
-fn generate_squares_under_attack_pawn(
-    board: &Board,
-    position: Position,
-    out: &mut HashSet<Position>,
-) {
+fn generate_squares_under_attack_pawn(board: &Board, position: Position, out: &mut Vec<Position>) {
     let points = if board.get(&position).unwrap().get_color() == PieceColor::White {
         [
             Position {
@@ -444,10 +553,15 @@ fn generate_squares_under_attack_pawn(
     };
     generate_from_points(position, board, out, &points);
 }
-fn generate_default_moves(game_data: &GameData, position: Position, out: &mut HashSet<Position>) {
+fn generate_default_moves(game_data: &GameData, position: Position, out: &mut Vec<Position>) {
     if let Some(&piece) = game_data.board.get(&position) {
         match piece {
-            PieceType::Pawn(_) => generate_moves_pawn(game_data, position, out),
+            PieceType::Pawn(_) => generate_moves_pawn(
+                &game_data.board,
+                position,
+                game_data.can_move_2_squares.contains(&position),
+                out,
+            ),
             _ => generate_squares_under_attack_for_position(&game_data.board, position, out),
         }
     }
@@ -455,7 +569,7 @@ fn generate_default_moves(game_data: &GameData, position: Position, out: &mut Ha
 fn generate_squares_under_attack_for_position(
     board: &Board,
     position: Position,
-    out: &mut HashSet<Position>,
+    out: &mut Vec<Position>,
 ) {
     if let Some(&piece) = board.get(&position) {
         match piece {
@@ -469,6 +583,22 @@ fn generate_squares_under_attack_for_position(
     }
 }
 
+/// Pseudo-legal destinations of the piece on `pos`: moves that would leave
+/// its own king in check are kept. Castling and en passant depend on game
+/// history and are not included; pawns may double-push from their start rank.
+pub fn piece_moves(board: &Board, pos: Position) -> Vec<Position> {
+    let mut out = Vec::new();
+    match board.get(&pos) {
+        Some(&PieceType::Pawn(color)) => {
+            let start_rank = if color == PieceColor::White { 1 } else { 6 };
+            generate_moves_pawn(board, pos, pos.y == start_rank, &mut out);
+        }
+        Some(_) => generate_squares_under_attack_for_position(board, pos, &mut out),
+        None => {}
+    }
+    out
+}
+
 impl GameData {
     pub fn new() -> Self {
         Self {
@@ -477,10 +607,150 @@ impl GameData {
             can_move_2_squares: HashSet::new(),
             to_move: PieceColor::White,
             moved_2_squares: None,
+            hash: zobrist::side_key(PieceColor::White),
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        }
+    }
+
+    /// Plain-text board with rank 8 at the top: FEN letters for pieces and
+    /// `.` for empty squares, framed with rank numbers and file letters.
+    pub fn to_ascii(&self) -> String {
+        let mut out = String::from("  +-----------------+\n");
+        for y in BOARD_SIZE.rev() {
+            out.push_str(&format!("{} |", y + 1));
+            for x in BOARD_SIZE {
+                let square = self
+                    .board
+                    .get(&Position { x, y })
+                    .map_or('.', |piece| piece.letter());
+                out.push(' ');
+                out.push(square);
+            }
+            out.push_str(" |\n");
+        }
+        out.push_str("  +-----------------+\n");
+        out.push_str("    a b c d e f g h\n");
+        out
+    }
+
+    /// `generate_moves` written out for debugging: one line per piece that
+    /// can move, by square, with its FEN letter, move count and destinations
+    /// (`N b1 (2): a3 c3`), then the totals.
+    pub fn moves_report(&self) -> String {
+        let mut moves: Vec<(Position, Vec<Position>)> = generate_moves(self).into_iter().collect();
+        moves.sort();
+        let mut out = String::new();
+        let mut total = 0;
+        for (from, mut destinations) in moves {
+            destinations.sort();
+            total += destinations.len();
+            let destinations: Vec<String> = destinations
+                .into_iter()
+                .map(Position::to_algebraic)
+                .collect();
+            out.push_str(&format!(
+                "{} {} ({}): {}\n",
+                self.board[&from].letter(),
+                from.to_algebraic(),
+                destinations.len(),
+                destinations.join(" ")
+            ));
+        }
+        out.push_str(&format!(
+            "{total} moves from {} pieces\n",
+            out.lines().count()
+        ));
+        out
+    }
+
+    /// Puts `piece` on the square named like `"e4"`, or empties it for `None`.
+    /// Panics on a malformed square name.
+    pub fn set_piece(&mut self, square: &str, piece: Option<PieceType>) {
+        let pos =
+            Position::from_algebraic(square).unwrap_or_else(|| panic!("invalid square '{square}'"));
+        match piece {
+            Some(piece) => self.board.insert(pos, piece),
+            None => self.board.remove(&pos),
+        };
+        self.hash = zobrist::hash(self);
+    }
+}
+/// The same position seen from the other side: the board flipped top to
+/// bottom with every piece changing color, the other side to move, and the
+/// castling rights and en passant square following along. An evaluation that
+/// treats both colors alike scores it as the negation of the original.
+pub fn mirror_position(game_data: &GameData) -> GameData {
+    let flip = |pos: Position| Position {
+        y: 7 - pos.y,
+        ..pos
+    };
+    let swap_color = |piece: PieceType| {
+        let color = piece.get_color().get_opposite();
+        match piece {
+            PieceType::King(_) => PieceType::King(color),
+            PieceType::Queen(_) => PieceType::Queen(color),
+            PieceType::Bishop(_) => PieceType::Bishop(color),
+            PieceType::Knight(_) => PieceType::Knight(color),
+            PieceType::Rook(_) => PieceType::Rook(color),
+            PieceType::Pawn(_) => PieceType::Pawn(color),
         }
+    };
+    let mut mirrored = GameData {
+        board: game_data
+            .board
+            .iter()
+            .map(|(&pos, &piece)| (flip(pos), swap_color(piece)))
+            .collect(),
+        castling: game_data
+            .castling
+            .iter()
+            .map(|(color, &rights)| (color.get_opposite(), rights))
+            .collect(),
+        can_move_2_squares: game_data
+            .can_move_2_squares
+            .iter()
+            .copied()
+            .map(flip)
+            .collect(),
+        to_move: game_data.to_move.get_opposite(),
+        moved_2_squares: game_data.moved_2_squares.map(flip),
+        hash: 0,
+        halfmove_clock: game_data.halfmove_clock,
+        fullmove_number: game_data.fullmove_number,
+    };
+    mirrored.hash = zobrist::hash(&mirrored);
+    mirrored
+}
+/// The piece on the square named like `"e4"`; `None` for an empty square or a
+/// malformed name.
+pub fn piece_at(game_data: &GameData, square: &str) -> Option<PieceType> {
+    let pos = Position::from_algebraic(square)?;
+    game_data.board.get(&pos).copied()
+}
+/// Exact, collision-free key for the piece placement: one nibble per square,
+/// a1 first, with 0 for an empty square and 1..=12 for the pieces.
+pub fn board_signature(board: &Board) -> [u8; 32] {
+    let mut signature = [0u8; 32];
+    for (&pos, &piece) in board {
+        let code = match piece {
+            PieceType::King(_) => 1,
+            PieceType::Queen(_) => 2,
+            PieceType::Bishop(_) => 3,
+            PieceType::Knight(_) => 4,
+            PieceType::Rook(_) => 5,
+            PieceType::Pawn(_) => 6,
+        } + if piece.get_color() == PieceColor::Black {
+            6
+        } else {
+            0
+        };
+        let index = (pos.y * 8 + pos.x) as usize;
+        signature[index / 2] |= code << (4 * (index % 2));
     }
+    signature
 }
-pub fn collect_kings(board: &Board) -> HashMap<PieceColor, Position> {
+pub(crate) fn collect_kings(board: &Board) -> HashMap<PieceColor, Position> {
     board
         .iter()
         .filter(|(_, &piece_type)| matches!(piece_type, PieceType::King(_)))
@@ -488,16 +758,330 @@ pub fn collect_kings(board: &Board) -> HashMap<PieceColor, Position> {
         .collect()
 }
 
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (-2, 1),
+    (-2, -1),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (1, 2),
+    (-1, -2),
+    (-1, 2),
+];
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+const ORTHOGONAL_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const DIAGONAL_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const QUEEN_DIRECTIONS: [(i8, i8); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+/// Whether any piece of color `by` attacks `square`. Scans outward from the
+/// square and returns on the first attacker, so an occupied square counts as
+/// attacked whatever color stands on it (i.e. it also answers "is it defended").
+pub fn is_square_attacked(board: &Board, square: Position, by: PieceColor) -> bool {
+    let piece_at = |(dx, dy): (i8, i8)| {
+        board
+            .get(&Position {
+                x: square.x + dx,
+                y: square.y + dy,
+            })
+            .copied()
+    };
+    if KNIGHT_OFFSETS
+        .into_iter()
+        .any(|offset| piece_at(offset) == Some(PieceType::Knight(by)))
+    {
+        return true;
+    }
+    if KING_OFFSETS
+        .into_iter()
+        .any(|offset| piece_at(offset) == Some(PieceType::King(by)))
+    {
+        return true;
+    }
+    // pawns attack forward, so look one rank back from their point of view
+    let pawn_dy = if by == PieceColor::White { -1 } else { 1 };
+    if [(-1, pawn_dy), (1, pawn_dy)]
+        .into_iter()
+        .any(|offset| piece_at(offset) == Some(PieceType::Pawn(by)))
+    {
+        return true;
+    }
+    let slider_attacks = |directions: [(i8, i8); 4], slider: PieceType| {
+        directions.into_iter().any(|(dx, dy)| {
+            let mut pos = Position {
+                x: square.x + dx,
+                y: square.y + dy,
+            };
+            while is_valid_chess_position(pos) {
+                if let Some(&piece) = board.get(&pos) {
+                    return piece == slider || piece == PieceType::Queen(by);
+                }
+                pos = Position {
+                    x: pos.x + dx,
+                    y: pos.y + dy,
+                };
+            }
+            false
+        })
+    };
+    slider_attacks(ORTHOGONAL_DIRECTIONS, PieceType::Rook(by))
+        || slider_attacks(DIAGONAL_DIRECTIONS, PieceType::Bishop(by))
+}
+
+/// Every square the piece on `pos` controls: what it attacks plus the squares
+/// of friendly pieces it protects, which the attack generators leave out. A
+/// slider's ray ends on the first piece it meets, whatever its color. Empty
+/// when `pos` is.
+pub fn defended_squares(board: &Board, pos: Position) -> HashSet<Position> {
+    let mut squares = HashSet::new();
+    let Some(&piece) = board.get(&pos) else {
+        return squares;
+    };
+    let pawn_dy = if piece.get_color() == PieceColor::White {
+        1
+    } else {
+        -1
+    };
+    let (directions, slides): (Vec<(i8, i8)>, bool) = match piece {
+        PieceType::King(_) => (KING_OFFSETS.to_vec(), false),
+        PieceType::Knight(_) => (KNIGHT_OFFSETS.to_vec(), false),
+        PieceType::Pawn(_) => (vec![(-1, pawn_dy), (1, pawn_dy)], false),
+        PieceType::Rook(_) => (ORTHOGONAL_DIRECTIONS.to_vec(), true),
+        PieceType::Bishop(_) => (DIAGONAL_DIRECTIONS.to_vec(), true),
+        PieceType::Queen(_) => ([ORTHOGONAL_DIRECTIONS, DIAGONAL_DIRECTIONS].concat(), true),
+    };
+    for (dx, dy) in directions {
+        let mut square = pos;
+        loop {
+            square = Position {
+                x: square.x + dx,
+                y: square.y + dy,
+            };
+            if !is_valid_chess_position(square) {
+                break;
+            }
+            squares.insert(square);
+            if !slides || board.contains_key(&square) {
+                break;
+            }
+        }
+    }
+    squares
+}
+
+/// `defended_squares` as a bitboard, see [`Position::bit_index`]. Masks of
+/// several pieces combine with `|` where the sets would need merging.
+pub fn attacks_bitboard(board: &Board, pos: Position) -> u64 {
+    let Some(&piece) = board.get(&pos) else {
+        return 0;
+    };
+    let pawn_dy = if piece.get_color() == PieceColor::White {
+        1
+    } else {
+        -1
+    };
+    let (directions, slides): (&[(i8, i8)], bool) = match piece {
+        PieceType::King(_) => (&KING_OFFSETS, false),
+        PieceType::Knight(_) => (&KNIGHT_OFFSETS, false),
+        PieceType::Pawn(_) => (&[(-1, pawn_dy), (1, pawn_dy)], false),
+        PieceType::Rook(_) => (&ORTHOGONAL_DIRECTIONS, true),
+        PieceType::Bishop(_) => (&DIAGONAL_DIRECTIONS, true),
+        PieceType::Queen(_) => (&QUEEN_DIRECTIONS, true),
+    };
+    let mut mask = 0;
+    for &(dx, dy) in directions {
+        let mut square = pos;
+        loop {
+            square = Position {
+                x: square.x + dx,
+                y: square.y + dy,
+            };
+            if !is_valid_chess_position(square) {
+                break;
+            }
+            mask |= square.bit();
+            if !slides || board.contains_key(&square) {
+                break;
+            }
+        }
+    }
+    mask
+}
+
+/// Every square a piece of `color` controls, own pieces included.
+pub fn side_attacks_bitboard(board: &Board, color: PieceColor) -> u64 {
+    board
+        .iter()
+        .filter(|(_, piece)| piece.get_color() == color)
+        .fold(0, |mask, (&pos, _)| mask | attacks_bitboard(board, pos))
+}
+
+fn find_king(board: &Board, color: PieceColor) -> Option<Position> {
+    board
+        .iter()
+        .find(|(_, &piece_type)| piece_type == PieceType::King(color))
+        .map(|(&position, _)| position)
+}
+
+/// Whether `to_move`'s king is safe on `new_board`. The move generators rely
+/// on the side to move having a king, which every position that passed
+/// `validate_position` has; `generate_moves_checked` tests it first.
 fn verify_board(to_move: PieceColor, new_board: &Board) -> bool {
-    let king = *collect_kings(&new_board).get(&to_move).unwrap();
-    let mut squares_under_attack = HashSet::<Position>::new();
-    generate_squares_under_attack_for_side(
-        &new_board,
-        to_move.get_opposite(),
-        &mut squares_under_attack,
-    );
-    !squares_under_attack.contains(&king)
+    let king = find_king(new_board, to_move).expect("the side to move has a king");
+    !is_square_attacked(new_board, king, to_move.get_opposite())
+}
+/// Squares a pinned piece may still move to: the line from its king up to and
+/// including the pinning piece.
+pub(crate) type Ray = HashSet<Position>;
+
+fn slides_along(piece: PieceType, (dx, dy): (i8, i8)) -> bool {
+    match piece {
+        PieceType::Queen(_) => true,
+        PieceType::Rook(_) => dx == 0 || dy == 0,
+        PieceType::Bishop(_) => dx != 0 && dy != 0,
+        _ => false,
+    }
+}
+
+/// Pieces of `color` pinned to their king on `king`, each with the ray it is
+/// confined to.
+pub(crate) fn compute_pins(
+    board: &Board,
+    king: Position,
+    color: PieceColor,
+) -> HashMap<Position, Ray> {
+    let mut pins = HashMap::new();
+    for direction in ORTHOGONAL_DIRECTIONS.into_iter().chain(DIAGONAL_DIRECTIONS) {
+        let mut ray = Ray::new();
+        let mut pinned = None;
+        let mut pos = king;
+        loop {
+            pos = Position {
+                x: pos.x + direction.0,
+                y: pos.y + direction.1,
+            };
+            if !is_valid_chess_position(pos) {
+                break;
+            }
+            ray.insert(pos);
+            let Some(&piece) = board.get(&pos) else {
+                continue;
+            };
+            if piece.get_color() == color {
+                if pinned.is_some() {
+                    break;
+                }
+                pinned = Some(pos);
+                continue;
+            }
+            if let Some(pinned) = pinned {
+                if slides_along(piece, direction) {
+                    pins.insert(pinned, ray);
+                }
+            }
+            break;
+        }
+    }
+    pins
+}
+
+/// The squares strictly between `a` and `b`, walking from `a`, when they
+/// share a rank, file or diagonal; empty otherwise and for neighbours.
+pub fn squares_between(a: Position, b: Position) -> Vec<Position> {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    if (dx, dy) == (0, 0) || (dx != 0 && dy != 0 && dx.abs() != dy.abs()) {
+        return Vec::new();
+    }
+    let (step_x, step_y) = (dx.signum(), dy.signum());
+    let steps = dx.abs().max(dy.abs());
+    (1..steps)
+        .map(|i| Position {
+            x: a.x + i * step_x,
+            y: a.y + i * step_y,
+        })
+        .collect()
+}
+
+/// One entry per piece giving check to `color`'s king on `king`: the squares
+/// from next to the king out to the checker, which comes last.
+fn checking_lines(board: &Board, king: Position, color: PieceColor) -> Vec<Vec<Position>> {
+    let by = color.get_opposite();
+    let mut checkers = Vec::new();
+    for (dx, dy) in KNIGHT_OFFSETS {
+        let pos = Position {
+            x: king.x + dx,
+            y: king.y + dy,
+        };
+        if board.get(&pos) == Some(&PieceType::Knight(by)) {
+            checkers.push(vec![pos]);
+        }
+    }
+    let pawn_dy = if color == PieceColor::White { 1 } else { -1 };
+    for dx in [-1, 1] {
+        let pos = Position {
+            x: king.x + dx,
+            y: king.y + pawn_dy,
+        };
+        if board.get(&pos) == Some(&PieceType::Pawn(by)) {
+            checkers.push(vec![pos]);
+        }
+    }
+    for direction in ORTHOGONAL_DIRECTIONS.into_iter().chain(DIAGONAL_DIRECTIONS) {
+        let mut line = Vec::new();
+        let mut pos = king;
+        loop {
+            pos = Position {
+                x: pos.x + direction.0,
+                y: pos.y + direction.1,
+            };
+            if !is_valid_chess_position(pos) {
+                break;
+            }
+            line.push(pos);
+            if let Some(&piece) = board.get(&pos) {
+                if piece.get_color() == by && slides_along(piece, direction) {
+                    checkers.push(line);
+                }
+                break;
+            }
+        }
+    }
+    checkers
+}
+
+/// Squares a non-king move must land on to resolve check: the checker itself
+/// or, for a slider, any square between it and the king. `None` when not in
+/// check; an empty set on double check, where only the king may move.
+fn check_evasion_squares(
+    board: &Board,
+    king: Position,
+    color: PieceColor,
+) -> Option<HashSet<Position>> {
+    let mut checkers = checking_lines(board, king, color);
+    match checkers.len() {
+        0 => None,
+        1 => Some(checkers.pop().unwrap().into_iter().collect()),
+        _ => Some(HashSet::new()),
+    }
 }
+
 fn try_make_move(game_data: &GameData, start: Position, end: Position) -> bool {
     let mut new_board = game_data.board.clone();
     let moving_piece = new_board.remove(&start).unwrap();
@@ -505,16 +1089,29 @@ fn try_make_move(game_data: &GameData, start: Position, end: Position) -> bool {
     verify_board(game_data.to_move, &new_board)
 }
 fn generate_normal_default_moves(game_data: &GameData, moves: &mut Moves) {
+    let king = find_king(&game_data.board, game_data.to_move).expect("the side to move has a king");
+    let pins = compute_pins(&game_data.board, king, game_data.to_move);
+    let evasions = check_evasion_squares(&game_data.board, king, game_data.to_move);
     for (&piece_pos, &piece_type) in game_data.board.iter() {
         if piece_type.get_color() != game_data.to_move {
             continue;
         }
-        let mut piece_moves = HashSet::<Position>::new();
-        generate_default_moves(&game_data, piece_pos, &mut piece_moves);
-        let mut valid_moves = HashSet::<Position>::new();
-        for &piece_move in piece_moves.iter() {
-            if try_make_move(&game_data, piece_pos, piece_move) {
-                valid_moves.insert(piece_move);
+        let mut piece_moves = Vec::new();
+        generate_default_moves(game_data, piece_pos, &mut piece_moves);
+        let mut valid_moves = Vec::new();
+        for piece_move in piece_moves {
+            // the king can walk into attacks, so it still needs the full check
+            let valid = if piece_pos == king {
+                try_make_move(game_data, piece_pos, piece_move)
+            } else {
+                pins.get(&piece_pos)
+                    .is_none_or(|ray| ray.contains(&piece_move))
+                    && evasions
+                        .as_ref()
+                        .is_none_or(|squares| squares.contains(&piece_move))
+            };
+            if valid {
+                valid_moves.push(piece_move);
             }
         }
         if !valid_moves.is_empty() {
@@ -529,91 +1126,83 @@ fn generate_squares_under_attack_for_side(
 ) {
     for (&position, &piece_type) in board.iter() {
         if piece_type.get_color() == to_move {
-            generate_squares_under_attack_for_position(&board, position, out);
+            let mut squares = Vec::new();
+            generate_squares_under_attack_for_position(board, position, &mut squares);
+            out.extend(squares);
         }
     }
 }
-fn castling_common(
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CastleSide {
+    KingSide,
+    QueenSide,
+}
+
+/// Whether `color`'s king on `king_pos` could castle to `side` on this board:
+/// the rook is in its corner, the squares between them are empty and the king
+/// doesn't pass through or land on an attacked square.
+fn castle_path_clear(
     board: &Board,
     king_pos: Position,
-    rook_pos: Position,
-    final_king_pos: Position,
-    final_rook_pos: Position,
-    must_be_empty: &[Position],
-    must_not_be_attacked: &[Position],
-    attack_squares: &HashSet<Position>,
-    moves: &mut Moves,
-) {
-    let empty_checker = |pos| board.contains_key(pos);
-    let under_attack_checker = |pos| attack_squares.contains(pos);
-    if must_be_empty.iter().any(empty_checker)
-        || must_not_be_attacked.iter().any(under_attack_checker)
-    {
-        return;
-    }
+    side: CastleSide,
+    color: PieceColor,
+) -> bool {
+    let (rook_x, must_not_be_attacked): (i8, &[i8]) = match side {
+        CastleSide::KingSide => (7, &[5, 6]),
+        CastleSide::QueenSide => (0, &[2, 3]),
+    };
+    let on_rank = |x: i8| Position { x, ..king_pos };
+    board.get(&on_rank(rook_x)) == Some(&PieceType::Rook(color))
+        && !squares_between(king_pos, on_rank(rook_x))
+            .iter()
+            .any(|square| board.contains_key(square))
+        && !must_not_be_attacked
+            .iter()
+            .any(|&x| is_square_attacked(board, on_rank(x), color.get_opposite()))
+}
 
-    if let Some(king_moves) = moves.get_mut(&king_pos) {
-        king_moves.insert(final_king_pos);
-    } else {
-        let mut king_moves = HashSet::<Position>::new();
-        king_moves.insert(final_king_pos);
-        moves.insert(king_pos, king_moves);
+/// The castles the side to move can play right now: the right hasn't been
+/// lost, the king isn't in check and the way is clear.
+pub fn available_castles(game_data: &GameData) -> Vec<CastleSide> {
+    let color = game_data.to_move;
+    let Some(&castling) = game_data.castling.get(&color) else {
+        return Vec::new();
+    };
+    let Some(king_pos) = find_king(&game_data.board, color) else {
+        return Vec::new();
+    };
+    if is_square_attacked(&game_data.board, king_pos, color.get_opposite()) {
+        return Vec::new();
     }
+    [
+        (CastleSide::KingSide, castling.king_side),
+        (CastleSide::QueenSide, castling.queen_side),
+    ]
+    .into_iter()
+    .filter(|&(side, allowed)| {
+        allowed && castle_path_clear(&game_data.board, king_pos, side, color)
+    })
+    .map(|(side, _)| side)
+    .collect()
 }
-fn generate_castling_moves(game_data: &GameData, moves: &mut Moves) {
-    let castling = game_data.castling.get(&game_data.to_move);
-    if castling.is_none() {
-        return;
-    }
-    let castling = *castling.unwrap();
-    let king_pos = *collect_kings(&game_data.board)
-        .get(&game_data.to_move)
-        .unwrap();
 
-    let mut attack_squares = HashSet::<Position>::new();
-    generate_squares_under_attack_for_side(
-        &game_data.board,
-        game_data.to_move.get_opposite(),
-        &mut attack_squares,
-    );
-
-    if attack_squares.contains(&king_pos) {
-        return;
-    }
-    if castling.king_side {
-        let move_path = [Position { x: 6, ..king_pos }, Position { x: 5, ..king_pos }];
-        castling_common(
-            &game_data.board,
-            king_pos,
-            Position { x: 7, ..king_pos },
-            Position { x: 6, ..king_pos },
-            Position { x: 5, ..king_pos },
-            &move_path,
-            &move_path,
-            &attack_squares,
-            moves,
-        );
-    }
-    if castling.queen_side {
-        let move_path = [
-            Position { x: 1, ..king_pos },
-            Position { x: 2, ..king_pos },
-            Position { x: 3, ..king_pos },
-        ];
-        castling_common(
-            &game_data.board,
-            king_pos,
-            Position { x: 7, ..king_pos },
-            Position { x: 2, ..king_pos },
-            Position { x: 3, ..king_pos },
-            &move_path,
-            &move_path[1..],
-            &attack_squares,
-            moves,
-        );
+fn generate_castling_moves(game_data: &GameData, moves: &mut Moves) {
+    for side in available_castles(game_data) {
+        // available_castles found the king already
+        let king_pos = find_king(&game_data.board, game_data.to_move).unwrap();
+        let x = match side {
+            CastleSide::KingSide => 6,
+            CastleSide::QueenSide => 2,
+        };
+        moves
+            .entry(king_pos)
+            .or_default()
+            .push(Position { x, ..king_pos });
     }
 }
 
+/// Every legal move of the side to move, by origin square. Panics when the
+/// side to move has no king; see `generate_moves_checked`.
 pub fn generate_moves(game_data: &GameData) -> Moves {
     let mut moves = Moves::new();
     generate_normal_default_moves(game_data, &mut moves);
@@ -621,6 +1210,78 @@ pub fn generate_moves(game_data: &GameData) -> Moves {
     generate_castling_moves(game_data, &mut moves);
     moves
 }
+/// `generate_moves` for positions nobody validated: a missing king is an
+/// error instead of a panic.
+pub fn generate_moves_checked(game_data: &GameData) -> Result<Moves, PositionError> {
+    if find_king(&game_data.board, game_data.to_move).is_none() {
+        return Err(PositionError::KingCount(game_data.to_move, 0));
+    }
+    Ok(generate_moves(game_data))
+}
+/// Legal destinations of the piece on `from` only, including castling and en
+/// passant. Empty when the square is empty or not the side to move's.
+pub fn legal_moves_for(game_data: &GameData, from: Position) -> Vec<Position> {
+    if game_data
+        .board
+        .get(&from)
+        .is_none_or(|piece| piece.get_color() != game_data.to_move)
+    {
+        return Vec::new();
+    }
+    let mut out = Vec::new();
+    generate_default_moves(game_data, from, &mut out);
+    out.retain(|&to| try_make_move(game_data, from, to));
+    let mut special = Moves::new();
+    generate_en_passant_moves(game_data, &mut special);
+    generate_castling_moves(game_data, &mut special);
+    out.extend(special.remove(&from).unwrap_or_default());
+    out
+}
+/// Whether moving the piece on `from` to `to` is legal, including castling and
+/// en passant. Only that piece's moves are generated, which makes this much
+/// cheaper than searching `generate_moves` when the intended move is known.
+pub fn is_legal_move(game_data: &GameData, from: Position, to: Position) -> bool {
+    if game_data
+        .board
+        .get(&from)
+        .is_none_or(|piece| piece.get_color() != game_data.to_move)
+    {
+        return false;
+    }
+    let mut pseudo_legal = Vec::new();
+    generate_default_moves(game_data, from, &mut pseudo_legal);
+    if pseudo_legal.contains(&to) {
+        return try_make_move(game_data, from, to);
+    }
+    let mut special = Moves::new();
+    generate_en_passant_moves(game_data, &mut special);
+    generate_castling_moves(game_data, &mut special);
+    special
+        .get(&from)
+        .is_some_and(|destinations| destinations.contains(&to))
+}
+/// Whether the side to move has any legal move, stopping at the first one
+/// found instead of generating them all. Castling isn't tried: when it is
+/// legal, so is the king's step towards the rook.
+pub fn has_legal_moves(game_data: &GameData) -> bool {
+    let mut destinations = Vec::new();
+    for (&from, &piece) in game_data.board.iter() {
+        if piece.get_color() != game_data.to_move {
+            continue;
+        }
+        destinations.clear();
+        generate_default_moves(game_data, from, &mut destinations);
+        if destinations
+            .iter()
+            .any(|&to| try_make_move(game_data, from, to))
+        {
+            return true;
+        }
+    }
+    let mut en_passant = Moves::new();
+    generate_en_passant_moves(game_data, &mut en_passant);
+    !en_passant.is_empty()
+}
 pub fn postprocess_move(
     game_data: &GameData,
     start: Position,
@@ -628,8 +1289,16 @@ pub fn postprocess_move(
 ) -> (GameData, Option<Position>) {
     let mut new_game_data = game_data.clone();
     let moving_piece = new_game_data.board.remove(&start).unwrap();
+    let mut hash = game_data.hash
+        ^ zobrist::castling_key(&game_data.castling)
+        ^ zobrist::en_passant_key(game_data)
+        ^ zobrist::piece_key(moving_piece, start);
     new_game_data.moved_2_squares = None;
     let mut to_be_promoted = None;
+    new_game_data.halfmove_clock = new_game_data.halfmove_clock.saturating_add(1);
+    if game_data.to_move == PieceColor::Black {
+        new_game_data.fullmove_number = new_game_data.fullmove_number.saturating_add(1);
+    }
     // castling
     if matches!(moving_piece, PieceType::King(_)) {
         new_game_data.castling.remove(&game_data.to_move);
@@ -639,45 +1308,36 @@ pub fn postprocess_move(
                     .board
                     .remove(&Position { x: 7, ..end })
                     .unwrap();
-                new_game_data.board.insert(
-                    Position {
-                        x: end.x - 1,
-                        ..end
-                    },
-                    rook,
-                );
+                let rook_end = Position {
+                    x: end.x - 1,
+                    ..end
+                };
+                new_game_data.board.insert(rook_end, rook);
+                hash ^= zobrist::piece_key(rook, Position { x: 7, ..end })
+                    ^ zobrist::piece_key(rook, rook_end);
             } else {
                 let rook = new_game_data
                     .board
                     .remove(&Position { x: 0, ..end })
                     .unwrap();
-                new_game_data.board.insert(
-                    Position {
-                        x: end.x + 1,
-                        ..end
-                    },
-                    rook,
-                );
-            }
-        }
-    }
-    else if matches!(moving_piece, PieceType::Rook(_))
-    {
-        if let Some(castling) = new_game_data.castling.get_mut(&moving_piece.get_color()) {
-            if start.x == 0 {
-                castling.queen_side = false;
-            }
-            else {
-                castling.king_side = false;
+                let rook_end = Position {
+                    x: end.x + 1,
+                    ..end
+                };
+                new_game_data.board.insert(rook_end, rook);
+                hash ^= zobrist::piece_key(rook, Position { x: 0, ..end })
+                    ^ zobrist::piece_key(rook, rook_end);
             }
         }
     }
     // en passant
     else if matches!(moving_piece, PieceType::Pawn(_)) {
+        new_game_data.halfmove_clock = 0;
         new_game_data.can_move_2_squares.remove(&start);
-        if let Some(en_passant) = game_data.moved_2_squares {
-            if en_passant.x == end.x && start.y == en_passant.y {
-                new_game_data.board.remove(&en_passant);
+        if game_data.en_passant_target() == Some(end) {
+            let captured_square = game_data.moved_2_squares.unwrap();
+            if let Some(captured) = new_game_data.board.remove(&captured_square) {
+                hash ^= zobrist::piece_key(captured, captured_square);
             }
         } else if (start.y - end.y).abs() == 2 {
             new_game_data.moved_2_squares = Some(end);
@@ -686,54 +1346,743 @@ pub fn postprocess_move(
             to_be_promoted = Some(end);
         }
     }
-    new_game_data.board.insert(end, moving_piece);
+    if let Some(captured) = new_game_data.board.insert(end, moving_piece) {
+        hash ^= zobrist::piece_key(captured, end);
+        new_game_data.halfmove_clock = 0;
+        // a pawn taken on its starting square never made its double push, and
+        // the piece taking it mustn't inherit it
+        new_game_data.can_move_2_squares.remove(&end);
+    }
+    // a rook leaving its corner, or taken on it, ends castling on that side
+    forfeit_corner(&mut new_game_data.castling, start);
+    forfeit_corner(&mut new_game_data.castling, end);
     new_game_data.to_move = new_game_data.to_move.get_opposite();
+    new_game_data.hash = hash
+        ^ zobrist::piece_key(moving_piece, end)
+        ^ zobrist::castling_key(&new_game_data.castling)
+        ^ zobrist::en_passant_key(&new_game_data)
+        // the other side is to move
+        ^ zobrist::side_key(PieceColor::White)
+        ^ zobrist::side_key(PieceColor::Black);
     // TODO: fill with all after effects
     (new_game_data, to_be_promoted)
 }
 
-#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, Default)]
-pub struct Position {
-    pub x: i8,
-    pub y: i8,
+/// Clears the castling right that needs a rook on `square`, if it is a corner.
+fn forfeit_corner(castling: &mut HashMap<PieceColor, Castling>, square: Position) {
+    let color = match square.y {
+        0 => PieceColor::White,
+        7 => PieceColor::Black,
+        _ => return,
+    };
+    if let Some(rights) = castling.get_mut(&color) {
+        match square.x {
+            0 => rights.queen_side = false,
+            7 => rights.king_side = false,
+            _ => {}
+        }
+    }
 }
 
-pub type Moves = HashMap<Position, HashSet<Position>>;
+/// Applies a single move, including the promotion choice. A pawn reaching the
+/// last rank without an explicit `promotion` becomes a queen. The move is
+/// trusted to be legal and panics when its origin is empty; untrusted moves
+/// go through `apply_move_checked`.
+pub fn apply_move(game_data: &GameData, mv: &Move) -> GameData {
+    let (mut new_game_data, to_be_promoted) = postprocess_move(game_data, mv.from, mv.to);
+    if let Some(promotion_pos) = to_be_promoted {
+        let piece = mv.promotion.unwrap_or(PieceType::Queen(game_data.to_move));
+        let pawn = new_game_data.board.insert(promotion_pos, piece).unwrap();
+        new_game_data.hash ^=
+            zobrist::piece_key(pawn, promotion_pos) ^ zobrist::piece_key(piece, promotion_pos);
+    }
+    new_game_data
+}
 
-#[test]
-fn generate_en_passant_move_1_right() {
-    let mut board: Board = HashMap::new();
-    let to_move = PieceColor::White;
-    board.insert(Position { x: 0, y: 0 }, PieceType::King(to_move));
-    let moved_2_squares = Position { x: 7, y: 4 };
-    board.insert(moved_2_squares, PieceType::Pawn(to_move.get_opposite()));
-    let right_pos = Position { x: 6, y: 4 };
-    board.insert(right_pos, PieceType::Pawn(to_move));
-    let game_data = GameData {
-        board,
-        castling: HashMap::new(),
-        can_move_2_squares: HashSet::new(),
-        to_move,
-        moved_2_squares: Some(moved_2_squares),
-    };
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    /// No piece of the side to move stands on the origin square.
+    NoPieceToMove(Position),
+    /// The move would leave the mover's own king attacked.
+    WouldExposeKing,
+    /// The piece can't make this move, or the promotion doesn't fit it.
+    Illegal(Move),
+}
 
-    let mut moves = Moves::new();
-    generate_en_passant_moves(&game_data, &mut moves);
-    println!("{game_data}");
-    assert_eq!(
-        vec![Position { x: 7, y: 5 }],
-        moves
-            .get(&right_pos)
-            .unwrap()
-            .iter()
-            .cloned()
-            .collect::<Vec::<Position>>()
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::NoPieceToMove(pos) => {
+                write!(f, "no piece of the side to move on {}", pos.to_algebraic())
+            }
+            MoveError::WouldExposeKing => write!(f, "move leaves the king in check"),
+            MoveError::Illegal(mv) => write!(f, "illegal move {}", move_to_uci(mv)),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// Whether `mv` takes a pawn to the last rank.
+pub fn promotes(game_data: &GameData, mv: &Move) -> bool {
+    matches!(game_data.board.get(&mv.from), Some(PieceType::Pawn(_)))
+        && (mv.to.y == 0 || mv.to.y == 7)
+}
+
+/// Whether the promotion piece `mv` names, if any, may be chosen: a queen,
+/// rook, bishop or knight of the side to move, on a move that promotes.
+pub fn promotion_fits(game_data: &GameData, mv: &Move) -> bool {
+    match mv.promotion {
+        None => true,
+        Some(PieceType::King(_) | PieceType::Pawn(_)) => false,
+        Some(promotion) => promotes(game_data, mv) && promotion.get_color() == game_data.to_move,
+    }
+}
+
+/// `apply_move` for moves from untrusted input. The move is judged on the
+/// position before anything is played, so a move the piece can't make is an
+/// error rather than a panic, and one it could make but for its king is told
+/// apart from the rest.
+pub fn apply_move_checked(game_data: &GameData, mv: &Move) -> Result<GameData, MoveError> {
+    if game_data
+        .board
+        .get(&mv.from)
+        .is_none_or(|piece| piece.get_color() != game_data.to_move)
+    {
+        return Err(MoveError::NoPieceToMove(mv.from));
+    }
+    if !promotion_fits(game_data, mv) {
+        return Err(MoveError::Illegal(*mv));
+    }
+    if !is_legal_move(game_data, mv.from, mv.to) {
+        let mut pseudo_legal = Vec::new();
+        generate_default_moves(game_data, mv.from, &mut pseudo_legal);
+        return Err(if pseudo_legal.contains(&mv.to) {
+            MoveError::WouldExposeKing
+        } else {
+            MoveError::Illegal(*mv)
+        });
+    }
+    Ok(apply_move(game_data, mv))
+}
+
+/// Legal captures of the side to move, en passant included, sorted like
+/// `legal_moves_flat`. Only captures are checked for king safety, which makes
+/// this much cheaper than filtering the full move list. A capture onto the
+/// last rank is a queen promotion, as in `legal_moves_flat`.
+pub fn capture_moves(game_data: &GameData) -> Vec<Move> {
+    let mut captures = first_captures(game_data, usize::MAX);
+    captures.sort_by_key(|mv| (mv.from, mv.to));
+    captures
+}
+
+/// Whether the side to move is out of check and has nothing to take, so a
+/// static evaluation of the position isn't about to be overturned by an
+/// exchange.
+pub fn is_quiet(game_data: &GameData) -> bool {
+    !is_in_check(game_data) && first_captures(game_data, 1).is_empty()
+}
+
+/// Up to `limit` legal captures, in no particular order.
+fn first_captures(game_data: &GameData, limit: usize) -> Vec<Move> {
+    let mut captures = Vec::new();
+    let mut destinations = Vec::new();
+    for (&from, &piece) in game_data.board.iter() {
+        if piece.get_color() != game_data.to_move {
+            continue;
+        }
+        destinations.clear();
+        generate_default_moves(game_data, from, &mut destinations);
+        // the generators never land on a friendly piece
+        for &to in &destinations {
+            if game_data.board.contains_key(&to) && try_make_move(game_data, from, to) {
+                let promotion = match piece {
+                    PieceType::Pawn(color) if to.y == 0 || to.y == 7 => {
+                        Some(PieceType::Queen(color))
+                    }
+                    _ => None,
+                };
+                captures.push(Move {
+                    from,
+                    to,
+                    promotion,
+                });
+                if captures.len() >= limit {
+                    return captures;
+                }
+            }
+        }
+    }
+    let mut en_passant = Moves::new();
+    generate_en_passant_moves(game_data, &mut en_passant);
+    for (from, destinations) in en_passant {
+        captures.extend(destinations.into_iter().map(|to| Move {
+            from,
+            to,
+            promotion: None,
+        }));
+    }
+    captures.truncate(limit);
+    captures
+}
+
+/// Plays `moves` one after the other from `start`, each as origin,
+/// destination and promotion piece (a queen when left out). Stops at the
+/// first move that isn't legal, with its index.
+pub fn play_moves(
+    start: &GameData,
+    moves: &[(Position, Position, Option<PieceType>)],
+) -> Result<GameData, (usize, MoveError)> {
+    let mut game_data = start.clone();
+    for (i, &(from, to, promotion)) in moves.iter().enumerate() {
+        let mv = Move {
+            from,
+            to,
+            promotion,
+        };
+        game_data = apply_move_checked(&game_data, &mv).map_err(|error| (i, error))?;
+    }
+    Ok(game_data)
+}
+
+/// Every legal move of the side to move, with a separate entry for each
+/// promotion piece (queen, rook, bishop, knight) of a pawn reaching the last
+/// rank. The order is that of `legal_moves_flat`.
+pub fn generate_moves_detailed(game_data: &GameData) -> Vec<Move> {
+    let mut result = Vec::new();
+    for mv in legal_moves_flat(game_data) {
+        match mv.promotion {
+            Some(promotion) => {
+                let color = promotion.get_color();
+                result.extend(
+                    [
+                        PieceType::Queen(color),
+                        PieceType::Rook(color),
+                        PieceType::Bishop(color),
+                        PieceType::Knight(color),
+                    ]
+                    .map(|piece| Move {
+                        promotion: Some(piece),
+                        ..mv
+                    }),
+                );
+            }
+            None => result.push(mv),
+        }
+    }
+    result
+}
+
+/// Legal moves of the side to move as a flat list, one per origin and
+/// destination, sorted by origin and then destination so the order doesn't
+/// depend on the board's hasher. Pawn moves to the last rank are reported as
+/// queen promotions; see `generate_moves_detailed` for the full set.
+pub fn legal_moves_flat(game_data: &GameData) -> Vec<Move> {
+    let mut result = Vec::new();
+    let mut moves: Vec<(Position, Vec<Position>)> = generate_moves(game_data).into_iter().collect();
+    moves.sort();
+    for (from, mut destinations) in moves {
+        destinations.sort();
+        let piece = *game_data.board.get(&from).unwrap();
+        for to in destinations {
+            let promotion = match piece {
+                PieceType::Pawn(color) if to.y == 0 || to.y == 7 => Some(PieceType::Queen(color)),
+                _ => None,
+            };
+            result.push(Move {
+                from,
+                to,
+                promotion,
+            });
+        }
+    }
+    result
+}
+
+/// The move the side to move is forced into when it has exactly one legal
+/// move, for hints and puzzles.
+pub fn forced_move(game_data: &GameData) -> Option<(Position, Position)> {
+    match legal_moves_flat(game_data)[..] {
+        [mv] => Some((mv.from, mv.to)),
+        _ => None,
+    }
+}
+
+/// Counts the leaf nodes of the legal move tree to `depth` plies.
+pub fn perft(game_data: &GameData, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = generate_moves_detailed(game_data);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves
+        .iter()
+        .map(|mv| perft(&apply_move(game_data, mv), depth - 1))
+        .sum()
+}
+
+/// Leaf counts of the move tree broken down the way reference perft tables
+/// are, which narrows down the kind of move a wrong total comes from. Each
+/// counter is about the moves made on the last ply.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PerftStats {
+    pub nodes: u64,
+    /// Captures, en passant included.
+    pub captures: u64,
+    pub en_passant: u64,
+    pub castles: u64,
+    /// Every promotion piece counts, so a pawn reaching the last rank makes
+    /// four.
+    pub promotions: u64,
+    /// Moves giving check, mates included.
+    pub checks: u64,
+    pub checkmates: u64,
+}
+
+impl std::ops::AddAssign for PerftStats {
+    fn add_assign(&mut self, other: Self) {
+        self.nodes += other.nodes;
+        self.captures += other.captures;
+        self.en_passant += other.en_passant;
+        self.castles += other.castles;
+        self.promotions += other.promotions;
+        self.checks += other.checks;
+        self.checkmates += other.checkmates;
+    }
+}
+
+/// `perft` with the leaves sorted by kind; see [`PerftStats`].
+pub fn perft_stats(game_data: &GameData, depth: u32) -> PerftStats {
+    let mut stats = PerftStats::default();
+    if depth == 0 {
+        stats.nodes = 1;
+        return stats;
+    }
+    for mv in generate_moves_detailed(game_data) {
+        let after = apply_move(game_data, &mv);
+        if depth > 1 {
+            stats += perft_stats(&after, depth - 1);
+            continue;
+        }
+        let en_passant = is_en_passant(game_data, &mv);
+        stats.nodes += 1;
+        if en_passant || game_data.board.contains_key(&mv.to) {
+            stats.captures += 1;
+        }
+        if en_passant {
+            stats.en_passant += 1;
+        }
+        if is_castling(game_data, &mv) {
+            stats.castles += 1;
+        }
+        if mv.promotion.is_some() {
+            stats.promotions += 1;
+        }
+        if is_in_check(&after) {
+            stats.checks += 1;
+            if !has_legal_moves(&after) {
+                stats.checkmates += 1;
+            }
+        }
+    }
+    stats
+}
+
+/// Whether the side to move is in check; never true without a king.
+pub fn is_in_check(game_data: &GameData) -> bool {
+    find_king(&game_data.board, game_data.to_move).is_some_and(|king| {
+        is_square_attacked(&game_data.board, king, game_data.to_move.get_opposite())
+    })
+}
+
+/// Origin squares of every legal move landing on `target`.
+pub fn movers_to(game_data: &GameData, target: Position) -> Vec<Position> {
+    generate_moves(game_data)
+        .into_iter()
+        .filter(|(_, destinations)| destinations.contains(&target))
+        .map(|(origin, _)| origin)
+        .collect()
+}
+
+fn is_en_passant(game_data: &GameData, mv: &Move) -> bool {
+    matches!(game_data.board.get(&mv.from), Some(PieceType::Pawn(_)))
+        && game_data.en_passant_target() == Some(mv.to)
+}
+
+fn is_castling(game_data: &GameData, mv: &Move) -> bool {
+    matches!(game_data.board.get(&mv.from), Some(PieceType::King(_)))
+        && (mv.from.x - mv.to.x).abs() == 2
+}
+
+/// The legal moves of a position by kind, see [`categorize_moves`]. The lists
+/// overlap the way [`PerftStats`] counts: an en passant capture is also a
+/// capture, a pawn taking on the last rank is both a capture and a
+/// promotion, and any of them may give check. `quiet` holds the rest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MoveCategories {
+    pub quiet: Vec<Move>,
+    pub captures: Vec<Move>,
+    pub checks: Vec<Move>,
+    pub castles: Vec<Move>,
+    pub en_passant: Vec<Move>,
+    /// One entry per promotion piece, as in `generate_moves_detailed`.
+    pub promotions: Vec<Move>,
+}
+
+/// Sorts the legal moves of the side to move into [`MoveCategories`], each
+/// list ordered by origin, destination and promotion piece.
+pub fn categorize_moves(game_data: &GameData) -> MoveCategories {
+    let mut moves = generate_moves_detailed(game_data);
+    moves.sort_by_key(|mv| (mv.from, mv.to, mv.promotion.map(|piece| piece.fen_char())));
+    let mut categories = MoveCategories::default();
+    for mv in moves {
+        let en_passant = is_en_passant(game_data, &mv);
+        let capture = en_passant || game_data.board.contains_key(&mv.to);
+        let castling = is_castling(game_data, &mv);
+        let check = move_gives_check(game_data, &mv);
+        if capture {
+            categories.captures.push(mv);
+        }
+        if en_passant {
+            categories.en_passant.push(mv);
+        }
+        if castling {
+            categories.castles.push(mv);
+        }
+        if mv.promotion.is_some() {
+            categories.promotions.push(mv);
+        }
+        if check {
+            categories.checks.push(mv);
+        }
+        if !(capture || castling || check || mv.promotion.is_some()) {
+            categories.quiet.push(mv);
+        }
+    }
+    categories
+}
+
+/// Whether playing `mv` puts the opponent in check, whether by the moved piece
+/// itself or by a line it uncovers.
+pub fn move_gives_check(game_data: &GameData, mv: &Move) -> bool {
+    let new_game_data = apply_move(game_data, mv);
+    find_king(&new_game_data.board, new_game_data.to_move)
+        .is_some_and(|king| is_square_attacked(&new_game_data.board, king, game_data.to_move))
+}
+
+/// Who is giving check to the side to move.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CheckInfo {
+    pub king: Option<Position>,
+    /// Squares of the checking pieces, in sorted order.
+    pub checkers: Vec<Position>,
+}
+
+impl CheckInfo {
+    pub fn is_check(&self) -> bool {
+        !self.checkers.is_empty()
+    }
+    /// Two checkers at once, which only a king move can answer.
+    pub fn is_double_check(&self) -> bool {
+        self.checkers.len() >= 2
+    }
+}
+
+/// Squares of the enemy pieces attacking `color`'s king, found by looking out
+/// from the king along knight jumps, pawn captures and sliding lines. Sorted;
+/// empty when `color` has no king.
+pub fn checking_pieces(game_data: &GameData, color: PieceColor) -> Vec<Position> {
+    let Some(king) = find_king(&game_data.board, color) else {
+        return Vec::new();
+    };
+    let mut checkers: Vec<Position> = checking_lines(&game_data.board, king, color)
+        .into_iter()
+        .filter_map(|line| line.last().copied())
+        .collect();
+    checkers.sort();
+    checkers
+}
+
+/// The pieces attacking the king of the side to move, see `checking_pieces`.
+pub fn check_info(game_data: &GameData) -> CheckInfo {
+    CheckInfo {
+        king: find_king(&game_data.board, game_data.to_move),
+        checkers: checking_pieces(game_data, game_data.to_move),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStatus {
+    Ongoing,
+    Checkmate,
+    Stalemate,
+}
+
+pub fn game_status(game_data: &GameData) -> GameStatus {
+    if has_legal_moves(game_data) {
+        GameStatus::Ongoing
+    } else if is_in_check(game_data) {
+        GameStatus::Checkmate
+    } else {
+        GameStatus::Stalemate
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionError {
+    KingCount(PieceColor, usize),
+    PawnOnBackRank(Position),
+    TooManyPawns(PieceColor, usize),
+    OpponentInCheck,
+    InconsistentCastling(PieceColor),
+}
+
+impl std::fmt::Display for PositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PositionError::KingCount(color, count) => {
+                write!(f, "{color:?} has {count} kings instead of one")
+            }
+            PositionError::PawnOnBackRank(pos) => {
+                write!(f, "pawn on back rank at {}", pos.to_algebraic())
+            }
+            PositionError::TooManyPawns(color, count) => {
+                write!(f, "{color:?} has {count} pawns")
+            }
+            PositionError::OpponentInCheck => write!(f, "side not to move is in check"),
+            PositionError::InconsistentCastling(color) => {
+                write!(
+                    f,
+                    "{color:?} castling rights don't match king and rook placement"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+/// Checks that a position could occur in a legal game, listing every
+/// violation found rather than stopping at the first one.
+pub fn validate_position(game_data: &GameData) -> Result<(), Vec<PositionError>> {
+    let board = &game_data.board;
+    let mut errors = Vec::new();
+    for color in [PieceColor::White, PieceColor::Black] {
+        let kings = board
+            .values()
+            .filter(|&&piece| piece == PieceType::King(color))
+            .count();
+        if kings != 1 {
+            errors.push(PositionError::KingCount(color, kings));
+        }
+        let pawns = board
+            .values()
+            .filter(|&&piece| piece == PieceType::Pawn(color))
+            .count();
+        if pawns > 8 {
+            errors.push(PositionError::TooManyPawns(color, pawns));
+        }
+        if let Some(castling) = game_data.castling.get(&color) {
+            let back_rank = if color == PieceColor::White { 0 } else { 7 };
+            let has =
+                |x: i8, piece: PieceType| board.get(&Position { x, y: back_rank }) == Some(&piece);
+            let king_home = has(4, PieceType::King(color));
+            if (castling.king_side && !(king_home && has(7, PieceType::Rook(color))))
+                || (castling.queen_side && !(king_home && has(0, PieceType::Rook(color))))
+            {
+                errors.push(PositionError::InconsistentCastling(color));
+            }
+        }
+    }
+    let mut back_rank_pawns: Vec<Position> = board
+        .iter()
+        .filter(|(pos, piece)| matches!(piece, PieceType::Pawn(_)) && (pos.y == 0 || pos.y == 7))
+        .map(|(&pos, _)| pos)
+        .collect();
+    back_rank_pawns.sort();
+    errors.extend(
+        back_rank_pawns
+            .into_iter()
+            .map(PositionError::PawnOnBackRank),
     );
+    let opponent = game_data.to_move.get_opposite();
+    if !errors
+        .iter()
+        .any(|e| matches!(e, PositionError::KingCount(..)))
+        && find_king(board, opponent)
+            .is_some_and(|king| is_square_attacked(board, king, game_data.to_move))
+    {
+        errors.push(PositionError::OpponentInCheck);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, Default)]
+pub struct Position {
+    pub x: i8,
+    pub y: i8,
+}
+
+impl Position {
+    /// Parses a square such as `"e4"`.
+    pub fn from_algebraic(square: &str) -> Option<Position> {
+        let bytes = square.as_bytes();
+        if bytes.len() != 2 {
+            return None;
+        }
+        let position = Position {
+            x: bytes[0].wrapping_sub(b'a') as i8,
+            y: bytes[1].wrapping_sub(b'1') as i8,
+        };
+        if is_valid_chess_position(position) {
+            Some(position)
+        } else {
+            None
+        }
+    }
+    pub fn to_algebraic(self) -> String {
+        format!("{}{}", (b'a' + self.x as u8) as char, self.y + 1)
+    }
+    /// The square's bit in a bitboard, a1 being bit 0 and h8 bit 63.
+    pub fn bit_index(self) -> u32 {
+        (self.y * 8 + self.x) as u32
+    }
+    pub fn from_bit_index(index: u32) -> Option<Position> {
+        (index < 64).then_some(Position {
+            x: (index % 8) as i8,
+            y: (index / 8) as i8,
+        })
+    }
+    pub fn bit(self) -> u64 {
+        1 << self.bit_index()
+    }
+}
+
+/// The squares set in a bitboard, a1 first.
+pub fn bitboard_squares(mut mask: u64) -> impl Iterator<Item = Position> {
+    std::iter::from_fn(move || {
+        let index = mask.trailing_zeros();
+        mask &= mask.wrapping_sub(1);
+        Position::from_bit_index(index)
+    })
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct Move {
+    pub from: Position,
+    pub to: Position,
+    pub promotion: Option<PieceType>,
+}
+
+pub type Moves = HashMap<Position, Vec<Position>>;
+
+/// Parses long algebraic notation such as `e2e4` or `e7e8q`. Only the
+/// coordinates are checked; the promotion piece takes its color from the rank
+/// it lands on.
+pub fn parse_uci_move(s: &str) -> Option<Move> {
+    let from = Position::from_algebraic(s.get(0..2)?)?;
+    let to = Position::from_algebraic(s.get(2..4)?)?;
+    let promotion = match s.get(4..)? {
+        "" => None,
+        letter => {
+            let color = match to.y {
+                7 => PieceColor::White,
+                0 => PieceColor::Black,
+                _ => return None,
+            };
+            Some(match letter {
+                "q" => PieceType::Queen(color),
+                "r" => PieceType::Rook(color),
+                "b" => PieceType::Bishop(color),
+                "n" => PieceType::Knight(color),
+                _ => return None,
+            })
+        }
+    };
+    Some(Move {
+        from,
+        to,
+        promotion,
+    })
+}
+
+pub fn move_to_uci(mv: &Move) -> String {
+    let promotion = match mv.promotion {
+        Some(PieceType::Queen(_)) => "q",
+        Some(PieceType::Rook(_)) => "r",
+        Some(PieceType::Bishop(_)) => "b",
+        Some(PieceType::Knight(_)) => "n",
+        _ => "",
+    };
+    format!(
+        "{}{}{}",
+        mv.from.to_algebraic(),
+        mv.to.to_algebraic(),
+        promotion
+    )
+}
+
+#[test]
+fn generate_en_passant_move_1_right() {
+    let mut board: Board = HashMap::new();
+    let to_move = PieceColor::White;
+    board.insert(Position { x: 0, y: 0 }, PieceType::King(to_move));
+    let moved_2_squares = Position { x: 7, y: 4 };
+    board.insert(moved_2_squares, PieceType::Pawn(to_move.get_opposite()));
+    let right_pos = Position { x: 6, y: 4 };
+    board.insert(right_pos, PieceType::Pawn(to_move));
+    let game_data = GameData {
+        board,
+        castling: HashMap::new(),
+        can_move_2_squares: HashSet::new(),
+        to_move,
+        moved_2_squares: Some(moved_2_squares),
+        hash: 0,
+        halfmove_clock: 0,
+        fullmove_number: 1,
+    };
+
+    let mut moves = Moves::new();
+    generate_en_passant_moves(&game_data, &mut moves);
+    println!("{game_data}");
+    assert_eq!(vec![Position { x: 7, y: 5 }], moves[&right_pos]);
+}
+
+#[test]
+fn en_passant_needs_a_pawn_of_the_side_to_move() {
+    // after ...d5 Black's own c5 pawn stands beside it, White has no pawn there
+    let game_data =
+        crate::fen::from_fen("rnbqkbnr/pp2pppp/8/2pp4/P7/4P3/1PPP1PPP/RNBQKBNR w KQkq d6 0 1")
+            .unwrap();
+    let mut moves = Moves::new();
+    generate_en_passant_moves(&game_data, &mut moves);
+    assert!(moves.is_empty());
+}
+
+#[test]
+fn en_passant_cannot_open_the_rank_to_the_king() {
+    // after ...c5 both pawns stand between the king on a5 and the rook on h5,
+    // taking en passant would clear the whole rank
+    let pinned = crate::fen::from_fen("8/8/8/KPp4r/8/8/8/4k3 w - c6 0 1").unwrap();
+    let b5 = Position { x: 1, y: 4 };
+    let c6 = Position { x: 2, y: 5 };
+    assert_eq!(generate_moves(&pinned)[&b5], vec![Position { x: 1, y: 5 }]);
+    assert!(!legal_moves_flat(&pinned).iter().any(|mv| mv.to == c6));
+
+    // with the rook gone the capture is fine again
+    let free = crate::fen::from_fen("8/8/8/KPp5/8/8/8/4k3 w - c6 0 1").unwrap();
+    assert!(generate_moves(&free)[&b5].contains(&c6));
 }
 
 #[test]
 fn generate_vertical_horizontal_inclusive_test() {
-    let mut out = HashSet::<Position>::new();
+    let mut out = Vec::new();
     let mut board = HashMap::<Position, PieceType>::new();
     board.insert(
         Position { x: 4, y: 3 },
@@ -749,7 +2098,7 @@ fn generate_vertical_horizontal_inclusive_test() {
 
 #[test]
 fn generate_vertical_horizontal_exclusive_test() {
-    let mut out = HashSet::<Position>::new();
+    let mut out = Vec::new();
     let mut board = HashMap::<Position, PieceType>::new();
     board.insert(
         Position { x: 4, y: 3 },
@@ -765,7 +2114,7 @@ fn generate_vertical_horizontal_exclusive_test() {
 
 #[test]
 fn generate_vertical_horizontal_horsie_test() {
-    let mut out = HashSet::<Position>::new();
+    let mut out = Vec::new();
     let mut board = HashMap::<Position, PieceType>::new();
     board.insert(
         Position { x: 4, y: 3 },
@@ -799,6 +2148,9 @@ fn test_castling() {
             can_move_2_squares: HashSet::new(),
             to_move: PieceColor::Black,
             moved_2_squares: None,
+            hash: 0,
+            halfmove_clock: 0,
+            fullmove_number: 1,
         },
         &mut moves,
     );
@@ -824,6 +2176,9 @@ fn test_rooks() {
         can_move_2_squares: HashSet::new(),
         to_move: PieceColor::Black,
         moved_2_squares: None,
+        hash: 0,
+        halfmove_clock: 0,
+        fullmove_number: 1,
     });
     assert_eq!(moves.get(&Position { x: 7, y: 7 }).unwrap().len(), 9);
     assert_eq!(moves.get(&Position { x: 0, y: 7 }).unwrap().len(), 10);
@@ -848,6 +2203,9 @@ fn test_bishops() {
         can_move_2_squares: HashSet::new(),
         to_move: PieceColor::Black,
         moved_2_squares: None,
+        hash: 0,
+        halfmove_clock: 0,
+        fullmove_number: 1,
     });
     assert_eq!(moves.get(&Position { x: 7, y: 7 }).unwrap().len(), 7);
     assert_eq!(moves.get(&Position { x: 0, y: 7 }).unwrap().len(), 7);
@@ -866,6 +2224,9 @@ fn test_queen() {
         can_move_2_squares: HashSet::new(),
         to_move: PieceColor::Black,
         moved_2_squares: None,
+        hash: 0,
+        halfmove_clock: 0,
+        fullmove_number: 1,
     });
     assert_eq!(moves.get(&Position { x: 4, y: 4 }).unwrap().len(), 26);
 }
@@ -883,6 +2244,9 @@ fn test_king_under_attack() {
         can_move_2_squares: HashSet::new(),
         to_move: PieceColor::Black,
         moved_2_squares: None,
+        hash: 0,
+        halfmove_clock: 0,
+        fullmove_number: 1,
     });
     assert!(moves
         .get(&Position { x: 4, y: 7 })
@@ -903,9 +2267,1291 @@ fn test_king_under_attack_unreachable() {
         can_move_2_squares: HashSet::new(),
         to_move: PieceColor::Black,
         moved_2_squares: None,
+        hash: 0,
+        halfmove_clock: 0,
+        fullmove_number: 1,
     });
     assert!(!moves
         .get(&Position { x: 4, y: 7 })
         .unwrap()
         .contains(&Position { x: 4, y: 6 }));
 }
+
+#[test]
+fn uci_move_round_trip() {
+    for notation in ["e2e4", "g8f6", "a1h8", "e7e8q", "b2a1n", "h7h8r", "c2c1b"] {
+        let mv = parse_uci_move(notation).unwrap();
+        assert_eq!(move_to_uci(&mv), notation);
+    }
+    let promotion = parse_uci_move("e7e8q").unwrap();
+    assert_eq!(promotion.from, Position { x: 4, y: 6 });
+    assert_eq!(promotion.to, Position { x: 4, y: 7 });
+    assert_eq!(
+        promotion.promotion,
+        Some(PieceType::Queen(PieceColor::White))
+    );
+}
+
+#[test]
+fn uci_move_rejects_invalid_notation() {
+    for notation in [
+        "", "e2", "e2e", "i2e4", "e9e4", "e2e4q", "e7e8k", "e7e8qq", "E2E4",
+    ] {
+        assert_eq!(parse_uci_move(notation), None, "{notation}");
+    }
+}
+
+#[test]
+fn is_square_attacked_by_each_piece_type() {
+    let target = Position { x: 3, y: 3 };
+    let attackers = [
+        (Position { x: 3, y: 7 }, PieceType::Rook(PieceColor::White)),
+        (
+            Position { x: 0, y: 0 },
+            PieceType::Bishop(PieceColor::White),
+        ),
+        (Position { x: 6, y: 6 }, PieceType::Queen(PieceColor::White)),
+        (Position { x: 3, y: 0 }, PieceType::Queen(PieceColor::White)),
+        (
+            Position { x: 5, y: 4 },
+            PieceType::Knight(PieceColor::White),
+        ),
+        (Position { x: 4, y: 4 }, PieceType::King(PieceColor::White)),
+        (Position { x: 2, y: 2 }, PieceType::Pawn(PieceColor::White)),
+        (Position { x: 4, y: 4 }, PieceType::Pawn(PieceColor::Black)),
+    ];
+    for (position, piece) in attackers {
+        let mut board = Board::new();
+        board.insert(position, piece);
+        assert!(
+            is_square_attacked(&board, target, piece.get_color()),
+            "{piece:?} on {position:?}"
+        );
+        assert!(!is_square_attacked(
+            &board,
+            target,
+            piece.get_color().get_opposite()
+        ));
+    }
+}
+
+#[test]
+fn is_square_attacked_respects_blockers_and_pawn_direction() {
+    let target = Position { x: 3, y: 3 };
+    let mut board = Board::new();
+    board.insert(Position { x: 3, y: 7 }, PieceType::Rook(PieceColor::White));
+    board.insert(
+        Position { x: 3, y: 5 },
+        PieceType::Knight(PieceColor::Black),
+    );
+    board.insert(Position { x: 2, y: 4 }, PieceType::Pawn(PieceColor::White));
+    board.insert(
+        Position { x: 7, y: 3 },
+        PieceType::Bishop(PieceColor::White),
+    );
+    assert!(!is_square_attacked(&board, target, PieceColor::White));
+
+    // a friendly piece on the square still counts as defended
+    board.insert(target, PieceType::Pawn(PieceColor::White));
+    board.insert(
+        Position { x: 1, y: 1 },
+        PieceType::Bishop(PieceColor::White),
+    );
+    assert!(is_square_attacked(&board, target, PieceColor::White));
+}
+
+#[test]
+fn perft_start_position() {
+    let game_data = GameData::default();
+    assert_eq!(perft(&game_data, 1), 20);
+    assert_eq!(perft(&game_data, 2), 400);
+    assert_eq!(perft(&game_data, 3), 8902);
+}
+
+#[test]
+fn perft_tricky_positions() {
+    // "kiwipete": castling, pins and en passant all in one position
+    let kiwipete = crate::fen::from_fen(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    )
+    .unwrap();
+    assert_eq!(perft(&kiwipete, 1), 48);
+    assert_eq!(perft(&kiwipete, 2), 2039);
+
+    let rook_endgame = crate::fen::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+    assert_eq!(perft(&rook_endgame, 1), 14);
+    assert_eq!(perft(&rook_endgame, 2), 191);
+    assert_eq!(perft(&rook_endgame, 3), 2812);
+
+    // the knight can take the h1 rook, after which White may no longer castle
+    let castling_rights =
+        crate::fen::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap();
+    assert_eq!(perft(&castling_rights, 3), 62379);
+}
+
+#[test]
+fn compute_pins_restricts_to_ray() {
+    let mut board = Board::new();
+    let king = Position { x: 4, y: 0 };
+    board.insert(king, PieceType::King(PieceColor::White));
+    board.insert(Position { x: 4, y: 2 }, PieceType::Rook(PieceColor::White));
+    board.insert(Position { x: 4, y: 6 }, PieceType::Rook(PieceColor::Black));
+    board.insert(
+        Position { x: 2, y: 2 },
+        PieceType::Bishop(PieceColor::White),
+    );
+    board.insert(
+        Position { x: 0, y: 4 },
+        PieceType::Bishop(PieceColor::Black),
+    );
+    // shielded by another white piece, so not pinned
+    board.insert(
+        Position { x: 5, y: 1 },
+        PieceType::Knight(PieceColor::White),
+    );
+    board.insert(Position { x: 6, y: 2 }, PieceType::Pawn(PieceColor::White));
+    board.insert(
+        Position { x: 7, y: 3 },
+        PieceType::Bishop(PieceColor::Black),
+    );
+
+    let pins = compute_pins(&board, king, PieceColor::White);
+    assert_eq!(pins.len(), 2);
+    let rook_ray = pins.get(&Position { x: 4, y: 2 }).unwrap();
+    assert_eq!(rook_ray.len(), 6);
+    assert!(rook_ray.contains(&Position { x: 4, y: 6 }));
+    assert!(pins.contains_key(&Position { x: 2, y: 2 }));
+
+    let moves = generate_moves(&GameData {
+        board,
+        castling: HashMap::new(),
+        can_move_2_squares: HashSet::new(),
+        to_move: PieceColor::White,
+        moved_2_squares: None,
+        hash: 0,
+        halfmove_clock: 0,
+        fullmove_number: 1,
+    });
+    assert_eq!(moves.get(&Position { x: 4, y: 2 }).unwrap().len(), 5);
+    assert_eq!(moves.get(&Position { x: 2, y: 2 }).unwrap().len(), 3);
+}
+
+#[test]
+fn check_restricts_moves_to_block_or_capture() {
+    let mut board = Board::new();
+    board.insert(Position { x: 4, y: 0 }, PieceType::King(PieceColor::White));
+    board.insert(Position { x: 4, y: 7 }, PieceType::Rook(PieceColor::Black));
+    board.insert(Position { x: 0, y: 3 }, PieceType::Rook(PieceColor::White));
+    board.insert(
+        Position { x: 3, y: 5 },
+        PieceType::Knight(PieceColor::White),
+    );
+    let mut game_data = GameData {
+        board,
+        castling: HashMap::new(),
+        can_move_2_squares: HashSet::new(),
+        to_move: PieceColor::White,
+        moved_2_squares: None,
+        hash: 0,
+        halfmove_clock: 0,
+        fullmove_number: 1,
+    };
+    let moves = generate_moves(&game_data);
+    assert_eq!(moves[&Position { x: 0, y: 3 }], [Position { x: 4, y: 3 }]);
+    let mut knight_moves = moves[&Position { x: 3, y: 5 }].clone();
+    knight_moves.sort();
+    assert_eq!(
+        knight_moves,
+        [Position { x: 4, y: 3 }, Position { x: 4, y: 7 }]
+    );
+
+    // double check: only the king may move
+    game_data.board.insert(
+        Position { x: 5, y: 2 },
+        PieceType::Knight(PieceColor::Black),
+    );
+    let moves = generate_moves(&game_data);
+    assert_eq!(moves.len(), 1);
+    assert!(moves.contains_key(&Position { x: 4, y: 0 }));
+}
+
+#[test]
+fn move_gives_check_direct_and_discovered() {
+    let game_data = crate::fen::from_fen("4k3/8/8/8/8/2N5/1B6/R3K3 w - - 0 1").unwrap();
+    // the rook checks along the back rank
+    assert!(move_gives_check(
+        &game_data,
+        &parse_uci_move("a1a8").unwrap()
+    ));
+    assert!(!move_gives_check(
+        &game_data,
+        &parse_uci_move("a1a7").unwrap()
+    ));
+    // the knight steps off the long diagonal, exposing the king to the bishop
+    let game_data = crate::fen::from_fen("8/8/5k2/8/8/2N5/1B6/4K3 w - - 0 1").unwrap();
+    assert!(move_gives_check(
+        &game_data,
+        &parse_uci_move("c3a4").unwrap()
+    ));
+    assert!(!move_gives_check(
+        &game_data,
+        &parse_uci_move("b2a1").unwrap()
+    ));
+}
+
+#[test]
+fn movers_to_lists_every_origin() {
+    let game_data = crate::fen::from_fen("4k3/8/8/8/8/2N3N1/8/4K3 w - - 0 1").unwrap();
+    let mut movers = movers_to(&game_data, Position { x: 4, y: 3 });
+    movers.sort();
+    assert_eq!(
+        movers,
+        vec![Position { x: 2, y: 2 }, Position { x: 6, y: 2 }]
+    );
+    assert!(movers_to(&game_data, Position { x: 0, y: 7 }).is_empty());
+}
+
+#[test]
+fn piece_moves_ignores_check() {
+    let game_data = crate::fen::from_fen("4k3/8/8/8/4Q3/8/4P3/R3K2r w Q - 0 1").unwrap();
+    let board = &game_data.board;
+    assert_eq!(piece_moves(board, Position { x: 4, y: 3 }).len(), 25);
+    assert_eq!(piece_moves(board, Position { x: 0, y: 0 }).len(), 10);
+    let pawn_moves = piece_moves(board, Position { x: 4, y: 1 });
+    assert_eq!(pawn_moves.len(), 1);
+    assert!(pawn_moves.contains(&Position { x: 4, y: 2 }));
+    assert!(piece_moves(board, Position { x: 3, y: 3 }).is_empty());
+    assert_eq!(
+        generate_moves(&game_data)[&Position { x: 4, y: 3 }],
+        [Position { x: 7, y: 0 }]
+    );
+}
+
+#[test]
+fn validate_position_reports_each_violation() {
+    let errors = |fen: &str| validate_position(&crate::fen::from_fen(fen).unwrap()).unwrap_err();
+    assert_eq!(validate_position(&GameData::default()), Ok(()));
+    assert_eq!(
+        errors("4k3/8/8/8/8/8/8/4K1K1 w - - 0 1"),
+        [PositionError::KingCount(PieceColor::White, 2)]
+    );
+    assert_eq!(
+        errors("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+        [PositionError::KingCount(PieceColor::Black, 0)]
+    );
+    assert_eq!(
+        errors("P3k3/8/8/8/8/8/8/4K2p w - - 0 1"),
+        [
+            PositionError::PawnOnBackRank(Position { x: 0, y: 7 }),
+            PositionError::PawnOnBackRank(Position { x: 7, y: 0 }),
+        ]
+    );
+    assert_eq!(
+        errors("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - - 0 1"),
+        [PositionError::TooManyPawns(PieceColor::White, 9)]
+    );
+    assert_eq!(
+        errors("4k3/8/8/8/4Q3/8/8/4K3 w - - 0 1"),
+        [PositionError::OpponentInCheck]
+    );
+    assert_eq!(
+        errors("r3k3/8/8/8/8/8/8/4K2R w Kkq - 0 1"),
+        [PositionError::InconsistentCastling(PieceColor::Black)]
+    );
+}
+
+#[test]
+fn to_ascii_start_position() {
+    assert_eq!(
+        GameData::default().to_ascii(),
+        "  +-----------------+\n\
+         8 | r n b q k b n r |\n\
+         7 | p p p p p p p p |\n\
+         6 | . . . . . . . . |\n\
+         5 | . . . . . . . . |\n\
+         4 | . . . . . . . . |\n\
+         3 | . . . . . . . . |\n\
+         2 | P P P P P P P P |\n\
+         1 | R N B Q K B N R |\n\
+         \x20 +-----------------+\n\
+         \x20   a b c d e f g h\n"
+    );
+}
+
+#[test]
+fn board_signature_distinguishes_positions() {
+    let start = GameData::default();
+    let after_e4 = apply_move(&start, &parse_uci_move("e2e4").unwrap());
+    assert_eq!(
+        board_signature(&start.board),
+        board_signature(&GameData::default().board)
+    );
+    assert_ne!(
+        board_signature(&start.board),
+        board_signature(&after_e4.board)
+    );
+
+    let mut recolored = start.board.clone();
+    recolored.insert(Position { x: 4, y: 1 }, PieceType::Pawn(PieceColor::Black));
+    assert_ne!(board_signature(&start.board), board_signature(&recolored));
+}
+
+#[test]
+fn capturing_an_unmoved_pawn_does_not_inherit_its_double_push() {
+    let game_data = crate::fen::from_fen("4k3/1p6/P7/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let game_data = apply_move(&game_data, &parse_uci_move("a6b7").unwrap());
+    let game_data = apply_move(&game_data, &parse_uci_move("e8e7").unwrap());
+    let b7 = Position::from_algebraic("b7").unwrap();
+    assert_eq!(legal_moves_for(&game_data, b7), [Position { x: 1, y: 7 }]);
+}
+
+#[test]
+fn legal_moves_for_pinned_bishop() {
+    let game_data = crate::fen::from_fen("4k3/8/8/8/7q/8/5B2/4K2R w K - 0 1").unwrap();
+    let mut bishop_moves = legal_moves_for(&game_data, Position { x: 5, y: 1 });
+    bishop_moves.sort();
+    assert_eq!(
+        bishop_moves,
+        [Position { x: 6, y: 2 }, Position { x: 7, y: 3 }]
+    );
+    assert!(legal_moves_for(&game_data, Position { x: 4, y: 7 }).is_empty());
+    assert!(legal_moves_for(&game_data, Position { x: 0, y: 0 }).is_empty());
+    assert!(legal_moves_for(&game_data, Position { x: 4, y: 0 }).contains(&Position { x: 6, y: 0 }));
+    for (from, destinations) in generate_moves(&game_data) {
+        assert_eq!(legal_moves_for(&game_data, from), destinations);
+    }
+}
+
+#[test]
+fn generate_moves_detailed_expands_promotions() {
+    let game_data = crate::fen::from_fen("k7/6P1/8/8/8/8/8/K7 w - - 0 1").unwrap();
+    let pawn = Position { x: 6, y: 6 };
+    let promotions: Vec<Move> = generate_moves_detailed(&game_data)
+        .into_iter()
+        .filter(|mv| mv.from == pawn)
+        .collect();
+    assert_eq!(promotions.len(), 4);
+    for piece in [
+        PieceType::Queen(PieceColor::White),
+        PieceType::Rook(PieceColor::White),
+        PieceType::Bishop(PieceColor::White),
+        PieceType::Knight(PieceColor::White),
+    ] {
+        assert!(promotions.contains(&Move {
+            from: pawn,
+            to: Position { x: 6, y: 7 },
+            promotion: Some(piece),
+        }));
+    }
+    assert_eq!(
+        legal_moves_flat(&game_data).len() + 3,
+        generate_moves_detailed(&game_data).len()
+    );
+    assert_eq!(perft(&game_data, 1), 7);
+}
+
+#[test]
+fn is_legal_move_checks_a_single_move() {
+    let game_data = crate::fen::from_fen("3rk3/8/8/4Pp2/8/8/8/R3K2R w KQ f6 0 1").unwrap();
+    let square = |s: &str| Position::from_algebraic(s).unwrap();
+    assert!(is_legal_move(&game_data, square("e5"), square("e6")));
+    assert!(is_legal_move(&game_data, square("e5"), square("f6")));
+    assert!(is_legal_move(&game_data, square("e1"), square("g1")));
+    // the black rook controls the d-file, so the king may neither step onto
+    // it nor castle across it
+    assert!(!is_legal_move(&game_data, square("e1"), square("d1")));
+    assert!(!is_legal_move(&game_data, square("e1"), square("c1")));
+    assert!(!is_legal_move(&game_data, square("e1"), square("e3")));
+    assert!(!is_legal_move(&game_data, square("d8"), square("d1")));
+    assert!(!is_legal_move(&game_data, square("e4"), square("e5")));
+    for (from, destinations) in generate_moves(&game_data) {
+        for to in destinations {
+            assert!(is_legal_move(&game_data, from, to));
+        }
+    }
+}
+
+#[test]
+fn check_info_counts_checkers() {
+    let quiet = crate::fen::from_fen("4k3/8/8/8/8/8/8/4K2R b - - 0 1").unwrap();
+    assert_eq!(
+        check_info(&quiet),
+        CheckInfo {
+            king: Some(Position { x: 4, y: 7 }),
+            checkers: vec![],
+        }
+    );
+
+    let single = crate::fen::from_fen("4k3/8/8/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+    let info = check_info(&single);
+    assert!(info.is_check() && !info.is_double_check());
+    assert_eq!(info.checkers, [Position { x: 4, y: 0 }]);
+
+    // the knight on d6 and the rook on e1 both give check
+    let double = crate::fen::from_fen("4k3/8/3N4/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+    let info = check_info(&double);
+    assert!(info.is_double_check());
+    assert_eq!(
+        info.checkers,
+        [Position { x: 3, y: 5 }, Position { x: 4, y: 0 }]
+    );
+}
+
+#[test]
+fn king_cannot_approach_enemy_king() {
+    let game_data = crate::fen::from_fen("8/8/4k3/8/4K3/8/8/8 w - - 0 1").unwrap();
+    let moves = legal_moves_for(&game_data, Position { x: 4, y: 3 });
+    for square in ["d5", "e5", "f5"] {
+        assert!(!moves.contains(&Position::from_algebraic(square).unwrap()));
+    }
+    assert_eq!(moves.len(), 5);
+
+    // nor take a piece the enemy king defends
+    let game_data = crate::fen::from_fen("8/8/4k3/4p3/4K3/8/8/8 w - - 0 1").unwrap();
+    let moves = legal_moves_for(&game_data, Position { x: 4, y: 3 });
+    assert!(!moves.contains(&Position { x: 4, y: 4 }));
+}
+
+#[test]
+fn knight_underpromotion_mates_where_queen_does_not() {
+    // the king on h7 is boxed in by its own pieces, only a knight on f8 reaches it
+    let game_data = crate::fen::from_fen("6bn/5Ppk/6pp/8/8/8/8/K7 w - - 0 1").unwrap();
+    let from = Position { x: 5, y: 6 };
+    let to = Position { x: 5, y: 7 };
+    let promotions: Vec<PieceType> = generate_moves_detailed(&game_data)
+        .into_iter()
+        .filter(|mv| mv.from == from && mv.to == to)
+        .filter_map(|mv| mv.promotion)
+        .collect();
+    assert_eq!(promotions.len(), 4);
+
+    for promotion in promotions {
+        let mv = Move {
+            from,
+            to,
+            promotion: Some(promotion),
+        };
+        let after = apply_move(&game_data, &mv);
+        let knight = promotion == PieceType::Knight(PieceColor::White);
+        assert_eq!(move_gives_check(&game_data, &mv), knight);
+        let expected = if knight {
+            GameStatus::Checkmate
+        } else {
+            GameStatus::Ongoing
+        };
+        assert_eq!(game_status(&after), expected, "{promotion:?}");
+    }
+    let knight = Move {
+        from,
+        to,
+        promotion: Some(PieceType::Knight(PieceColor::White)),
+    };
+    assert_eq!(crate::san::move_to_san(&game_data, &knight), "f8=N#");
+}
+
+#[test]
+fn available_castles_checks_the_way() {
+    let castles = |fen: &str| available_castles(&crate::fen::from_fen(fen).unwrap());
+    assert_eq!(
+        castles("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"),
+        [CastleSide::KingSide, CastleSide::QueenSide]
+    );
+    // own bishop on f1
+    assert_eq!(
+        castles("r3k2r/8/8/8/8/8/8/R3KB1R w KQkq - 0 1"),
+        [CastleSide::QueenSide]
+    );
+    // the rook on d8 covers d1, which the king would cross
+    assert_eq!(
+        castles("3rk2r/8/8/8/8/8/8/R3K2R w KQk - 0 1"),
+        [CastleSide::KingSide]
+    );
+    // no castling out of check
+    assert!(castles("r3k2r/8/8/8/8/8/4r3/R3K2R w KQkq - 0 1").is_empty());
+    // a right without its rook is no use
+    assert!(castles("r3k2r/8/8/8/8/8/8/4K2R w Qkq - 0 1").is_empty());
+}
+
+#[test]
+fn piece_at_looks_up_squares_by_name() {
+    let mut game_data = GameData::default();
+    assert_eq!(
+        piece_at(&game_data, "e1"),
+        Some(PieceType::King(PieceColor::White))
+    );
+    assert_eq!(piece_at(&game_data, "e4"), None);
+    assert_eq!(piece_at(&game_data, "e9"), None);
+
+    game_data.set_piece("e4", Some(PieceType::Knight(PieceColor::Black)));
+    game_data.set_piece("e1", None);
+    assert_eq!(
+        piece_at(&game_data, "e4"),
+        Some(PieceType::Knight(PieceColor::Black))
+    );
+    assert_eq!(piece_at(&game_data, "e1"), None);
+}
+
+#[test]
+fn forced_move_only_when_a_single_move_is_legal() {
+    assert_eq!(forced_move(&GameData::default()), None);
+    // back rank check: the king is boxed in and only the a8 rook can take
+    let game_data = crate::fen::from_fen("R7/8/4k3/8/8/8/6PP/r6K w - - 0 1").unwrap();
+    let square = |s| Position::from_algebraic(s).unwrap();
+    assert_eq!(forced_move(&game_data), Some((square("a8"), square("a1"))));
+    // checkmate leaves no move at all
+    let mated = crate::fen::from_fen("8/8/4k3/8/8/8/6PP/r6K w - - 0 1").unwrap();
+    assert_eq!(forced_move(&mated), None);
+}
+
+#[test]
+fn piece_letters_and_values() {
+    let pieces = [
+        (PieceType::King(PieceColor::White), 'K', 0),
+        (PieceType::Queen(PieceColor::White), 'Q', 900),
+        (PieceType::Rook(PieceColor::White), 'R', 500),
+        (PieceType::Bishop(PieceColor::White), 'B', 330),
+        (PieceType::Knight(PieceColor::White), 'N', 320),
+        (PieceType::Pawn(PieceColor::White), 'P', 100),
+        (PieceType::King(PieceColor::Black), 'k', 0),
+        (PieceType::Queen(PieceColor::Black), 'q', 900),
+        (PieceType::Rook(PieceColor::Black), 'r', 500),
+        (PieceType::Bishop(PieceColor::Black), 'b', 330),
+        (PieceType::Knight(PieceColor::Black), 'n', 320),
+        (PieceType::Pawn(PieceColor::Black), 'p', 100),
+    ];
+    for (piece, letter, value) in pieces {
+        assert_eq!(piece.letter(), letter);
+        assert_eq!(PieceType::from_letter(letter), Some(piece));
+        assert_eq!(piece.value(), value);
+    }
+    assert_eq!(PieceType::from_letter('x'), None);
+    assert_eq!(PieceType::from_letter('1'), None);
+}
+
+#[test]
+fn fen_chars_round_trip() {
+    for color in [PieceColor::White, PieceColor::Black] {
+        for piece in [
+            PieceType::King(color),
+            PieceType::Queen(color),
+            PieceType::Rook(color),
+            PieceType::Bishop(color),
+            PieceType::Knight(color),
+            PieceType::Pawn(color),
+        ] {
+            assert_eq!(PieceType::from_letter(piece.fen_char()), Some(piece));
+            assert_ne!(piece.to_string(), piece.fen_char().to_string());
+        }
+    }
+    assert_eq!("w".parse(), Ok(PieceColor::White));
+    assert_eq!("b".parse(), Ok(PieceColor::Black));
+    assert_eq!(
+        "white".parse::<PieceColor>(),
+        Err(ParseColorError("white".to_string()))
+    );
+}
+
+#[test]
+fn apply_move_checked_refuses_pinned_piece_moves() {
+    // the knight on e2 is pinned to its king by the rook on e8
+    let game_data = crate::fen::from_fen("4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+    let mv = |uci| parse_uci_move(uci).unwrap();
+    assert_eq!(
+        apply_move_checked(&game_data, &mv("e2c3")).unwrap_err(),
+        MoveError::WouldExposeKing
+    );
+    assert_eq!(
+        apply_move_checked(&game_data, &mv("d4d5")).unwrap_err(),
+        MoveError::NoPieceToMove(Position::from_algebraic("d4").unwrap())
+    );
+    // the king itself may step off the file
+    let after = apply_move_checked(&game_data, &mv("e1d1")).unwrap();
+    assert_eq!(after.to_move, PieceColor::Black);
+
+    // castling with a right but no rook, castling without the right, and a
+    // rook move through a piece
+    let bare = crate::fen::from_fen("4k3/8/8/8/7P/8/8/4K2R w Q - 0 1").unwrap();
+    for uci in ["e1c1", "e1g1", "h1h8"] {
+        assert_eq!(
+            apply_move_checked(&bare, &mv(uci)).unwrap_err(),
+            MoveError::Illegal(mv(uci)),
+            "{uci}"
+        );
+    }
+    // a rook can't promote
+    let promoting = Move {
+        promotion: Some(PieceType::Queen(PieceColor::White)),
+        ..mv("h1h3")
+    };
+    assert_eq!(
+        apply_move_checked(&bare, &promoting).unwrap_err(),
+        MoveError::Illegal(promoting)
+    );
+}
+
+#[test]
+fn perft_stats_match_reference_tables() {
+    let start = GameData::default();
+    assert_eq!(
+        perft_stats(&start, 2),
+        PerftStats {
+            nodes: 400,
+            ..PerftStats::default()
+        }
+    );
+    assert_eq!(
+        perft_stats(&start, 3),
+        PerftStats {
+            nodes: 8902,
+            captures: 34,
+            checks: 12,
+            ..PerftStats::default()
+        }
+    );
+    let kiwipete = crate::fen::from_fen(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    )
+    .unwrap();
+    assert_eq!(
+        perft_stats(&kiwipete, 2),
+        PerftStats {
+            nodes: 2039,
+            captures: 351,
+            en_passant: 1,
+            castles: 91,
+            promotions: 0,
+            checks: 3,
+            checkmates: 0,
+        }
+    );
+    assert_eq!(perft_stats(&kiwipete, 2).nodes, perft(&kiwipete, 2));
+}
+
+#[test]
+fn is_quiet_needs_no_check_and_nothing_to_take() {
+    use crate::fen::from_fen;
+    // 1.e4 e5 2.d3 Nc6, nothing is in contact yet
+    let quiet =
+        from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/3P4/PPP2PPP/RNBQKBNR w KQkq - 1 3").unwrap();
+    assert!(is_quiet(&quiet));
+    // the other knight wandered to g4, where the queen takes it
+    let hanging =
+        from_fen("r1bqkb1r/pppp1ppp/2n5/4p3/4P1n1/3P4/PPP2PPP/RNBQKBNR w KQkq - 3 4").unwrap();
+    assert!(!is_quiet(&hanging));
+    assert!(!capture_moves(&hanging).is_empty());
+    // no captures at all, but in check
+    let check = from_fen("4k3/8/8/8/8/8/8/K3r3 w - - 0 1").unwrap();
+    assert!(capture_moves(&check).is_empty());
+    assert!(!is_quiet(&check));
+    // only an en passant capture
+    let en_passant = from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+    assert!(!is_quiet(&en_passant));
+}
+
+#[test]
+fn move_lists_keep_a_fixed_order() {
+    // every parse builds its maps with a fresh hasher
+    let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    let first = crate::fen::from_fen(fen).unwrap();
+    let second = crate::fen::from_fen(fen).unwrap();
+    let flat = legal_moves_flat(&first);
+    assert_eq!(flat, legal_moves_flat(&second));
+    assert_eq!(flat, legal_moves_flat(&first));
+    assert!(flat
+        .windows(2)
+        .all(|pair| (pair[0].from, pair[0].to) < (pair[1].from, pair[1].to)));
+    assert_eq!(
+        flat.len(),
+        generate_moves(&first).values().map(Vec::len).sum::<usize>()
+    );
+
+    assert_eq!(
+        generate_moves_detailed(&first),
+        generate_moves_detailed(&second)
+    );
+    let captures = capture_moves(&first);
+    assert_eq!(captures, capture_moves(&second));
+    assert!(captures
+        .windows(2)
+        .all(|pair| (pair[0].from, pair[0].to) < (pair[1].from, pair[1].to)));
+}
+
+#[test]
+fn categorize_moves_buckets_each_kind() {
+    // Black has just played ...d5; White can castle, take en passant, promote
+    // on b8 or by taking on a8, and check along the back rank
+    let game_data = crate::fen::from_fen("r3k3/1P6/8/3pP3/8/8/8/4K2R w K d6 0 1").unwrap();
+    let categories = categorize_moves(&game_data);
+    let uci = |moves: &[Move]| moves.iter().map(move_to_uci).collect::<Vec<_>>();
+
+    assert_eq!(uci(&categories.castles), ["e1g1"]);
+    assert_eq!(uci(&categories.en_passant), ["e5d6"]);
+    assert_eq!(
+        uci(&categories.captures),
+        ["b7a8b", "b7a8n", "b7a8q", "b7a8r", "e5d6"]
+    );
+    assert_eq!(categories.promotions.len(), 8);
+    assert_eq!(
+        uci(&categories.checks),
+        ["b7a8q", "b7a8r", "b7b8q", "b7b8r", "h1h8"]
+    );
+    assert!(uci(&categories.quiet).contains(&"e5e6".to_string()));
+
+    // the quiet moves are exactly the ones in no other list
+    let all = generate_moves_detailed(&game_data);
+    let special = [
+        &categories.captures,
+        &categories.checks,
+        &categories.castles,
+        &categories.promotions,
+    ];
+    for mv in &all {
+        let in_special = special.iter().any(|list| list.contains(mv));
+        assert_eq!(categories.quiet.contains(mv), !in_special, "{mv:?}");
+    }
+    assert!(categories.quiet.len() < all.len());
+}
+
+#[test]
+fn squares_between_aligned_squares() {
+    let square = |name| Position::from_algebraic(name).unwrap();
+    let between = |a, b| {
+        squares_between(square(a), square(b))
+            .into_iter()
+            .map(Position::to_algebraic)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(between("a1", "e1"), ["b1", "c1", "d1"]);
+    assert_eq!(between("h1", "e1"), ["g1", "f1"]);
+    assert_eq!(between("d2", "d6"), ["d3", "d4", "d5"]);
+    assert_eq!(between("a1", "d4"), ["b2", "c3"]);
+    assert_eq!(between("g2", "d5"), ["f3", "e4"]);
+    // neighbours, the same square and squares off any line
+    assert!(between("e4", "e5").is_empty());
+    assert!(between("e4", "f5").is_empty());
+    assert!(between("e4", "e4").is_empty());
+    assert!(between("a1", "b3").is_empty());
+    assert!(between("a1", "h7").is_empty());
+}
+
+#[test]
+fn has_legal_moves_stops_short_of_mate_and_stalemate() {
+    use crate::fen::from_fen;
+    assert!(has_legal_moves(&GameData::default()));
+    // stalemate and checkmate
+    assert!(!has_legal_moves(
+        &from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap()
+    ));
+    assert!(!has_legal_moves(
+        &from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap()
+    ));
+    // in check but able to take the checker
+    assert!(has_legal_moves(
+        &from_fen("6Qk/8/8/8/8/8/8/6K1 b - - 0 1").unwrap()
+    ));
+}
+
+#[test]
+fn attack_bitboards_match_the_square_sets() {
+    for index in 0..64 {
+        let square = Position::from_bit_index(index).unwrap();
+        assert_eq!(square.bit_index(), index);
+        assert_eq!(bitboard_squares(square.bit()).collect::<Vec<_>>(), [square]);
+    }
+    assert_eq!(Position::from_bit_index(64), None);
+    assert_eq!(Position::from_algebraic("h8").unwrap().bit(), 1 << 63);
+
+    let mask = |squares: HashSet<Position>| {
+        squares
+            .into_iter()
+            .fold(0, |mask, square| mask | square.bit())
+    };
+    let mut kinds = HashSet::new();
+    for fen in [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ] {
+        let board = crate::fen::from_fen(fen).unwrap().board;
+        for (&pos, &piece) in &board {
+            kinds.insert(piece);
+            let bits = attacks_bitboard(&board, pos);
+            assert_eq!(
+                bits,
+                mask(defended_squares(&board, pos)),
+                "{piece:?} on {pos:?}"
+            );
+            assert_eq!(bitboard_squares(bits).count(), bits.count_ones() as usize);
+        }
+        for color in [PieceColor::White, PieceColor::Black] {
+            let attacked = side_attacks_bitboard(&board, color);
+            for index in 0..64 {
+                let square = Position::from_bit_index(index).unwrap();
+                assert_eq!(
+                    attacked & square.bit() != 0,
+                    is_square_attacked(&board, square, color),
+                    "{fen} {color:?} {}",
+                    square.to_algebraic()
+                );
+            }
+        }
+    }
+    assert_eq!(kinds.len(), 12);
+    assert_eq!(attacks_bitboard(&Board::new(), Position { x: 0, y: 0 }), 0);
+}
+
+#[test]
+fn defended_squares_include_friendly_pieces() {
+    let game_data = crate::fen::from_fen("4k3/8/8/8/8/P7/8/RN2K3 w - - 0 1").unwrap();
+    let square = |name| Position::from_algebraic(name).unwrap();
+    // the rook protects the pawn in front of it and the knight beside it,
+    // and sees no further
+    let rook = defended_squares(&game_data.board, square("a1"));
+    let mut names: Vec<String> = rook.into_iter().map(Position::to_algebraic).collect();
+    names.sort();
+    assert_eq!(names, ["a2", "a3", "b1"]);
+    // attacking moves still skip them
+    let mut attacks = Vec::new();
+    generate_squares_under_attack_for_position(&game_data.board, square("a1"), &mut attacks);
+    assert_eq!(attacks, [square("a2")]);
+
+    let knight = defended_squares(&game_data.board, square("b1"));
+    assert!(knight.contains(&square("a3")) && knight.contains(&square("d2")));
+    let pawn = defended_squares(&game_data.board, square("a3"));
+    assert_eq!(pawn, HashSet::from([square("b4")]));
+    assert!(defended_squares(&game_data.board, square("h8")).is_empty());
+}
+
+#[test]
+fn play_moves_scholars_mate() {
+    let moves: Vec<_> = ["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6", "h5f7"]
+        .iter()
+        .map(|uci| {
+            let mv = parse_uci_move(uci).unwrap();
+            (mv.from, mv.to, mv.promotion)
+        })
+        .collect();
+    let mated = play_moves(&GameData::default(), &moves).unwrap();
+    assert_eq!(game_status(&mated), GameStatus::Checkmate);
+
+    let square = |name| Position::from_algebraic(name).unwrap();
+    let mut bad = moves.clone();
+    bad[2] = (square("f1"), square("f3"), None);
+    let mv = parse_uci_move("f1f3").unwrap();
+    assert_eq!(
+        play_moves(&GameData::default(), &bad).unwrap_err(),
+        (2, MoveError::Illegal(mv))
+    );
+    bad[2] = (square("f3"), square("f4"), None);
+    assert_eq!(
+        play_moves(&GameData::default(), &bad).unwrap_err(),
+        (2, MoveError::NoPieceToMove(square("f3")))
+    );
+    // a promotion piece on a move that doesn't promote
+    let queen = Some(PieceType::Queen(PieceColor::White));
+    assert!(play_moves(&GameData::default(), &[(square("e2"), square("e4"), queen)]).is_err());
+
+    // castling with no rook to bring along is refused, not played
+    let bare = crate::fen::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(
+        play_moves(&bare, &[(square("e1"), square("g1"), None)]).unwrap_err(),
+        (0, MoveError::Illegal(parse_uci_move("e1g1").unwrap()))
+    );
+}
+
+#[test]
+fn checking_pieces_single_and_double_check() {
+    let square = |name| Position::from_algebraic(name).unwrap();
+    let game_data = crate::fen::from_fen("4k3/8/8/8/8/8/8/4RK2 b - - 0 1").unwrap();
+    assert_eq!(
+        checking_pieces(&game_data, PieceColor::Black),
+        [square("e1")]
+    );
+    assert!(checking_pieces(&game_data, PieceColor::White).is_empty());
+
+    // the knight jumps out of the bishop's way with check of its own
+    let game_data = crate::fen::from_fen("4k3/3N4/8/1B6/8/8/8/4K3 w - - 0 1").unwrap();
+    assert!(checking_pieces(&game_data, PieceColor::Black).is_empty());
+    let game_data = apply_move(&game_data, &parse_uci_move("d7f6").unwrap());
+    assert_eq!(
+        checking_pieces(&game_data, PieceColor::Black),
+        [square("b5"), square("f6")]
+    );
+    assert!(check_info(&game_data).is_double_check());
+}
+
+#[test]
+fn moves_report_start_position() {
+    let report = GameData::default().moves_report();
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines.len(), 11);
+    assert_eq!(lines[0], "P a2 (2): a3 a4");
+    assert!(lines.contains(&"N b1 (2): a3 c3"));
+    assert!(lines.contains(&"N g1 (2): f3 h3"));
+    assert_eq!(lines.iter().filter(|line| line.starts_with('P')).count(), 8);
+    assert_eq!(lines[10], "20 moves from 10 pieces");
+}
+
+#[test]
+fn only_rooks_on_their_corners_carry_castling_rights() {
+    let start = crate::fen::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let mut game_data = start.clone();
+    // the a1 rook gives up the long castle, but moving on from d1 costs
+    // White nothing more
+    for uci in ["a1d1", "a8a7", "d1d2"] {
+        game_data = apply_move(&game_data, &parse_uci_move(uci).unwrap());
+    }
+    assert_eq!(
+        crate::fen::to_fen(&game_data),
+        "4k2r/r7/8/8/8/8/3R4/4K2R b Kk - 3 2"
+    );
+    assert_eq!(available_castles(&game_data), [CastleSide::KingSide]);
+
+    // taking the h8 rook on its corner ends Black's short castle too
+    let game_data = apply_move(&start, &parse_uci_move("h1h8").unwrap());
+    assert_eq!(
+        crate::fen::to_fen(&game_data),
+        "r3k2R/8/8/8/8/8/8/R3K3 b Qq - 0 1"
+    );
+    assert_eq!(validate_position(&game_data), Ok(()));
+    assert_eq!(game_data.hash, zobrist::hash(&game_data));
+}
+
+#[test]
+fn king_move_and_return_loses_castling_for_good() {
+    let mut game_data = crate::fen::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    assert_eq!(available_castles(&game_data).len(), 2);
+    for uci in ["e1f1", "e8d8", "f1e1", "d8e8"] {
+        game_data = apply_move(&game_data, &parse_uci_move(uci).unwrap());
+    }
+    // both kings are home again with the rooks untouched
+    assert_eq!(
+        crate::fen::to_fen(&game_data),
+        "r3k2r/8/8/8/8/8/8/R3K2R w - - 4 3"
+    );
+    assert!(available_castles(&game_data).is_empty());
+    let king_moves = &generate_moves(&game_data)[&Position { x: 4, y: 0 }];
+    assert!(!king_moves.contains(&Position { x: 6, y: 0 }));
+    assert!(!king_moves.contains(&Position { x: 2, y: 0 }));
+    game_data = apply_move(&game_data, &parse_uci_move("a1b1").unwrap());
+    assert!(available_castles(&game_data).is_empty());
+}
+
+#[test]
+fn capture_moves_only_captures() {
+    let uci = |game_data: &GameData| {
+        let mut moves: Vec<String> = capture_moves(game_data).iter().map(move_to_uci).collect();
+        moves.sort();
+        moves
+    };
+    // en passant on c6, the pawn takes the knight, the bishop takes the queen
+    let game_data = crate::fen::from_fen("4k3/8/8/2pP4/8/1n3q2/P3B3/4K3 w - c6 0 1").unwrap();
+    assert_eq!(uci(&game_data), ["a2b3", "d5c6", "e2f3"]);
+
+    // the same captures as the full generator, pins and all
+    for fen in [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+    ] {
+        let game_data = crate::fen::from_fen(fen).unwrap();
+        let mut expected: Vec<String> = legal_moves_flat(&game_data)
+            .iter()
+            .filter(|mv| {
+                game_data.board.contains_key(&mv.to)
+                    || game_data.en_passant_target() == Some(mv.to)
+                        && matches!(game_data.board[&mv.from], PieceType::Pawn(_))
+            })
+            .map(move_to_uci)
+            .collect();
+        expected.sort();
+        assert_eq!(uci(&game_data), expected, "{fen}");
+    }
+}
+
+/// The castling rights of `color` as `(king side, queen side)`; a missing
+/// entry and one with both flags off mean the same.
+#[cfg(test)]
+fn castling_flags(game_data: &GameData, color: PieceColor) -> (bool, bool) {
+    game_data
+        .castling
+        .get(&color)
+        .map_or((false, false), |rights| {
+            (rights.king_side, rights.queen_side)
+        })
+}
+
+/// The moves random games checked, to show the seeds reach the special
+/// ones.
+#[cfg(test)]
+#[derive(Debug, Default)]
+struct RandomGameCoverage {
+    plies: usize,
+    captures: usize,
+    en_passant: usize,
+    castles: usize,
+    promotions: usize,
+    checks: usize,
+    checkmates: usize,
+    stalemates: usize,
+}
+
+/// The rules the position itself must keep, whatever led up to it.
+#[cfg(test)]
+fn assert_position_invariants(game_data: &GameData, context: &str) {
+    use crate::fen::{from_fen, to_fen};
+
+    assert_eq!(validate_position(game_data), Ok(()), "{context}");
+    assert_eq!(
+        game_data.hash,
+        crate::zobrist::hash(game_data),
+        "incremental hash drifted; {context}"
+    );
+
+    // the move list and the status tell the same story
+    let moves = generate_moves_detailed(game_data);
+    let status = game_status(game_data);
+    assert_eq!(
+        moves.is_empty(),
+        status != GameStatus::Ongoing,
+        "{status:?} with {} moves; {context}",
+        moves.len()
+    );
+    assert_eq!(has_legal_moves(game_data), !moves.is_empty(), "{context}");
+    if moves.is_empty() {
+        let expected = if is_in_check(game_data) {
+            GameStatus::Checkmate
+        } else {
+            GameStatus::Stalemate
+        };
+        assert_eq!(status, expected, "{context}");
+    }
+    assert_eq!(
+        generate_moves(game_data)
+            .values()
+            .map(Vec::len)
+            .sum::<usize>(),
+        legal_moves_flat(game_data).len(),
+        "{context}"
+    );
+    assert_eq!(
+        is_in_check(game_data),
+        !checking_pieces(game_data, game_data.to_move).is_empty(),
+        "{context}"
+    );
+    for mv in capture_moves(game_data) {
+        assert!(
+            moves.contains(&mv),
+            "capture {} isn't legal; {context}",
+            move_to_uci(&mv)
+        );
+    }
+
+    // nothing is lost on the way through FEN
+    let reread = from_fen(&to_fen(game_data)).unwrap();
+    assert_eq!(reread.board, game_data.board, "{context}");
+    assert_eq!(reread.to_move, game_data.to_move, "{context}");
+    assert_eq!(
+        reread.moved_2_squares, game_data.moved_2_squares,
+        "{context}"
+    );
+    for color in [PieceColor::White, PieceColor::Black] {
+        assert_eq!(
+            castling_flags(&reread, color),
+            castling_flags(game_data, color),
+            "{context}"
+        );
+    }
+    assert_eq!(reread.hash, game_data.hash, "{context}");
+}
+
+/// What playing `mv` must and mustn't change.
+#[cfg(test)]
+fn assert_move_invariants(
+    before: &GameData,
+    mv: &Move,
+    after: &GameData,
+    coverage: &mut RandomGameCoverage,
+    context: &str,
+) {
+    let mover = before.to_move;
+    let piece = before.board[&mv.from];
+    assert_eq!(piece.get_color(), mover, "{context}");
+    assert_eq!(after.to_move, mover.get_opposite(), "{context}");
+    assert!(
+        checking_pieces(after, mover).is_empty(),
+        "left the king in check; {context}"
+    );
+    assert!(!after.board.contains_key(&mv.from), "{context}");
+
+    // what lands on the destination square
+    let promoted = matches!(piece, PieceType::Pawn(_)) && (mv.to.y == 0 || mv.to.y == 7);
+    assert_eq!(promoted, mv.promotion.is_some(), "{context}");
+    let landed = mv.promotion.unwrap_or(piece);
+    assert_eq!(after.board.get(&mv.to), Some(&landed), "{context}");
+    if let Some(promotion) = mv.promotion {
+        assert_eq!(promotion.get_color(), mover, "{context}");
+        assert!(
+            !matches!(promotion, PieceType::Pawn(_) | PieceType::King(_)),
+            "{context}"
+        );
+        coverage.promotions += 1;
+    }
+
+    // a capture removes exactly one enemy piece, en passant from beside the
+    // destination
+    let en_passant = matches!(piece, PieceType::Pawn(_))
+        && mv.from.x != mv.to.x
+        && !before.board.contains_key(&mv.to);
+    if en_passant {
+        assert_eq!(before.en_passant_target(), Some(mv.to), "{context}");
+        let taken = Position {
+            x: mv.to.x,
+            y: mv.from.y,
+        };
+        assert_eq!(
+            before.board.get(&taken),
+            Some(&PieceType::Pawn(mover.get_opposite())),
+            "{context}"
+        );
+        assert!(!after.board.contains_key(&taken), "{context}");
+        coverage.en_passant += 1;
+    }
+    let captured = before.board.contains_key(&mv.to) || en_passant;
+    if let Some(&victim) = before.board.get(&mv.to) {
+        assert_eq!(victim.get_color(), mover.get_opposite(), "{context}");
+        assert!(!matches!(victim, PieceType::King(_)), "{context}");
+    }
+    assert_eq!(
+        after.board.len() + usize::from(captured),
+        before.board.len(),
+        "{context}"
+    );
+    if captured {
+        coverage.captures += 1;
+    }
+
+    // castling moves the rook across the king, from a side still allowed to
+    let castled = matches!(piece, PieceType::King(_)) && (mv.from.x - mv.to.x).abs() == 2;
+    if castled {
+        let (king_side, queen_side) = castling_flags(before, mover);
+        let (rook_from, rook_to) = if mv.to.x == 6 {
+            assert!(king_side, "{context}");
+            (7, 5)
+        } else {
+            assert!(queen_side, "{context}");
+            (0, 3)
+        };
+        let on_rank = |x| Position { x, y: mv.from.y };
+        assert!(!after.board.contains_key(&on_rank(rook_from)), "{context}");
+        assert_eq!(
+            after.board.get(&on_rank(rook_to)),
+            Some(&PieceType::Rook(mover)),
+            "{context}"
+        );
+        coverage.castles += 1;
+    }
+
+    // rights are only ever lost
+    for color in [PieceColor::White, PieceColor::Black] {
+        let (king_before, queen_before) = castling_flags(before, color);
+        let (king_after, queen_after) = castling_flags(after, color);
+        assert!(king_before || !king_after, "{context}");
+        assert!(queen_before || !queen_after, "{context}");
+    }
+    if matches!(piece, PieceType::King(_)) {
+        assert_eq!(castling_flags(after, mover), (false, false), "{context}");
+    }
+
+    // SAN names this move and no other; quiet moves are spelled plainly and
+    // the slowest to look up, so only the others are tried
+    let checks = !checking_pieces(after, after.to_move).is_empty();
+    if !(captured || castled || promoted || checks) {
+        return;
+    }
+    let san = crate::san::move_to_san(before, mv);
+    assert_eq!(
+        crate::san::san_to_move(before, &san),
+        Some(*mv),
+        "{san}; {context}"
+    );
+    assert_eq!(
+        san.ends_with('#'),
+        game_status(after) == GameStatus::Checkmate,
+        "{san}; {context}"
+    );
+}
+
+/// Plays random legal moves from `start` until the game ends or `max_plies`
+/// is reached, checking every position and move on the way.
+#[cfg(test)]
+fn random_game(start: &GameData, seed: u64, max_plies: usize, coverage: &mut RandomGameCoverage) {
+    use crate::engine::random_move;
+    use crate::random::SplitMix64;
+
+    let mut rng = SplitMix64::new(seed);
+    let mut game_data = start.clone();
+    let mut line = Vec::new();
+    loop {
+        let context = format!(
+            "{} seed {seed} after {}",
+            crate::fen::to_fen(start),
+            line.join(" ")
+        );
+        assert_position_invariants(&game_data, &context);
+        if is_in_check(&game_data) {
+            coverage.checks += 1;
+        }
+        if line.len() == max_plies {
+            return;
+        }
+        // the rare moves are all tried, whichever one gets played
+        for mv in generate_moves_detailed(&game_data) {
+            let piece = game_data.board[&mv.from];
+            let special = mv.promotion.is_some()
+                || matches!(piece, PieceType::King(_)) && (mv.from.x - mv.to.x).abs() == 2
+                || matches!(piece, PieceType::Pawn(_))
+                    && game_data.en_passant_target() == Some(mv.to);
+            if special {
+                let context = format!("{context} {} (not played)", move_to_uci(&mv));
+                assert_move_invariants(
+                    &game_data,
+                    &mv,
+                    &apply_move(&game_data, &mv),
+                    coverage,
+                    &context,
+                );
+            }
+        }
+        let Some(mv) = random_move(&game_data, &mut rng) else {
+            match game_status(&game_data) {
+                GameStatus::Checkmate => coverage.checkmates += 1,
+                GameStatus::Stalemate => coverage.stalemates += 1,
+                GameStatus::Ongoing => unreachable!("{context}"),
+            }
+            return;
+        };
+        let after = apply_move(&game_data, &mv);
+        let context = format!("{context} {}", move_to_uci(&mv));
+        assert_move_invariants(&game_data, &mv, &after, coverage, &context);
+        line.push(move_to_uci(&mv));
+        coverage.plies += 1;
+        game_data = after;
+    }
+}
+
+#[test]
+fn random_games_keep_the_rules() {
+    let mut coverage = RandomGameCoverage::default();
+    for seed in 0..200 {
+        random_game(&GameData::default(), seed, 200, &mut coverage);
+    }
+    // make sure the seeds reach every kind of special move, or the checks
+    // above prove little
+    assert!(coverage.plies > 1000, "{coverage:?}");
+    assert!(coverage.captures > 0, "{coverage:?}");
+    assert!(coverage.en_passant > 0, "{coverage:?}");
+    assert!(coverage.castles > 0, "{coverage:?}");
+    assert!(coverage.promotions > 0, "{coverage:?}");
+    assert!(coverage.checks > 0, "{coverage:?}");
+}
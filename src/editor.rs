@@ -0,0 +1,92 @@
+use crate::chess::{
+    validate_position, Castling, GameData, PieceColor, PieceType, Position, PositionError,
+};
+use crate::fen::{from_fen, to_fen};
+use sdl2::keyboard::Keycode;
+
+/// The pieces offered for placement, white first, in palette order.
+pub const PALETTE: [PieceType; 12] = [
+    PieceType::King(PieceColor::White),
+    PieceType::Queen(PieceColor::White),
+    PieceType::Rook(PieceColor::White),
+    PieceType::Bishop(PieceColor::White),
+    PieceType::Knight(PieceColor::White),
+    PieceType::Pawn(PieceColor::White),
+    PieceType::King(PieceColor::Black),
+    PieceType::Queen(PieceColor::Black),
+    PieceType::Rook(PieceColor::Black),
+    PieceType::Bishop(PieceColor::Black),
+    PieceType::Knight(PieceColor::Black),
+    PieceType::Pawn(PieceColor::Black),
+];
+
+/// A position being set up by hand. Squares are filled with the chosen
+/// palette piece and emptied one by one; the keys set the rest:
+///
+/// - `W`/`B` choose the side to move
+/// - `1`–`4` toggle `K`, `Q`, `k` and `q` castling
+/// - `Backspace` empties the whole board
+pub struct Editor {
+    game_data: GameData,
+    pub piece: PieceType,
+}
+
+impl Editor {
+    pub fn new(mut game_data: GameData) -> Self {
+        // a hand-made position has no double push to take en passant
+        game_data.moved_2_squares = None;
+        Self {
+            game_data,
+            piece: PALETTE[0],
+        }
+    }
+    pub fn position(&self) -> &GameData {
+        &self.game_data
+    }
+    pub fn place(&mut self, square: Position) {
+        self.game_data.board.insert(square, self.piece);
+    }
+    pub fn clear(&mut self, square: Position) {
+        self.game_data.board.remove(&square);
+    }
+
+    pub fn key(&mut self, key: Keycode) {
+        match key {
+            Keycode::W => self.game_data.to_move = PieceColor::White,
+            Keycode::B => self.game_data.to_move = PieceColor::Black,
+            Keycode::Num1 => self.castling(PieceColor::White).king_side ^= true,
+            Keycode::Num2 => self.castling(PieceColor::White).queen_side ^= true,
+            Keycode::Num3 => self.castling(PieceColor::Black).king_side ^= true,
+            Keycode::Num4 => self.castling(PieceColor::Black).queen_side ^= true,
+            Keycode::Backspace => self.game_data.board.clear(),
+            _ => {}
+        }
+    }
+    fn castling(&mut self, color: PieceColor) -> &mut Castling {
+        self.game_data.castling.entry(color).or_insert(Castling {
+            king_side: false,
+            queen_side: false,
+        })
+    }
+
+    /// Side to move and castling rights as in FEN, e.g. `"w KQkq"`.
+    pub fn status(&self) -> String {
+        let fen = self.fen();
+        fen.split_whitespace()
+            .skip(1)
+            .take(2)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+    pub fn fen(&self) -> String {
+        to_fen(&self.game_data)
+    }
+
+    /// The finished position, ready to play from if it is a legal one. Going
+    /// through FEN recomputes which pawns may still double push.
+    pub fn finish(&self) -> Result<GameData, Vec<PositionError>> {
+        let game_data = from_fen(&self.fen()).expect("to_fen output parses");
+        validate_position(&game_data)?;
+        Ok(game_data)
+    }
+}
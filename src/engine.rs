@@ -0,0 +1,1726 @@
+#![allow(dead_code)]
+use crate::chess::{
+    apply_move, capture_moves, generate_moves_detailed, has_legal_moves, is_in_check,
+    legal_moves_flat, piece_moves, Board, GameData, Move, PieceColor, PieceType, Position,
+};
+use crate::random::Rng;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+pub const MATE_SCORE: i32 = 100_000;
+pub const MAX_DEPTH: u32 = 64;
+/// Null-move pruning starts at this remaining depth and searches the pass
+/// this many plies shallower than a real move.
+const NULL_MOVE_MIN_DEPTH: u32 = 3;
+const NULL_MOVE_REDUCTION: u32 = 2;
+/// Half the width of the first aspiration window, in centipawns; each failed
+/// try widens it fourfold.
+const ASPIRATION_WINDOW: i32 = 50;
+
+/// Material in centipawns, White's minus Black's.
+pub fn material_balance(board: &Board) -> i32 {
+    board
+        .values()
+        .map(|&piece| match piece.get_color() {
+            PieceColor::White => piece.value(),
+            PieceColor::Black => -piece.value(),
+        })
+        .sum()
+}
+
+/// The pieces White and Black have lost since `start`, most valuable first.
+/// Pieces beyond the starting count of their kind are promoted pawns, so they
+/// are taken off the pawns missing rather than listed as captures.
+pub fn captured_pieces(start: &Board, current: &Board) -> (Vec<PieceType>, Vec<PieceType>) {
+    let count = |board: &Board, piece| board.values().filter(|&&p| p == piece).count();
+    let lost = |color: PieceColor| {
+        let mut lost = Vec::new();
+        let mut promoted = 0;
+        for piece in [
+            PieceType::Queen(color),
+            PieceType::Rook(color),
+            PieceType::Bishop(color),
+            PieceType::Knight(color),
+        ] {
+            let (before, now) = (count(start, piece), count(current, piece));
+            lost.extend(std::iter::repeat_n(piece, before.saturating_sub(now)));
+            promoted += now.saturating_sub(before);
+        }
+        let pawn = PieceType::Pawn(color);
+        let missing = count(start, pawn).saturating_sub(count(current, pawn));
+        lost.extend(std::iter::repeat_n(pawn, missing.saturating_sub(promoted)));
+        lost
+    };
+    (lost(PieceColor::White), lost(PieceColor::Black))
+}
+
+const PHASE_MAX: i32 = 256;
+const OPENING_PHASE: i32 = 224;
+const ENDGAME_PHASE: i32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamePhase {
+    Opening,
+    Middlegame,
+    Endgame,
+}
+
+/// Remaining non-pawn material scaled to `0..=256`: 256 with every minor,
+/// rook and queen still on the board, 0 with none left.
+pub fn phase_value(board: &Board) -> i32 {
+    let material: i32 = board
+        .values()
+        .map(|piece| match piece {
+            PieceType::Queen(_) => 4,
+            PieceType::Rook(_) => 2,
+            PieceType::Bishop(_) | PieceType::Knight(_) => 1,
+            PieceType::King(_) | PieceType::Pawn(_) => 0,
+        })
+        .sum();
+    material.min(24) * PHASE_MAX / 24
+}
+
+pub fn game_phase(board: &Board) -> GamePhase {
+    match phase_value(board) {
+        phase if phase >= OPENING_PHASE => GamePhase::Opening,
+        phase if phase <= ENDGAME_PHASE => GamePhase::Endgame,
+        _ => GamePhase::Middlegame,
+    }
+}
+
+// Piece-square tables in centipawns, laid out as seen from White's side with
+// rank 8 first. Only pawns and the king change between the middlegame and the
+// endgame.
+#[rustfmt::skip]
+const PAWN_MG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    10,  10,  20,  30,  30,  20,  10,  10,
+     5,   5,  10,  25,  25,  10,   5,   5,
+     0,   0,   0,  20,  20,   0,   0,   0,
+     5,  -5, -10,   0,   0, -10,  -5,   5,
+     5,  10,  10, -20, -20,  10,  10,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const PAWN_EG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    80,  80,  80,  80,  80,  80,  80,  80,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    30,  30,  30,  30,  30,  30,  30,  30,
+    20,  20,  20,  20,  20,  20,  20,  20,
+    10,  10,  10,  10,  10,  10,  10,  10,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+   -50, -40, -30, -30, -30, -30, -40, -50,
+   -40, -20,   0,   0,   0,   0, -20, -40,
+   -30,   0,  10,  15,  15,  10,   0, -30,
+   -30,   5,  15,  20,  20,  15,   5, -30,
+   -30,   0,  15,  20,  20,  15,   0, -30,
+   -30,   5,  10,  15,  15,  10,   5, -30,
+   -40, -20,   0,   5,   5,   0, -20, -40,
+   -50, -40, -30, -30, -30, -30, -40, -50,
+];
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+   -20, -10, -10, -10, -10, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   5,   5,  10,  10,   5,   5, -10,
+   -10,   0,  10,  10,  10,  10,   0, -10,
+   -10,  10,  10,  10,  10,  10,  10, -10,
+   -10,   5,   0,   0,   0,   0,   5, -10,
+   -20, -10, -10, -10, -10, -10, -10, -20,
+];
+#[rustfmt::skip]
+const ROOK: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+     5,  10,  10,  10,  10,  10,  10,   5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+     0,   0,   0,   5,   5,   0,   0,   0,
+];
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,   5,   5,   5,   0, -10,
+    -5,   0,   5,   5,   5,   5,   0,  -5,
+     0,   0,   5,   5,   5,   5,   0,  -5,
+   -10,   5,   5,   5,   5,   5,   0, -10,
+   -10,   0,   5,   0,   0,   0,   0, -10,
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+#[rustfmt::skip]
+const KING_MG: [i32; 64] = [
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -20, -30, -30, -40, -40, -30, -30, -20,
+   -10, -20, -20, -20, -20, -20, -20, -10,
+    20,  20,   0,   0,   0,   0,  20,  20,
+    20,  30,  10,   0,   0,  10,  30,  20,
+];
+#[rustfmt::skip]
+const KING_EG: [i32; 64] = [
+   -50, -40, -30, -20, -20, -30, -40, -50,
+   -30, -20, -10,   0,   0, -10, -20, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -30,   0,   0,   0,   0, -30, -30,
+   -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+/// Positional bonus for `piece` standing on `square`, from its owner's point
+/// of view, returned as a (middlegame, endgame) pair.
+fn piece_square_bonus(piece: PieceType, square: Position) -> (i32, i32) {
+    let rank = match piece.get_color() {
+        PieceColor::White => 7 - square.y,
+        PieceColor::Black => square.y,
+    };
+    let index = (rank * 8 + square.x) as usize;
+    match piece {
+        PieceType::Pawn(_) => (PAWN_MG[index], PAWN_EG[index]),
+        PieceType::Knight(_) => (KNIGHT[index], KNIGHT[index]),
+        PieceType::Bishop(_) => (BISHOP[index], BISHOP[index]),
+        PieceType::Rook(_) => (ROOK[index], ROOK[index]),
+        PieceType::Queen(_) => (QUEEN[index], QUEEN[index]),
+        PieceType::King(_) => (KING_MG[index], KING_EG[index]),
+    }
+}
+
+fn sign(color: PieceColor) -> i32 {
+    match color {
+        PieceColor::White => 1,
+        PieceColor::Black => -1,
+    }
+}
+
+/// Blends a middlegame and an endgame score by `phase`, see `phase_value`.
+fn taper(middlegame: i32, endgame: i32, phase: i32) -> i32 {
+    (middlegame * phase + endgame * (PHASE_MAX - phase)) / PHASE_MAX
+}
+
+/// Centipawns per pseudo-legal move. Short-range pieces gain more from each
+/// extra square than the queen, which has plenty anyway.
+fn mobility_weight(piece: PieceType) -> i32 {
+    match piece {
+        PieceType::Knight(_) | PieceType::Bishop(_) => 4,
+        PieceType::Rook(_) => 2,
+        PieceType::Queen(_) => 1,
+        PieceType::King(_) | PieceType::Pawn(_) => 0,
+    }
+}
+
+/// Pseudo-legal moves of every piece on the board, whichever side is to move,
+/// weighted by piece type.
+fn mobility(board: &Board) -> i32 {
+    board
+        .iter()
+        .filter(|(_, &piece)| mobility_weight(piece) != 0)
+        .map(|(&square, &piece)| {
+            sign(piece.get_color())
+                * mobility_weight(piece)
+                * piece_moves(board, square).len() as i32
+        })
+        .sum()
+}
+
+const DOUBLED_PAWN: i32 = -15;
+const ISOLATED_PAWN: i32 = -12;
+/// Bonus for a passed pawn by how far it has advanced, from its own first rank.
+const PASSED_PAWN: [i32; 8] = [0, 5, 10, 20, 35, 60, 100, 0];
+
+/// Pawn structure, White's minus Black's: a penalty for each pawn sharing its
+/// file with a friendly pawn in front of it and for each pawn with no friendly
+/// pawn on a neighbouring file, and a bonus growing with the rank for each
+/// pawn no enemy pawn can stop on its own or a neighbouring file.
+pub fn pawn_structure_score(board: &Board) -> i32 {
+    let pawns = |color: PieceColor| -> Vec<Position> {
+        board
+            .iter()
+            .filter(|(_, &piece)| piece == PieceType::Pawn(color))
+            .map(|(&square, _)| square)
+            .collect()
+    };
+    let mut score = 0;
+    for color in [PieceColor::White, PieceColor::Black] {
+        let own = pawns(color);
+        let enemy = pawns(color.get_opposite());
+        let forward = sign(color) as i8;
+        let mut term = 0;
+        for pawn in &own {
+            let ahead = |other: &Position| (other.y - pawn.y) * forward > 0;
+            if own.iter().any(|other| other.x == pawn.x && ahead(other)) {
+                term += DOUBLED_PAWN;
+            }
+            if !own.iter().any(|other| (other.x - pawn.x).abs() == 1) {
+                term += ISOLATED_PAWN;
+            }
+            if !enemy
+                .iter()
+                .any(|other| (other.x - pawn.x).abs() <= 1 && ahead(other))
+            {
+                let rank = match color {
+                    PieceColor::White => pawn.y,
+                    PieceColor::Black => 7 - pawn.y,
+                };
+                term += PASSED_PAWN[rank as usize];
+            }
+        }
+        score += sign(color) * term;
+    }
+    score
+}
+
+const SHIELD_PAWN: i32 = 12;
+const SHIELD_PAWN_ADVANCED: i32 = 6;
+const BACK_RANK_WEAKNESS: i32 = -30;
+
+/// Whether `color`'s king could be mated on its back rank: the king stands on
+/// its first rank, every square in front of it (its file and the ones beside
+/// it on the board) holds one of its own unmoved pawns, so there is no luft,
+/// and no rook or queen of its own stands on that rank to cover a check
+/// along it.
+pub fn back_rank_weakness(board: &Board, color: PieceColor) -> bool {
+    let (back_rank, pawn_rank) = match color {
+        PieceColor::White => (0, 1),
+        PieceColor::Black => (7, 6),
+    };
+    let Some(king) = board
+        .iter()
+        .find_map(|(&pos, &piece)| (piece == PieceType::King(color)).then_some(pos))
+    else {
+        return false;
+    };
+    if king.y != back_rank {
+        return false;
+    }
+    let walled_in = (king.x - 1..=king.x + 1)
+        .filter(|&x| (0..8).contains(&x))
+        .all(|x| board.get(&Position { x, y: pawn_rank }) == Some(&PieceType::Pawn(color)));
+    let covered = board.iter().any(|(pos, &piece)| {
+        pos.y == back_rank
+            && matches!(piece, PieceType::Rook(c) | PieceType::Queen(c) if c == color)
+    });
+    walled_in && !covered
+}
+
+/// Friendly pawns in front of each king, on its file and the two beside it:
+/// full credit one rank ahead, half two ranks ahead, less a penalty for a
+/// `back_rank_weakness` while the opponent has a rook or queen to use it.
+/// Only counts in the middlegame, an endgame king is better off active.
+fn king_safety(board: &Board, phase: i32) -> i32 {
+    let mut score = 0;
+    for (&king, &piece) in board {
+        let PieceType::King(color) = piece else {
+            continue;
+        };
+        let forward = sign(color) as i8;
+        let mut shield = 0;
+        for x in king.x - 1..=king.x + 1 {
+            for (distance, bonus) in [(1, SHIELD_PAWN), (2, SHIELD_PAWN_ADVANCED)] {
+                let square = Position {
+                    x,
+                    y: king.y + distance * forward,
+                };
+                if board.get(&square) == Some(&PieceType::Pawn(color)) {
+                    shield += bonus;
+                }
+            }
+        }
+        let opponent = color.get_opposite();
+        let heavy_opponent = board.values().any(
+            |&piece| matches!(piece, PieceType::Rook(c) | PieceType::Queen(c) if c == opponent),
+        );
+        if heavy_opponent && back_rank_weakness(board, color) {
+            shield += BACK_RANK_WEAKNESS;
+        }
+        score += sign(color) * shield;
+    }
+    taper(score, 0, phase)
+}
+
+/// The terms `evaluate` adds up, each in centipawns from White's point of
+/// view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EvalBreakdown {
+    pub material: i32,
+    /// Piece-square bonuses, blended between the middlegame and endgame tables.
+    pub piece_square: i32,
+    /// Room to move for the pieces, see `mobility_weight`.
+    pub mobility: i32,
+    /// Pawn shields in front of the kings and weak back ranks.
+    pub king_safety: i32,
+    /// Doubled, isolated and passed pawns, see `pawn_structure_score`.
+    pub pawn_structure: i32,
+}
+
+impl EvalBreakdown {
+    pub fn total(&self) -> i32 {
+        self.material + self.piece_square + self.mobility + self.king_safety + self.pawn_structure
+    }
+}
+
+impl std::fmt::Display for EvalBreakdown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "material      {:>6}", self.material)?;
+        writeln!(f, "piece-square  {:>6}", self.piece_square)?;
+        writeln!(f, "mobility      {:>6}", self.mobility)?;
+        writeln!(f, "king safety   {:>6}", self.king_safety)?;
+        writeln!(f, "pawns         {:>6}", self.pawn_structure)?;
+        write!(f, "total         {:>6}", self.total())
+    }
+}
+
+/// `evaluate`, term by term.
+pub fn evaluate_explain(game_data: &GameData) -> EvalBreakdown {
+    let board = &game_data.board;
+    let phase = phase_value(board);
+    let (mut middlegame, mut endgame) = (0, 0);
+    for (&square, &piece) in board {
+        let (mg_bonus, eg_bonus) = piece_square_bonus(piece, square);
+        middlegame += sign(piece.get_color()) * mg_bonus;
+        endgame += sign(piece.get_color()) * eg_bonus;
+    }
+    EvalBreakdown {
+        material: material_balance(board),
+        piece_square: taper(middlegame, endgame, phase),
+        mobility: mobility(board),
+        king_safety: king_safety(board, phase),
+        pawn_structure: pawn_structure_score(board),
+    }
+}
+
+/// Static evaluation in centipawns from White's point of view, the sum of the
+/// terms in `evaluate_explain`: positive when White stands better, whoever is
+/// to move. The search wants `evaluate_stm` instead.
+pub fn evaluate(game_data: &GameData) -> i32 {
+    evaluate_explain(game_data).total()
+}
+
+fn least_valuable_attacker(
+    board: &Board,
+    target: Position,
+    by: PieceColor,
+) -> Option<(Position, PieceType)> {
+    let pawn_dy = if by == PieceColor::White { -1 } else { 1 };
+    let mut candidates = vec![
+        Position {
+            x: target.x - 1,
+            y: target.y + pawn_dy,
+        },
+        Position {
+            x: target.x + 1,
+            y: target.y + pawn_dy,
+        },
+    ];
+    for (dx, dy) in [
+        (1, 2),
+        (2, 1),
+        (2, -1),
+        (1, -2),
+        (-1, -2),
+        (-2, -1),
+        (-2, 1),
+        (-1, 2),
+    ] {
+        candidates.push(Position {
+            x: target.x + dx,
+            y: target.y + dy,
+        });
+    }
+    for dx in -1i8..=1 {
+        for dy in -1i8..=1 {
+            // first piece met along each line, for the sliders
+            let mut pos = Position {
+                x: target.x + dx,
+                y: target.y + dy,
+            };
+            while (dx, dy) != (0, 0) && (0..8).contains(&pos.x) && (0..8).contains(&pos.y) {
+                if board.contains_key(&pos) {
+                    candidates.push(pos);
+                    break;
+                }
+                pos = Position {
+                    x: pos.x + dx,
+                    y: pos.y + dy,
+                };
+            }
+        }
+    }
+    candidates
+        .into_iter()
+        .filter_map(|pos| board.get(&pos).map(|&piece| (pos, piece)))
+        .filter(|&(pos, piece)| {
+            let (dx, dy) = (pos.x - target.x, pos.y - target.y);
+            let adjacent = dx.abs() <= 1 && dy.abs() <= 1;
+            piece.get_color() == by
+                && match piece {
+                    PieceType::Pawn(_) => dy == pawn_dy && dx.abs() == 1,
+                    PieceType::Knight(_) => dx.abs() + dy.abs() == 3 && dx != 0 && dy != 0,
+                    PieceType::Bishop(_) => dx.abs() == dy.abs(),
+                    PieceType::Rook(_) => dx == 0 || dy == 0,
+                    PieceType::Queen(_) => dx.abs() == dy.abs() || dx == 0 || dy == 0,
+                    PieceType::King(_) => adjacent,
+                }
+        })
+        .min_by_key(|&(_, piece)| see_value(piece))
+}
+
+fn see_value(piece: PieceType) -> i32 {
+    match piece {
+        // capturing with the king only pays off if nothing can recapture
+        PieceType::King(_) => MATE_SCORE,
+        _ => piece.value(),
+    }
+}
+
+/// Static exchange evaluation: the material `side` nets by starting a capture
+/// sequence on `target`, with both sides always recapturing with their least
+/// valuable attacker and free to stop once it no longer pays.
+pub fn see(board: &Board, target: Position, side: PieceColor) -> i32 {
+    let mut board = board.clone();
+    let mut gains = Vec::new();
+    let mut on_square = board.get(&target).map_or(0, |&piece| see_value(piece));
+    let mut color = side;
+    while let Some((from, attacker)) = least_valuable_attacker(&board, target, color) {
+        gains.push(on_square);
+        board.remove(&from);
+        // removing the attacker uncovers any slider lined up behind it
+        board.insert(target, attacker);
+        on_square = see_value(attacker);
+        color = color.get_opposite();
+    }
+    let Some((&first, rest)) = gains.split_first() else {
+        return 0;
+    };
+    let reply = rest
+        .iter()
+        .rev()
+        .fold(0, |score, &gain| (gain - score).max(0));
+    first - reply
+}
+
+/// Squares of the side to move's pieces that the opponent wins material by
+/// capturing, as `see` counts it: undefended, or defended too few times or
+/// by too valuable pieces. The king never hangs. Sorted.
+pub fn hanging_pieces(game_data: &GameData) -> Vec<Position> {
+    let opponent = game_data.to_move.get_opposite();
+    let mut hanging: Vec<Position> = game_data
+        .board
+        .iter()
+        .filter(|(_, &piece)| {
+            piece.get_color() == game_data.to_move && !matches!(piece, PieceType::King(_))
+        })
+        .map(|(&pos, _)| pos)
+        .filter(|&pos| see(&game_data.board, pos, opponent) > 0)
+        .collect();
+    hanging.sort();
+    hanging
+}
+
+/// Material a move wins before any recapture: the captured piece, including a
+/// pawn taken en passant.
+fn captured_piece(game_data: &GameData, mv: &Move) -> Option<PieceType> {
+    if let Some(&victim) = game_data.board.get(&mv.to) {
+        return Some(victim);
+    }
+    match game_data.board.get(&mv.from) {
+        Some(&PieceType::Pawn(color)) if mv.from.x != mv.to.x => {
+            Some(PieceType::Pawn(color.get_opposite()))
+        }
+        _ => None,
+    }
+}
+
+fn mvv_lva_score(game_data: &GameData, mv: &Move) -> i32 {
+    let promotion = mv.promotion.map_or(0, |piece| piece.value());
+    match captured_piece(game_data, mv) {
+        Some(victim) => {
+            let attacker = game_data.board.get(&mv.from).unwrap().value();
+            // any capture goes ahead of every quiet move
+            MATE_SCORE + 10 * victim.value() - attacker + promotion
+        }
+        None => promotion,
+    }
+}
+
+/// Sorts captures first, most valuable victim first and, among equal victims,
+/// least valuable attacker first. Quiet moves keep their relative order.
+pub fn order_moves(game_data: &GameData, moves: &mut [Move]) {
+    moves.sort_by_cached_key(|mv| -mvv_lva_score(game_data, mv));
+}
+
+/// `evaluate` from the point of view of the side to move: positive when the
+/// player about to move stands better, as negamax scores every node.
+pub fn evaluate_stm(game_data: &GameData) -> i32 {
+    match game_data.to_move {
+        PieceColor::White => evaluate(game_data),
+        PieceColor::Black => -evaluate(game_data),
+    }
+}
+
+/// What the search learned about a position: the best move it found there
+/// and how deep below it that search went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TtEntry {
+    pub best_move: Move,
+    pub depth: u32,
+}
+
+/// The best moves found by the search, by position hash. A position searched
+/// again tries its stored move first, and the stored moves strung together
+/// give the expected line, see `extract_pv`.
+#[derive(Debug, Clone, Default)]
+pub struct TranspositionTable {
+    entries: HashMap<u64, TtEntry>,
+}
+
+impl TranspositionTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn get(&self, hash: u64) -> Option<TtEntry> {
+        self.entries.get(&hash).copied()
+    }
+    pub fn best_move(&self, hash: u64) -> Option<Move> {
+        self.get(hash).map(|entry| entry.best_move)
+    }
+    /// Keeps `best_move` unless the position already has an entry from a
+    /// deeper search.
+    pub fn store(&mut self, hash: u64, best_move: Move, depth: u32) {
+        let entry = TtEntry { best_move, depth };
+        match self.entries.get_mut(&hash) {
+            Some(old) if old.depth > depth => {}
+            Some(old) => *old = entry,
+            None => {
+                self.entries.insert(hash, entry);
+            }
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// The line the search expects from `game_data`: the stored best move of each
+/// position in turn, at most `max_len` of them. Stops where the table has no
+/// move, or one that isn't legal there after a hash collision, and after a
+/// move that repeats a position of the line.
+pub fn extract_pv(game_data: &GameData, tt: &TranspositionTable, max_len: usize) -> Vec<Move> {
+    let mut pv = Vec::new();
+    let mut position = game_data.clone();
+    let mut seen = vec![position.hash];
+    while pv.len() < max_len {
+        let Some(mv) = tt.best_move(position.hash) else {
+            break;
+        };
+        if !generate_moves_detailed(&position).contains(&mv) {
+            break;
+        }
+        position = apply_move(&position, &mv);
+        pv.push(mv);
+        if seen.contains(&position.hash) {
+            break;
+        }
+        seen.push(position.hash);
+    }
+    pv
+}
+
+/// The position with the side to move passing: the other side to move and no
+/// en passant capture left.
+fn pass_turn(game_data: &GameData) -> GameData {
+    let mut passed = game_data.clone();
+    passed.to_move = game_data.to_move.get_opposite();
+    passed.moved_2_squares = None;
+    passed.hash = crate::zobrist::hash(&passed);
+    passed
+}
+
+/// Whether `color` has a piece besides the king and pawns. Without one,
+/// zugzwang is common enough that passing says nothing about the position.
+fn has_piece_besides_pawns(board: &Board, color: PieceColor) -> bool {
+    board.values().any(|&piece| {
+        piece.get_color() == color && !matches!(piece, PieceType::King(_) | PieceType::Pawn(_))
+    })
+}
+
+/// A move that neither captures nor promotes.
+fn is_quiet_move(game_data: &GameData, mv: &Move) -> bool {
+    mv.promotion.is_none() && captured_piece(game_data, mv).is_none()
+}
+
+pub struct Search {
+    deadline: Option<Instant>,
+    pub nodes: u64,
+    aborted: bool,
+    /// Resolve captures at the leaves instead of trusting the static eval.
+    pub quiescence: bool,
+    /// Order quiet moves by `killers` and `quiet_history` after the captures.
+    pub heuristics: bool,
+    /// Prune a node when passing the turn still fails high. Never applied in
+    /// check, twice in a row or with only the king and pawns left.
+    pub null_move: bool,
+    /// What a draw costs the side to move at the root, in centipawns. Above
+    /// zero the search plays on rather than settle for a repetition or
+    /// stalemate it could avoid, below zero it takes them more readily.
+    pub contempt: i32,
+    /// Let `deepen` search each iteration in a narrow window around the
+    /// previous score, widening it only when the score falls outside.
+    pub aspiration: bool,
+    /// Per ply, the last two quiet moves that caused a beta cutoff there,
+    /// the latest first.
+    pub killers: Vec<[Option<Move>; 2]>,
+    /// Quiet moves by origin and target square, scored by the cutoffs they
+    /// caused, deeper searches counting for more.
+    pub quiet_history: HashMap<(Position, Position), u32>,
+    /// Best moves of the positions searched so far, kept between iterations.
+    pub tt: TranspositionTable,
+    /// Position hashes of the game so far followed by the current search path.
+    history: Vec<u64>,
+}
+
+impl Search {
+    pub fn new(deadline: Option<Instant>) -> Self {
+        Self {
+            deadline,
+            nodes: 0,
+            aborted: false,
+            quiescence: true,
+            heuristics: true,
+            null_move: true,
+            contempt: 0,
+            aspiration: true,
+            killers: Vec::new(),
+            quiet_history: HashMap::new(),
+            tt: TranspositionTable::new(),
+            history: Vec::new(),
+        }
+    }
+
+    /// Positions played before the one being searched, oldest first. A line
+    /// that reaches one of them for the third time is scored as a draw.
+    pub fn with_history(mut self, history: &[GameData]) -> Self {
+        self.history = history.iter().map(|game_data| game_data.hash).collect();
+        self
+    }
+
+    /// Sets `contempt`.
+    pub fn with_contempt(mut self, contempt: i32) -> Self {
+        self.contempt = contempt;
+        self
+    }
+
+    /// A draw scored for the side to move `ply` plies below the root.
+    fn draw_score(&self, ply: u32) -> i32 {
+        if ply.is_multiple_of(2) {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+
+    fn is_repetition(&self, hash: u64) -> bool {
+        self.history.iter().filter(|&&seen| seen == hash).count() >= 2
+    }
+
+    /// `order_moves`, then the quiet moves reordered: the killers of `ply`
+    /// first, the rest by their history score. The move stored in `tt` for
+    /// the position goes ahead of everything.
+    fn order_moves_at(&self, game_data: &GameData, moves: &mut [Move], ply: u32) {
+        order_moves(game_data, moves);
+        if self.heuristics {
+            let quiet_from = moves
+                .iter()
+                .position(|mv| is_quiet_move(game_data, mv))
+                .unwrap_or(moves.len());
+            let killers = self.killers.get(ply as usize).copied().unwrap_or_default();
+            moves[quiet_from..].sort_by_cached_key(|mv| {
+                match killers.iter().position(|&killer| killer == Some(*mv)) {
+                    Some(slot) => (0, slot as u32),
+                    None => (1, u32::MAX - self.quiet_history_score(mv)),
+                }
+            });
+        }
+        if let Some(stored) = self.tt.best_move(game_data.hash) {
+            if let Some(at) = moves.iter().position(|&mv| mv == stored) {
+                moves[..=at].rotate_right(1);
+            }
+        }
+    }
+
+    fn quiet_history_score(&self, mv: &Move) -> u32 {
+        self.quiet_history
+            .get(&(mv.from, mv.to))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Remembers the quiet move `mv` for causing a cutoff at `ply`.
+    fn record_cutoff(&mut self, mv: Move, ply: u32, depth: u32) {
+        let ply = ply as usize;
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+        *self.quiet_history.entry((mv.from, mv.to)).or_insert(0) += depth * depth;
+    }
+
+    fn out_of_time(&mut self) -> bool {
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.aborted = true;
+        }
+        self.aborted
+    }
+
+    /// Extends the search along captures only until the position is quiet. The
+    /// side to move may always "stand pat" on the static evaluation instead.
+    pub fn quiescence(&mut self, game_data: &GameData, ply: u32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.out_of_time() {
+            return 0;
+        }
+        if !has_legal_moves(game_data) {
+            return if is_in_check(game_data) {
+                -MATE_SCORE + ply as i32
+            } else {
+                self.draw_score(ply)
+            };
+        }
+        let stand_pat = evaluate_stm(game_data);
+        if stand_pat >= beta {
+            return beta;
+        }
+        alpha = alpha.max(stand_pat);
+        let mut moves = capture_moves(game_data);
+        order_moves(game_data, &mut moves);
+        for mv in moves {
+            let score = -self.quiescence(&apply_move(game_data, &mv), ply + 1, -beta, -alpha);
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    /// Searches the position with the other side moving first on a null
+    /// window at `beta`, telling whether it still reaches `beta`.
+    fn null_move_fails_high(
+        &mut self,
+        game_data: &GameData,
+        depth: u32,
+        ply: u32,
+        beta: i32,
+    ) -> bool {
+        if !self.null_move
+            || depth < NULL_MOVE_MIN_DEPTH
+            // a mate score is exact, a pass can't prove one
+            || beta.abs() >= MATE_SCORE - MAX_DEPTH as i32
+            || is_in_check(game_data)
+            || !has_piece_besides_pawns(&game_data.board, game_data.to_move)
+        {
+            return false;
+        }
+        let score = -self.negamax(
+            &pass_turn(game_data),
+            depth - 1 - NULL_MOVE_REDUCTION,
+            ply + 1,
+            -beta,
+            -beta + 1,
+            false,
+        );
+        score >= beta && !self.aborted
+    }
+
+    fn negamax(
+        &mut self,
+        game_data: &GameData,
+        depth: u32,
+        ply: u32,
+        mut alpha: i32,
+        beta: i32,
+        allow_null: bool,
+    ) -> i32 {
+        if depth == 0 && self.quiescence {
+            return self.quiescence(game_data, ply, alpha, beta);
+        }
+        self.nodes += 1;
+        if self.out_of_time() {
+            return 0;
+        }
+        let mut moves = generate_moves_detailed(game_data);
+        if moves.is_empty() {
+            return if is_in_check(game_data) {
+                -MATE_SCORE + ply as i32
+            } else {
+                self.draw_score(ply)
+            };
+        }
+        if self.is_repetition(game_data.hash) {
+            return self.draw_score(ply);
+        }
+        if depth == 0 {
+            return evaluate_stm(game_data);
+        }
+        self.history.push(game_data.hash);
+        if allow_null && self.null_move_fails_high(game_data, depth, ply, beta) {
+            self.history.pop();
+            return beta;
+        }
+        self.order_moves_at(game_data, &mut moves, ply);
+        let mut best_move = None;
+        for mv in moves {
+            let score = -self.negamax(
+                &apply_move(game_data, &mv),
+                depth - 1,
+                ply + 1,
+                -beta,
+                -alpha,
+                true,
+            );
+            if score >= beta {
+                if is_quiet_move(game_data, &mv) {
+                    self.record_cutoff(mv, ply, depth);
+                }
+                best_move = Some(mv);
+                alpha = beta;
+                break;
+            }
+            if score > alpha {
+                best_move = Some(mv);
+                alpha = score;
+            }
+        }
+        self.history.pop();
+        if let Some(mv) = best_move.filter(|_| !self.aborted) {
+            self.tt.store(game_data.hash, mv, depth);
+        }
+        alpha
+    }
+
+    /// Searches every root move to `depth` plies. Returns `None` when there is
+    /// no legal move or the deadline interrupted the iteration.
+    pub fn search_root(&mut self, game_data: &GameData, depth: u32) -> Option<(Move, i32)> {
+        self.search_root_window(game_data, depth, -MATE_SCORE - 1, MATE_SCORE + 1)
+    }
+
+    /// `search_root` between `alpha` and `beta`. The score is exact only
+    /// inside the window: at `alpha` or below none of the moves reached it,
+    /// and at `beta` or above the returned move got there first.
+    fn search_root_window(
+        &mut self,
+        game_data: &GameData,
+        depth: u32,
+        mut alpha: i32,
+        beta: i32,
+    ) -> Option<(Move, i32)> {
+        let mut best: Option<(Move, i32)> = None;
+        let mut moves = generate_moves_detailed(game_data);
+        self.order_moves_at(game_data, &mut moves, 0);
+        self.history.push(game_data.hash);
+        for mv in moves {
+            let score = -self.negamax(
+                &apply_move(game_data, &mv),
+                depth.saturating_sub(1),
+                1,
+                -beta,
+                -alpha,
+                true,
+            );
+            if self.aborted {
+                best = None;
+                break;
+            }
+            if best.is_none() || score > alpha {
+                alpha = alpha.max(score);
+                best = Some((mv, score));
+            }
+            if score >= beta {
+                break;
+            }
+        }
+        self.history.pop();
+        if let Some((mv, _)) = best {
+            self.tt.store(game_data.hash, mv, depth);
+        }
+        best
+    }
+
+    /// `search_root` in a window around `guess`, widened and searched again
+    /// until the score lands inside it.
+    fn search_root_aspirated(
+        &mut self,
+        game_data: &GameData,
+        depth: u32,
+        guess: i32,
+    ) -> Option<(Move, i32)> {
+        let mut width = ASPIRATION_WINDOW;
+        loop {
+            if width >= MATE_SCORE {
+                return self.search_root(game_data, depth);
+            }
+            let (alpha, beta) = (guess - width, guess + width);
+            let (mv, score) = self.search_root_window(game_data, depth, alpha, beta)?;
+            if alpha < score && score < beta {
+                return Some((mv, score));
+            }
+            width *= 4;
+        }
+    }
+
+    /// Iterative deepening to `depth` plies, cut short by the deadline.
+    /// Returns the move of the last completed iteration with its score for
+    /// the side to move; if not even depth 1 finished, any legal move scored 0.
+    pub fn deepen(&mut self, game_data: &GameData, depth: u32) -> Option<(Move, i32)> {
+        let mut best: Option<(Move, i32)> = None;
+        for depth in 1..=depth.max(1) {
+            let result = match best {
+                Some((_, guess)) if self.aspiration => {
+                    self.search_root_aspirated(game_data, depth, guess)
+                }
+                _ => self.search_root(game_data, depth),
+            };
+            match result {
+                Some(result) => best = Some(result),
+                None => break,
+            }
+        }
+        best.or_else(|| legal_moves_flat(game_data).first().map(|&mv| (mv, 0)))
+    }
+}
+
+/// Best move for the side to move using a fixed-depth alpha-beta search.
+/// `history` holds the earlier positions of the game, oldest first.
+pub fn best_move(game_data: &GameData, depth: u32, history: &[GameData]) -> Option<Move> {
+    Search::new(None)
+        .with_history(history)
+        .search_root(game_data, depth.max(1))
+        .map(|(mv, _)| mv)
+}
+
+/// Every legal move with the score of a `depth` ply search after it, best
+/// first for the side to move. Each move gets a full window, so the scores
+/// compare across moves rather than only telling the best one apart.
+pub fn analyze(game_data: &GameData, depth: u32) -> Vec<(Move, i32)> {
+    let mut search = Search::new(None);
+    search.history.push(game_data.hash);
+    let mut scored: Vec<(Move, i32)> = generate_moves_detailed(game_data)
+        .into_iter()
+        .map(|mv| {
+            let score = -search.negamax(
+                &apply_move(game_data, &mv),
+                depth.max(1) - 1,
+                1,
+                -MATE_SCORE - 1,
+                MATE_SCORE + 1,
+                true,
+            );
+            (mv, score)
+        })
+        .collect();
+    scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    scored
+}
+
+/// Iterative deepening until `limit` runs out; keeps the result of the last
+/// completed iteration.
+pub fn best_move_timed(
+    game_data: &GameData,
+    limit: Duration,
+    history: &[GameData],
+) -> Option<Move> {
+    best_move_within(game_data, MAX_DEPTH, limit, history).map(|(mv, _)| mv)
+}
+
+/// `Search::deepen` to `depth` plies, cut short when `limit` runs out.
+pub fn best_move_within(
+    game_data: &GameData,
+    depth: u32,
+    limit: Duration,
+    history: &[GameData],
+) -> Option<(Move, i32)> {
+    Search::new(Some(Instant::now() + limit))
+        .with_history(history)
+        .deepen(game_data, depth)
+}
+
+/// A legal move picked uniformly at random, each promotion piece counting as
+/// a move of its own; `None` once the game is over. The weakest opponent
+/// there is, and handy for shaking out the rules with random games.
+pub fn random_move(game_data: &GameData, rng: &mut impl Rng) -> Option<Move> {
+    let moves = generate_moves_detailed(game_data);
+    if moves.is_empty() {
+        return None;
+    }
+    Some(moves[rng.below(moves.len())])
+}
+
+/// Shortest forced checkmate in at most `n` moves of the side to move. The
+/// line alternates attacker and defender moves, the defender always picking
+/// the reply that holds out longest, and ends with the mating move.
+pub fn find_mate(game_data: &GameData, n: u32) -> Option<Vec<Move>> {
+    (1..=n).find_map(|moves| mate_in(game_data, moves))
+}
+
+/// A mate in exactly `n` moves, given that none is shorter.
+fn mate_in(game_data: &GameData, n: u32) -> Option<Vec<Move>> {
+    'attack: for mv in generate_moves_detailed(game_data) {
+        let next = apply_move(game_data, &mv);
+        let replies = generate_moves_detailed(&next);
+        if replies.is_empty() {
+            if is_in_check(&next) {
+                return Some(vec![mv]);
+            }
+            // stalemate
+            continue;
+        }
+        if n == 1 {
+            continue;
+        }
+        let mut longest: Option<Vec<Move>> = None;
+        for reply in replies {
+            let Some(mut line) = find_mate(&apply_move(&next, &reply), n - 1) else {
+                continue 'attack;
+            };
+            if longest.as_ref().is_none_or(|l| line.len() + 1 > l.len()) {
+                line.insert(0, reply);
+                longest = Some(line);
+            }
+        }
+        let mut line = longest.unwrap();
+        line.insert(0, mv);
+        return Some(line);
+    }
+    None
+}
+
+#[test]
+fn evaluate_stm_takes_the_side_to_moves_view() {
+    // White is a queen up either way
+    let white_to_move = crate::fen::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+    let black_to_move = crate::fen::from_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+    assert!(evaluate(&black_to_move) > 0);
+    assert!(evaluate_stm(&black_to_move) < 0);
+    assert_eq!(evaluate_stm(&black_to_move), -evaluate(&black_to_move));
+    assert_eq!(evaluate_stm(&white_to_move), evaluate(&white_to_move));
+}
+
+#[test]
+fn evaluate_start_position_is_balanced() {
+    assert_eq!(evaluate(&GameData::default()), 0);
+}
+
+#[test]
+fn best_move_captures_hanging_queen() {
+    let game_data = crate::fen::from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+    let mv = best_move(&game_data, 2, &[]).unwrap();
+    assert_eq!(mv.to, Position { x: 3, y: 4 });
+}
+
+#[test]
+fn analyze_ranks_the_winning_capture_first() {
+    // the queen on d5 is loose, nothing else is going on
+    let game_data =
+        crate::fen::from_fen("r1b1kbnr/pppp1ppp/2n5/3q4/8/2N5/PPPP1PPP/R1BQKBNR w KQkq - 0 1")
+            .unwrap();
+    let scored = analyze(&game_data, 2);
+    assert_eq!(scored.len(), generate_moves_detailed(&game_data).len());
+    assert_eq!(crate::chess::move_to_uci(&scored[0].0), "c3d5");
+    assert!(scored[0].1 > scored[1].1 + 500);
+    assert!(scored.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    assert!(analyze(
+        &crate::fen::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap(),
+        2
+    )
+    .is_empty());
+}
+
+#[test]
+fn best_move_finds_mate_in_one() {
+    let game_data = crate::fen::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    let mv = best_move(&game_data, 2, &[]).unwrap();
+    assert_eq!(mv.to, Position { x: 0, y: 7 });
+}
+
+#[test]
+fn see_queen_takes_defended_pawn() {
+    let game_data = crate::fen::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+    assert_eq!(
+        see(&game_data.board, Position { x: 3, y: 4 }, PieceColor::White),
+        -800
+    );
+}
+
+#[test]
+fn see_undefended_piece_and_xray() {
+    let game_data = crate::fen::from_fen("4k3/8/8/3n4/8/8/8/3RK3 w - - 0 1").unwrap();
+    assert_eq!(
+        see(&game_data.board, Position { x: 3, y: 4 }, PieceColor::White),
+        320
+    );
+
+    // the second rook behind the first settles the exchange on a7
+    let game_data = crate::fen::from_fen("r3k3/p7/8/8/8/8/R7/R3K3 w - - 0 1").unwrap();
+    assert_eq!(
+        see(&game_data.board, Position { x: 0, y: 6 }, PieceColor::White),
+        100
+    );
+    let game_data = crate::fen::from_fen("r3k3/p7/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+    assert_eq!(
+        see(&game_data.board, Position { x: 0, y: 6 }, PieceColor::White),
+        -400
+    );
+}
+
+#[test]
+fn hanging_pieces_by_exchange() {
+    let hanging = |fen: &str| hanging_pieces(&crate::fen::from_fen(fen).unwrap());
+    let d4 = Position { x: 3, y: 3 };
+    // attacked by a pawn and not defended at all
+    assert_eq!(hanging("4k3/8/8/4p3/3N4/8/8/4K3 w - - 0 1"), [d4]);
+    // two knights against one defender
+    assert_eq!(hanging("4k3/8/2n1n3/8/3N4/1N6/8/4K3 w - - 0 1"), [d4]);
+    // a pawn evens it up
+    assert!(hanging("4k3/8/2n1n3/8/3N4/1NP5/8/4K3 w - - 0 1").is_empty());
+    // only the side to move's pieces count
+    assert!(hanging("4k3/8/8/4p3/3N4/8/8/4K3 b - - 0 1").is_empty());
+}
+
+#[test]
+fn order_moves_puts_captures_first() {
+    // the pawn on e4 can take the queen, the rook can take the queen or a pawn
+    let game_data = crate::fen::from_fen("4k3/p7/8/3q4/4P3/8/8/R2RK3 w - - 0 1").unwrap();
+    let mut moves = legal_moves_flat(&game_data);
+    order_moves(&game_data, &mut moves);
+    let captures = moves
+        .iter()
+        .take_while(|mv| captured_piece(&game_data, mv).is_some())
+        .count();
+    assert_eq!(captures, 3);
+    assert!(moves[captures..]
+        .iter()
+        .all(|mv| captured_piece(&game_data, mv).is_none()));
+    assert_eq!(crate::chess::move_to_uci(&moves[0]), "e4d5");
+    assert_eq!(crate::chess::move_to_uci(&moves[1]), "d1d5");
+    assert_eq!(crate::chess::move_to_uci(&moves[2]), "a1a7");
+}
+
+#[test]
+fn killer_moves_come_before_other_quiets() {
+    let game_data = crate::fen::from_fen("4k3/p7/8/3q4/4P3/8/8/R2RK3 w - - 0 1").unwrap();
+    let killer = crate::chess::parse_uci_move("a1a6").unwrap();
+    let mut search = Search::new(None);
+    let mut moves = legal_moves_flat(&game_data);
+    let quiet_from = moves
+        .iter()
+        .filter(|mv| !is_quiet_move(&game_data, mv))
+        .count();
+
+    search.record_cutoff(killer, 3, 2);
+    search.order_moves_at(&game_data, &mut moves, 3);
+    // the captures still go first, the killer leads the quiet moves
+    assert_eq!(quiet_from, 3);
+    assert_eq!(moves[quiet_from], killer);
+    // killers belong to their ply, the history score is shared
+    search.order_moves_at(&game_data, &mut moves, 4);
+    assert_eq!(moves[quiet_from], killer);
+    search.record_cutoff(crate::chess::parse_uci_move("e1f2").unwrap(), 4, 3);
+    search.order_moves_at(&game_data, &mut moves, 4);
+    assert_eq!(crate::chess::move_to_uci(&moves[quiet_from]), "e1f2");
+    assert_eq!(moves[quiet_from + 1], killer);
+}
+
+#[test]
+fn quiet_move_heuristics_cut_the_tree() {
+    // a handful of middlegame positions with tactics in them
+    let suite = [
+        "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+        "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 8",
+        "2rq1rk1/pb1nbppp/1p2pn2/3p4/2PP4/1PN1PN2/PB2BPPP/2RQ1RK1 w - - 0 12",
+    ];
+    let nodes = |heuristics: bool| -> u64 {
+        suite
+            .iter()
+            .map(|fen| {
+                let game_data = crate::fen::from_fen(fen).unwrap();
+                let mut search = Search::new(None);
+                search.heuristics = heuristics;
+                for depth in 1..=3 {
+                    search.search_root(&game_data, depth);
+                }
+                search.nodes
+            })
+            .sum()
+    };
+    let (plain, ordered) = (nodes(false), nodes(true));
+    assert!(
+        ordered < plain,
+        "{ordered} nodes with the heuristics, {plain} without"
+    );
+}
+
+#[test]
+fn null_move_changes_the_nodes_not_the_move() {
+    // the queen on d5 is loose, nothing else is going on
+    let game_data =
+        crate::fen::from_fen("r1b1kbnr/pppp1ppp/2n5/3q4/8/2N5/PPPP1PPP/R1BQKBNR w KQkq - 0 1")
+            .unwrap();
+    let search = |null_move: bool| {
+        let mut search = Search::new(None);
+        search.null_move = null_move;
+        let (mv, _) = search.search_root(&game_data, 4).unwrap();
+        (mv, search.nodes)
+    };
+    let (without, without_nodes) = search(false);
+    let (with, with_nodes) = search(true);
+    assert_eq!(crate::chess::move_to_uci(&without), "c3d5");
+    assert_eq!(with, without);
+    assert_ne!(with_nodes, without_nodes);
+}
+
+#[test]
+fn null_move_is_skipped_without_pieces() {
+    let pawns = crate::fen::from_fen("4k3/4p3/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+    assert!(!has_piece_besides_pawns(&pawns.board, PieceColor::White));
+    let mut search = Search::new(None);
+    assert!(!search.null_move_fails_high(&pawns, 6, 1, 0));
+    assert_eq!(search.nodes, 0);
+
+    let passed = pass_turn(&crate::fen::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap());
+    assert_eq!(passed.to_move, PieceColor::Black);
+    assert_eq!(passed.moved_2_squares, None);
+    assert_eq!(passed.hash, crate::zobrist::hash(&passed));
+}
+
+#[test]
+fn quiescence_avoids_poisoned_pawn() {
+    let game_data = crate::fen::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+    let poisoned = crate::chess::parse_uci_move("d1d5").unwrap();
+
+    let mut search = Search::new(None);
+    search.quiescence = false;
+    let (mv, _) = search.search_root(&game_data, 1).unwrap();
+    assert_eq!(mv, poisoned);
+
+    let (mv, score) = Search::new(None).search_root(&game_data, 1).unwrap();
+    assert_ne!(mv, poisoned);
+    assert!(score >= 0);
+}
+
+#[test]
+fn game_phase_from_material() {
+    let board = GameData::default().board;
+    assert_eq!(phase_value(&board), 256);
+    assert_eq!(game_phase(&board), GamePhase::Opening);
+
+    let board = crate::fen::from_fen("r3k3/pp6/8/8/8/8/PP6/R3K3 w - - 0 1")
+        .unwrap()
+        .board;
+    assert_eq!(game_phase(&board), GamePhase::Endgame);
+
+    let board = crate::fen::from_fen("4k3/4p3/8/8/8/8/4P3/4K3 w - - 0 1")
+        .unwrap()
+        .board;
+    assert_eq!(phase_value(&board), 0);
+    assert_eq!(game_phase(&board), GamePhase::Endgame);
+
+    let board = crate::fen::from_fen("r1bqk3/pppppppp/8/8/8/8/PPPPPPPP/R1BQK3 w - - 0 1")
+        .unwrap()
+        .board;
+    assert_eq!(game_phase(&board), GamePhase::Middlegame);
+}
+
+#[test]
+fn king_tables_taper_with_phase() {
+    let king = PieceType::King(PieceColor::White);
+    let (g1_mg, g1_eg) = piece_square_bonus(king, Position { x: 6, y: 0 });
+    let (e4_mg, e4_eg) = piece_square_bonus(king, Position { x: 4, y: 3 });
+    assert!(g1_mg > e4_mg);
+    assert!(e4_eg > g1_eg);
+
+    let central = crate::fen::from_fen("4k3/8/8/8/4K3/8/8/8 w - - 0 1").unwrap();
+    let back_rank = crate::fen::from_fen("4k3/8/8/8/8/8/8/6K1 w - - 0 1").unwrap();
+    assert!(evaluate(&central) > evaluate(&back_rank));
+}
+
+#[test]
+fn search_avoids_repetition_when_ahead() {
+    let game_data = crate::fen::from_fen("6k1/8/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+    let (mv, _) = Search::new(None).search_root(&game_data, 2).unwrap();
+    let repeated = apply_move(&game_data, &mv);
+
+    let history = [
+        repeated.clone(),
+        game_data.clone(),
+        repeated,
+        game_data.clone(),
+    ];
+    let (alternative, score) = Search::new(None)
+        .with_history(&history)
+        .search_root(&game_data, 2)
+        .unwrap();
+    assert_ne!(alternative, mv);
+    assert!(score > 0);
+}
+
+#[test]
+fn search_claims_repetition_when_behind() {
+    let game_data = crate::fen::from_fen("q5k1/8/8/8/8/8/8/6K1 w - - 0 1").unwrap();
+    let shuffle = crate::chess::parse_uci_move("g1f1").unwrap();
+    let repeated = apply_move(&game_data, &shuffle);
+    let history = [
+        repeated.clone(),
+        game_data.clone(),
+        repeated,
+        game_data.clone(),
+    ];
+    let (mv, score) = Search::new(None)
+        .with_history(&history)
+        .search_root(&game_data, 2)
+        .unwrap();
+    assert_eq!(mv, shuffle);
+    assert_eq!(score, 0);
+}
+
+#[test]
+fn contempt_plays_on_instead_of_repeating() {
+    // a pawn down, White can repeat with Kf1
+    let game_data = crate::fen::from_fen("6k1/5ppp/8/8/8/8/5PP1/6K1 w - - 0 1").unwrap();
+    let shuffle = crate::chess::parse_uci_move("g1f1").unwrap();
+    let repeated = apply_move(&game_data, &shuffle);
+    let history = [
+        repeated.clone(),
+        game_data.clone(),
+        repeated,
+        game_data.clone(),
+    ];
+    let (mv, score) = Search::new(None)
+        .with_history(&history)
+        .search_root(&game_data, 2)
+        .unwrap();
+    assert_eq!((mv, score), (shuffle, 0));
+
+    let (mv, score) = Search::new(None)
+        .with_history(&history)
+        .with_contempt(300)
+        .search_root(&game_data, 2)
+        .unwrap();
+    assert_ne!(mv, shuffle);
+    assert!(-300 < score && score < 0, "{score}");
+}
+
+#[test]
+fn aspiration_windows_keep_the_result() {
+    // each with a single best move: a mate in one, a loose queen, a fork
+    let positions = [
+        "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+        "r1b1kbnr/pppp1ppp/2n5/3q4/8/2N5/PPPP1PPP/R1BQKBNR w KQkq - 0 1",
+        "r3k3/8/8/1N6/8/8/8/4K3 w - - 0 1",
+    ];
+    let mut nodes_differ = false;
+    for fen in positions {
+        let game_data = crate::fen::from_fen(fen).unwrap();
+        let deepen = |aspiration: bool| {
+            let mut search = Search::new(None);
+            // null-move pruning depends on the window, the plain search doesn't
+            search.null_move = false;
+            search.aspiration = aspiration;
+            let result = search.deepen(&game_data, 4).unwrap();
+            (result, search.nodes)
+        };
+        let (full, full_nodes) = deepen(false);
+        let (aspirated, aspirated_nodes) = deepen(true);
+        assert_eq!(aspirated, full, "{fen}");
+        nodes_differ |= aspirated_nodes != full_nodes;
+    }
+    assert!(nodes_differ);
+}
+
+#[test]
+fn material_and_captures() {
+    let start = GameData::default().board;
+    assert_eq!(material_balance(&start), 0);
+    assert_eq!(captured_pieces(&start, &start), (vec![], vec![]));
+
+    // the queens are off and Black is a pawn down
+    let traded = crate::fen::from_fen("rnb1kbnr/pppp1ppp/8/8/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 0 1")
+        .unwrap()
+        .board;
+    assert_eq!(material_balance(&traded), 100);
+    assert_eq!(
+        captured_pieces(&start, &traded),
+        (
+            vec![PieceType::Queen(PieceColor::White)],
+            vec![
+                PieceType::Queen(PieceColor::Black),
+                PieceType::Pawn(PieceColor::Black)
+            ]
+        )
+    );
+
+    // a7a8=Q isn't a lost pawn, but losing the h-pawn as well is
+    let start = crate::fen::from_fen("7k/P7/8/8/8/8/7P/4K3 w - - 0 1")
+        .unwrap()
+        .board;
+    let promoted = crate::fen::from_fen("Q6k/8/8/8/8/8/7P/4K3 b - - 0 1")
+        .unwrap()
+        .board;
+    assert_eq!(captured_pieces(&start, &promoted), (vec![], vec![]));
+    let traded = crate::fen::from_fen("Q6k/8/8/8/8/8/8/4K3 b - - 0 1")
+        .unwrap()
+        .board;
+    assert_eq!(
+        captured_pieces(&start, &traded),
+        (vec![PieceType::Pawn(PieceColor::White)], vec![])
+    );
+}
+
+#[test]
+fn evaluation_breakdown_sums_to_evaluate() {
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+    ] {
+        let game_data = crate::fen::from_fen(fen).unwrap();
+        let breakdown = evaluate_explain(&game_data);
+        assert_eq!(breakdown.total(), evaluate(&game_data), "{fen}");
+        assert_eq!(breakdown.material, material_balance(&game_data.board));
+    }
+
+    // a castled king behind its pawns against a bare one
+    let sheltered = crate::fen::from_fen("r3k2r/8/8/8/8/8/5PPP/R3QRK1 w - - 0 1").unwrap();
+    assert!(evaluate_explain(&sheltered).king_safety > 0);
+}
+
+#[test]
+fn back_rank_weakness_needs_a_walled_in_king() {
+    let board = |fen: &str| crate::fen::from_fen(fen).unwrap().board;
+    // Black's king behind f7, g7 and h7 with nothing on the eighth rank
+    let weak = board("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1");
+    assert!(back_rank_weakness(&weak, PieceColor::Black));
+    // White's rook guards the first rank
+    assert!(!back_rank_weakness(&weak, PieceColor::White));
+    // ...h6 made luft
+    assert!(!back_rank_weakness(
+        &board("6k1/5pp1/7p/8/8/8/5PPP/R5K1 w - - 0 1"),
+        PieceColor::Black
+    ));
+    // a king in the corner only needs two pawns to be shut in
+    assert!(back_rank_weakness(
+        &board("7k/6pp/8/8/8/8/8/R5K1 w - - 0 1"),
+        PieceColor::Black
+    ));
+    // a queen on the back rank can interpose
+    assert!(!back_rank_weakness(
+        &board("3q2k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"),
+        PieceColor::Black
+    ));
+    // the king has stepped up
+    assert!(!back_rank_weakness(
+        &board("8/5pkp/6p1/8/8/8/8/R5K1 w - - 0 1"),
+        PieceColor::Black
+    ));
+
+    // the evaluation holds it against Black only while White has a rook
+    let without_rook = board("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+    assert_eq!(king_safety(&without_rook, PHASE_MAX), 0);
+    let with_rook = board("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1");
+    assert_eq!(king_safety(&with_rook, PHASE_MAX), -BACK_RANK_WEAKNESS);
+}
+
+#[test]
+fn mobility_favours_the_freer_side() {
+    let start = GameData::default();
+    assert_eq!(evaluate_explain(&start).mobility, 0);
+
+    // White's centre pawns have opened lines for the bishops and queen
+    let open =
+        crate::fen::from_fen("rnbqkbnr/pppppppp/8/8/3PP3/8/PPP2PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+    assert!(evaluate_explain(&open).mobility > 0);
+
+    // the same counts for Black, whoever is to move
+    let cramped =
+        crate::fen::from_fen("rnbqkbnr/ppp2ppp/8/3pp3/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1").unwrap();
+    assert_eq!(
+        evaluate_explain(&cramped).mobility,
+        -evaluate_explain(&open).mobility
+    );
+}
+
+#[test]
+fn pawn_structure_features() {
+    let score = |fen: &str| pawn_structure_score(&crate::fen::from_fen(fen).unwrap().board);
+    assert_eq!(
+        score("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+        0
+    );
+
+    // c2/c3 doubled against b7/c7/d7: one doubled pawn, nothing isolated or passed
+    assert_eq!(
+        score("4k3/1ppp4/8/8/8/2P5/1PP5/4K3 w - - 0 1"),
+        DOUBLED_PAWN
+    );
+    // Black's lone a-pawn is isolated, and every pawn here is passed
+    assert_eq!(
+        score("4k3/p7/8/8/8/8/6PP/4K3 w - - 0 1"),
+        2 * PASSED_PAWN[1] - (ISOLATED_PAWN + PASSED_PAWN[1])
+    );
+    // a passed pawn is worth more the further it has gone
+    assert!(score("4k3/8/1P6/8/8/8/8/4K3 w - - 0 1") > score("4k3/8/8/8/8/1P6/8/4K3 w - - 0 1"));
+    // b2 and c7 are both isolated, and each keeps the other from being passed
+    assert_eq!(score("4k3/2p5/8/8/8/8/1P6/4K3 w - - 0 1"), 0);
+}
+
+#[test]
+fn find_mate_in_two_with_the_rooks() {
+    // a rook on the seventh shuts the king on the back rank, then the other
+    // one mates along it wherever the king goes
+    let game_data = crate::fen::from_fen("6k1/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+    assert_eq!(find_mate(&game_data, 1), None);
+    let line = find_mate(&game_data, 2).unwrap();
+    assert_eq!(line.len(), 3);
+    assert_eq!(line[0].to.y, 6);
+    assert_eq!(line[2].to.y, 7);
+
+    let after = apply_move(&game_data, &line[0]);
+    for reply in generate_moves_detailed(&after) {
+        assert!(find_mate(&apply_move(&after, &reply), 1).is_some());
+    }
+    let end = line.iter().fold(game_data, |gd, mv| apply_move(&gd, mv));
+    assert_eq!(
+        crate::chess::game_status(&end),
+        crate::chess::GameStatus::Checkmate
+    );
+}
+
+#[test]
+fn principal_variation_follows_the_mate() {
+    let game_data = crate::fen::from_fen("6k1/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+    let mut search = Search::new(None);
+    let (mv, score) = search.deepen(&game_data, 3).unwrap();
+    assert_eq!(score, MATE_SCORE - 3);
+
+    let pv = extract_pv(&game_data, &search.tt, 10);
+    assert_eq!(pv.len(), 3);
+    assert_eq!(pv[0], mv);
+    let end = pv
+        .iter()
+        .fold(game_data.clone(), |gd, mv| apply_move(&gd, mv));
+    assert_eq!(
+        crate::chess::game_status(&end),
+        crate::chess::GameStatus::Checkmate
+    );
+    assert_eq!(extract_pv(&game_data, &search.tt, 2), pv[..2]);
+    assert!(extract_pv(&game_data, &TranspositionTable::new(), 10).is_empty());
+}
+
+#[test]
+fn transposition_table_keeps_the_deeper_move() {
+    let [shallow, deep] = ["e2e4", "d2d4"].map(|mv| crate::chess::parse_uci_move(mv).unwrap());
+    let mut tt = TranspositionTable::new();
+    tt.store(1, deep, 4);
+    tt.store(1, shallow, 2);
+    assert_eq!(tt.best_move(1), Some(deep));
+    tt.store(1, shallow, 4);
+    assert_eq!(
+        tt.get(1),
+        Some(TtEntry {
+            best_move: shallow,
+            depth: 4
+        })
+    );
+    assert_eq!(tt.best_move(2), None);
+    assert_eq!(tt.len(), 1);
+}
+
+#[test]
+fn evaluation_is_color_symmetric() {
+    use crate::chess::mirror_position;
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w Kq - 4 4",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ] {
+        let game_data = crate::fen::from_fen(fen).unwrap();
+        let mirrored = mirror_position(&game_data);
+        assert_eq!(evaluate(&mirrored), -evaluate(&game_data), "{fen}");
+        assert_eq!(
+            generate_moves_detailed(&mirrored).len(),
+            generate_moves_detailed(&game_data).len(),
+            "{fen}"
+        );
+        assert_eq!(
+            crate::chess::perft(&mirrored, 2),
+            crate::chess::perft(&game_data, 2)
+        );
+        // mirroring twice gives the position back
+        let back = mirror_position(&mirrored);
+        assert_eq!(crate::fen::to_fen(&back), crate::fen::to_fen(&game_data));
+        assert_eq!(back.hash, game_data.hash);
+    }
+}
+
+#[test]
+fn best_move_within_stops_at_the_depth() {
+    let game_data = crate::fen::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    let (mv, score) = best_move_within(&game_data, 2, Duration::from_secs(60), &[]).unwrap();
+    assert_eq!(mv.to, Position { x: 0, y: 7 });
+    assert!(score > MATE_SCORE - 10);
+    // no time at all still answers with a legal move
+    let (mv, _) = best_move_within(&game_data, 6, Duration::ZERO, &[]).unwrap();
+    assert!(legal_moves_flat(&game_data).contains(&mv));
+}
+
+#[test]
+fn random_games_repeat_for_a_seed() {
+    use crate::chess::{game_status, GameStatus};
+    use crate::random::SplitMix64;
+    let play = |seed| {
+        let mut rng = SplitMix64::new(seed);
+        let mut game_data = GameData::default();
+        let mut moves = Vec::new();
+        while moves.len() < 200 {
+            let Some(mv) = random_move(&game_data, &mut rng) else {
+                assert_ne!(game_status(&game_data), GameStatus::Ongoing);
+                break;
+            };
+            assert!(generate_moves_detailed(&game_data).contains(&mv));
+            game_data = apply_move(&game_data, &mv);
+            moves.push(mv);
+        }
+        moves
+    };
+    let game = play(42);
+    assert!(!game.is_empty());
+    assert_eq!(play(42), game);
+    assert_ne!(play(43), game);
+}
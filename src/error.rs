@@ -0,0 +1,122 @@
+//! One error type for everything that can go wrong turning outside input
+//! into positions and moves, for callers that don't care which step failed.
+//! Each step still has its own error, wrapped here and kept as the `source`.
+use crate::chess::{MoveError, PositionError};
+use crate::fen::FenError;
+use crate::pgn::PgnError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Fen(FenError),
+    Pgn(PgnError),
+    Move(MoveError),
+    /// Every rule the position breaks, see `validate_position`.
+    InvalidPosition(Vec<PositionError>),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Fen(e) => write!(f, "invalid FEN: {e}"),
+            Error::Pgn(e) => write!(f, "invalid PGN: {e}"),
+            Error::Move(e) => write!(f, "invalid move: {e}"),
+            Error::InvalidPosition(errors) => {
+                write!(f, "invalid position: ")?;
+                write_position_errors(f, errors)
+            }
+        }
+    }
+}
+
+/// Every rule a position breaks, separated by `; `.
+pub(crate) fn write_position_errors(
+    f: &mut std::fmt::Formatter<'_>,
+    errors: &[PositionError],
+) -> std::fmt::Result {
+    for (i, e) in errors.iter().enumerate() {
+        if i > 0 {
+            write!(f, "; ")?;
+        }
+        write!(f, "{e}")?;
+    }
+    Ok(())
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Fen(e) => Some(e),
+            Error::Pgn(e) => Some(e),
+            Error::Move(e) => Some(e),
+            Error::InvalidPosition(errors) => errors
+                .first()
+                .map(|e| e as &(dyn std::error::Error + 'static)),
+        }
+    }
+}
+
+impl From<FenError> for Error {
+    fn from(e: FenError) -> Self {
+        Error::Fen(e)
+    }
+}
+
+impl From<PgnError> for Error {
+    fn from(e: PgnError) -> Self {
+        Error::Pgn(e)
+    }
+}
+
+impl From<MoveError> for Error {
+    fn from(e: MoveError) -> Self {
+        Error::Move(e)
+    }
+}
+
+impl From<PositionError> for Error {
+    fn from(e: PositionError) -> Self {
+        Error::InvalidPosition(vec![e])
+    }
+}
+
+impl From<Vec<PositionError>> for Error {
+    fn from(errors: Vec<PositionError>) -> Self {
+        Error::InvalidPosition(errors)
+    }
+}
+
+#[test]
+fn malformed_input_is_an_error_not_a_panic() {
+    use crate::chess::{
+        apply_move_checked, generate_moves_checked, is_in_check, parse_uci_move, PieceColor,
+        Position,
+    };
+    use crate::fen::{from_fen, from_fen_checked};
+    use crate::pgn::import_pgn;
+
+    assert!(matches!(from_fen_checked("not a fen"), Err(Error::Fen(_))));
+    assert_eq!(
+        from_fen_checked("8/8/8/8/8/8/8/4K3 w - - 0 1").unwrap_err(),
+        Error::InvalidPosition(vec![PositionError::KingCount(PieceColor::Black, 0)])
+    );
+
+    // the parser takes a position without kings, the move generator doesn't
+    let kingless = from_fen("8/8/8/8/8/8/4P3/8 w - - 0 1").unwrap();
+    assert_eq!(
+        generate_moves_checked(&kingless).unwrap_err(),
+        PositionError::KingCount(PieceColor::White, 0)
+    );
+    assert!(!is_in_check(&kingless));
+
+    let start = from_fen_checked("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    let mv = parse_uci_move("e4e5").unwrap();
+    assert_eq!(
+        apply_move_checked(&start.unwrap(), &mv).unwrap_err(),
+        MoveError::NoPieceToMove(Position { x: 4, y: 3 })
+    );
+
+    let load = |pgn: &str| -> Result<usize, Error> { Ok(import_pgn(pgn)?.ply_count()) };
+    assert_eq!(load("1. e4 e5"), Ok(2));
+    assert!(matches!(load("1. e5"), Err(Error::Pgn(_))));
+    assert!(std::error::Error::source(&load("1. e5").unwrap_err()).is_some());
+}
@@ -0,0 +1,443 @@
+use crate::chess::{
+    legal_moves_flat, validate_position, Castling, GameData, PieceColor, PieceType, Position,
+};
+use crate::error::Error;
+use crate::zobrist;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    MissingField(&'static str),
+    RankCount(usize),
+    InvalidPiece(char),
+    InvalidRank(usize),
+    InvalidSideToMove(String),
+    InvalidCastling(char),
+    InvalidEnPassant(String),
+    InvalidClock(String),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::MissingField(field) => write!(f, "missing {field} field"),
+            FenError::RankCount(count) => write!(f, "expected 8 ranks, found {count}"),
+            FenError::InvalidPiece(c) => write!(f, "invalid piece '{c}'"),
+            FenError::InvalidRank(rank) => write!(f, "rank {rank} does not describe 8 squares"),
+            FenError::InvalidSideToMove(side) => write!(f, "invalid side to move '{side}'"),
+            FenError::InvalidCastling(c) => write!(f, "invalid castling flag '{c}'"),
+            FenError::InvalidEnPassant(square) => write!(f, "invalid en passant square '{square}'"),
+            FenError::InvalidClock(clock) => write!(f, "invalid move clock '{clock}'"),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+fn parse_placement(placement: &str, game_data: &mut GameData) -> Result<(), FenError> {
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::RankCount(ranks.len()));
+    }
+    for (i, rank) in ranks.iter().enumerate() {
+        let y = 7 - i as i8;
+        let mut x = 0i8;
+        for c in rank.chars() {
+            if let Some(empty) = c.to_digit(10) {
+                x += empty as i8;
+                if x > 8 {
+                    return Err(FenError::InvalidRank(y as usize + 1));
+                }
+                continue;
+            }
+            if x >= 8 {
+                return Err(FenError::InvalidRank(y as usize + 1));
+            }
+            let piece = PieceType::from_letter(c).ok_or(FenError::InvalidPiece(c))?;
+            let position = Position { x, y };
+            game_data.board.insert(position, piece);
+            if piece == PieceType::Pawn(PieceColor::White) && y == 1
+                || piece == PieceType::Pawn(PieceColor::Black) && y == 6
+            {
+                game_data.can_move_2_squares.insert(position);
+            }
+            x += 1;
+        }
+        if x != 8 {
+            return Err(FenError::InvalidRank(y as usize + 1));
+        }
+    }
+    Ok(())
+}
+
+fn parse_castling(castling: &str, game_data: &mut GameData) -> Result<(), FenError> {
+    if castling == "-" {
+        return Ok(());
+    }
+    for c in castling.chars() {
+        let color = if c.is_ascii_uppercase() {
+            PieceColor::White
+        } else {
+            PieceColor::Black
+        };
+        let rights = game_data.castling.entry(color).or_insert(Castling {
+            king_side: false,
+            queen_side: false,
+        });
+        match c.to_ascii_lowercase() {
+            'k' => rights.king_side = true,
+            'q' => rights.queen_side = true,
+            _ => return Err(FenError::InvalidCastling(c)),
+        }
+    }
+    Ok(())
+}
+
+/// Builds a position from Forsyth–Edwards Notation. The halfmove clock and
+/// fullmove number are optional and default to `0 1`.
+pub fn from_fen(fen: &str) -> Result<GameData, FenError> {
+    let mut fields = fen.split_whitespace();
+    let mut game_data = GameData::new();
+
+    let placement = fields.next().ok_or(FenError::MissingField("placement"))?;
+    parse_placement(placement, &mut game_data)?;
+
+    let side = fields
+        .next()
+        .ok_or(FenError::MissingField("side to move"))?;
+    game_data.to_move = side
+        .parse()
+        .map_err(|_| FenError::InvalidSideToMove(side.to_string()))?;
+
+    let castling = fields.next().ok_or(FenError::MissingField("castling"))?;
+    parse_castling(castling, &mut game_data)?;
+
+    let en_passant = fields.next().ok_or(FenError::MissingField("en passant"))?;
+    if en_passant != "-" {
+        let target = Position::from_algebraic(en_passant)
+            .ok_or_else(|| FenError::InvalidEnPassant(en_passant.to_string()))?;
+        // the pawn that skipped the target belongs to the side not to move
+        match (game_data.to_move, target.y) {
+            (PieceColor::White, 5) | (PieceColor::Black, 2) => {}
+            _ => return Err(FenError::InvalidEnPassant(en_passant.to_string())),
+        }
+        game_data.set_en_passant_target(Some(target));
+    }
+
+    // the fullmove number starts at 1
+    let mut clock = |default, min| match fields.next() {
+        None => Ok(default),
+        Some(clock) => clock
+            .parse()
+            .ok()
+            .filter(|&value| value >= min)
+            .ok_or_else(|| FenError::InvalidClock(clock.to_string())),
+    };
+    game_data.halfmove_clock = clock(0, 0)?;
+    game_data.fullmove_number = clock(1, 1)?;
+    game_data.hash = zobrist::hash(&game_data);
+    Ok(game_data)
+}
+
+/// `from_fen` for positions that are going to be played from: the position
+/// must also pass `validate_position`, so the move generators can rely on it.
+pub fn from_fen_checked(fen: &str) -> Result<GameData, Error> {
+    let game_data = from_fen(fen)?;
+    validate_position(&game_data)?;
+    Ok(game_data)
+}
+
+/// Writes the position as FEN, move clocks included.
+pub fn to_fen(game_data: &GameData) -> String {
+    let mut ranks = Vec::new();
+    for y in (0..8).rev() {
+        let mut rank = String::new();
+        let mut empty = 0;
+        for x in 0..8 {
+            match game_data.board.get(&Position { x, y }) {
+                Some(&piece) => {
+                    if empty > 0 {
+                        rank.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    rank.push(piece.fen_char());
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            rank.push_str(&empty.to_string());
+        }
+        ranks.push(rank);
+    }
+    let side = match game_data.to_move {
+        PieceColor::White => "w",
+        PieceColor::Black => "b",
+    };
+    let mut castling = String::new();
+    for (color, king_side, queen_side) in
+        [(PieceColor::White, 'K', 'Q'), (PieceColor::Black, 'k', 'q')]
+    {
+        if let Some(rights) = game_data.castling.get(&color) {
+            if rights.king_side {
+                castling.push(king_side);
+            }
+            if rights.queen_side {
+                castling.push(queen_side);
+            }
+        }
+    }
+    if castling.is_empty() {
+        castling.push('-');
+    }
+    let en_passant = game_data
+        .en_passant_target()
+        .map_or("-".to_string(), Position::to_algebraic);
+    format!(
+        "{} {side} {castling} {en_passant} {} {}",
+        ranks.join("/"),
+        game_data.halfmove_clock,
+        game_data.fullmove_number
+    )
+}
+
+/// Whether the side to move has an en passant capture. Positions that can't
+/// be played from, say for want of a king, only ask for a pawn next to the
+/// one that skipped.
+fn en_passant_usable(game_data: &GameData) -> bool {
+    let Some(skipped) = game_data.moved_2_squares else {
+        return false;
+    };
+    let board = &game_data.board;
+    if validate_position(game_data).is_err() {
+        let pawn = PieceType::Pawn(game_data.to_move);
+        return [-1, 1].into_iter().any(|dx| {
+            board.get(&Position {
+                x: skipped.x + dx,
+                y: skipped.y,
+            }) == Some(&pawn)
+        });
+    }
+    legal_moves_flat(game_data).iter().any(|mv| {
+        matches!(board.get(&mv.from), Some(PieceType::Pawn(_)))
+            && mv.from.x != mv.to.x
+            && !board.contains_key(&mv.to)
+    })
+}
+
+/// The first four FEN fields, which identify a position for repetition and
+/// book lookups regardless of the move clocks. An en passant square no pawn
+/// can use is dropped, so it doesn't tell otherwise equal positions apart.
+pub fn position_key(game_data: &GameData) -> String {
+    let mut game_data = game_data.clone();
+    if !en_passant_usable(&game_data) {
+        game_data.set_en_passant_target(None);
+    }
+    to_fen(&game_data)
+        .split_whitespace()
+        .take(4)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Respells `fen` canonically so one position has one FEN: castling rights
+/// in `KQkq` order, `-` for no rights and for an en passant square no pawn
+/// can use, and single spaces. The move clocks are kept, `0 1` if missing.
+pub fn normalize_fen(fen: &str) -> Result<String, FenError> {
+    Ok(to_fen(&normalized(fen)?))
+}
+
+/// `normalize_fen` with the move clocks reset to `0 1`, for telling apart
+/// positions rather than moments in a game.
+pub fn normalize_fen_zeroed(fen: &str) -> Result<String, FenError> {
+    let mut game_data = normalized(fen)?;
+    game_data.halfmove_clock = 0;
+    game_data.fullmove_number = 1;
+    Ok(to_fen(&game_data))
+}
+
+fn normalized(fen: &str) -> Result<GameData, FenError> {
+    let mut game_data = from_fen(fen)?;
+    if !en_passant_usable(&game_data) {
+        game_data.set_en_passant_target(None);
+    }
+    Ok(game_data)
+}
+
+#[test]
+fn to_fen_round_trip() {
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 1",
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Kq - 0 1",
+        "8/8/8/8/8/8/8/4K2k b - - 0 1",
+    ] {
+        assert_eq!(to_fen(&from_fen(fen).unwrap()), fen);
+    }
+    assert_eq!(
+        to_fen(&GameData::default()),
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+    );
+}
+
+#[test]
+fn move_clocks_survive_the_round_trip() {
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 5 17";
+    let game_data = from_fen(fen).unwrap();
+    assert_eq!(
+        (game_data.halfmove_clock, game_data.fullmove_number),
+        (5, 17)
+    );
+    assert_eq!(to_fen(&game_data), fen);
+
+    // a quiet White move only ticks the halfmove clock, Black's reply
+    // completes the move, and a pawn move resets the clock
+    use crate::chess::{apply_move, parse_uci_move};
+    let mut clocks = Vec::new();
+    let mut game_data = game_data;
+    for mv in ["f1c4", "g8f6", "d2d3", "f8c5", "c4f7"] {
+        game_data = apply_move(&game_data, &parse_uci_move(mv).unwrap());
+        clocks.push((game_data.halfmove_clock, game_data.fullmove_number));
+    }
+    assert_eq!(clocks, [(6, 17), (7, 18), (0, 18), (1, 19), (0, 19)]);
+    assert_eq!(
+        from_fen("8/8/8/8/8/8/8/4K2k b - -").map(|g| to_fen(&g)),
+        Ok("8/8/8/8/8/8/8/4K2k b - - 0 1".to_string())
+    );
+    assert_eq!(
+        from_fen("8/8/8/8/8/8/8/4K2k b - - 3 -1").unwrap_err(),
+        FenError::InvalidClock("-1".to_string())
+    );
+    assert_eq!(
+        from_fen("8/8/8/8/8/8/8/4K2k b - - 3 0").unwrap_err(),
+        FenError::InvalidClock("0".to_string())
+    );
+    assert_eq!(
+        from_fen("8/8/8/8/8/8/8/4K2k b - - 4294967296 1").unwrap_err(),
+        FenError::InvalidClock("4294967296".to_string())
+    );
+    // the clocks stop at their largest value rather than overflow
+    let game_data = from_fen("4k3/8/8/8/8/8/8/4K2R b K - 4294967295 4294967295").unwrap();
+    let game_data = apply_move(&game_data, &parse_uci_move("e8e7").unwrap());
+    assert_eq!(
+        (game_data.halfmove_clock, game_data.fullmove_number),
+        (u32::MAX, u32::MAX)
+    );
+}
+
+#[test]
+fn from_fen_start_position() {
+    let game_data = from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+    let default = GameData::default();
+    assert_eq!(game_data.board, default.board);
+    assert_eq!(game_data.can_move_2_squares, default.can_move_2_squares);
+    assert_eq!(game_data.to_move, PieceColor::White);
+    assert_eq!(game_data.moved_2_squares, None);
+    assert!(
+        game_data
+            .castling
+            .get(&PieceColor::Black)
+            .unwrap()
+            .queen_side
+    );
+}
+
+#[test]
+fn from_fen_en_passant_landing_square() {
+    let game_data =
+        from_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2").unwrap();
+    assert_eq!(game_data.moved_2_squares, Some(Position { x: 3, y: 4 }));
+    assert_eq!(game_data.en_passant_target(), Some(Position { x: 3, y: 5 }));
+}
+
+#[test]
+fn en_passant_field_follows_double_pushes() {
+    use crate::chess::{apply_move, parse_uci_move};
+    let mut game_data = GameData::default();
+    let mut fields = Vec::new();
+    // the target only lasts for the reply, and the capture removes the pawn
+    for mv in ["e2e4", "a7a6", "e4e5", "d7d5", "e5d6"] {
+        game_data = apply_move(&game_data, &parse_uci_move(mv).unwrap());
+        fields.push(
+            to_fen(&game_data)
+                .split_whitespace()
+                .nth(3)
+                .unwrap()
+                .to_string(),
+        );
+    }
+    assert_eq!(fields, ["e3", "-", "-", "d6", "-"]);
+    assert!(!game_data.board.contains_key(&Position { x: 3, y: 4 }));
+
+    // a double push answering a double push replaces the target
+    let game_data = apply_move(&GameData::default(), &parse_uci_move("e2e4").unwrap());
+    let game_data = apply_move(&game_data, &parse_uci_move("c7c5").unwrap());
+    assert_eq!(
+        game_data.en_passant_target(),
+        Position::from_algebraic("c6")
+    );
+    assert_eq!(
+        from_fen(&to_fen(&game_data)).unwrap().moved_2_squares,
+        game_data.moved_2_squares
+    );
+}
+
+#[test]
+fn from_fen_rejects_malformed_input() {
+    assert_eq!(
+        from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq -").unwrap_err(),
+        FenError::RankCount(7)
+    );
+    assert_eq!(
+        from_fen("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").unwrap_err(),
+        FenError::InvalidRank(6)
+    );
+    assert_eq!(
+        from_fen("rnbqkbnr/ppppxppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").unwrap_err(),
+        FenError::InvalidPiece('x')
+    );
+    assert_eq!(
+        from_fen("8/8/8/8/8/8/8/8 x - -").unwrap_err(),
+        FenError::InvalidSideToMove("x".to_string())
+    );
+    assert_eq!(
+        from_fen("8/8/8/8/8/8/8/8 w -").unwrap_err(),
+        FenError::MissingField("en passant")
+    );
+}
+
+#[test]
+fn normalize_fen_merges_spellings() {
+    let canonical = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+    for fen in [
+        canonical,
+        "r3k2r/8/8/8/8/8/8/R3K2R w qkQK - 0 1",
+        "r3k2r/8/8/8/8/8/8/R3K2R   w  KqQk  -  0  1",
+        "r3k2r/8/8/8/8/8/8/R3K2R w KQkq -",
+    ] {
+        assert_eq!(normalize_fen(fen).unwrap(), canonical, "{fen}");
+    }
+    // nothing can take on e3, so the square means nothing
+    let after_e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+    assert_eq!(
+        normalize_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap(),
+        after_e4
+    );
+    // but here d4 can
+    let usable = "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3";
+    assert_eq!(normalize_fen(usable).unwrap(), usable);
+
+    assert_eq!(
+        normalize_fen("8/8/8/8/8/8/8/4K2k b - - 12 40").unwrap(),
+        "8/8/8/8/8/8/8/4K2k b - - 12 40"
+    );
+    assert_eq!(
+        normalize_fen_zeroed("8/8/8/8/8/8/8/4K2k b - - 12 40").unwrap(),
+        normalize_fen_zeroed("8/8/8/8/8/8/8/4K2k b - - 3 7").unwrap()
+    );
+    assert_eq!(
+        normalize_fen("8/8/8/8/8/8/8/4K2k b - - x 1"),
+        Err(FenError::InvalidClock("x".to_string()))
+    );
+    assert!(normalize_fen("8/8/8 w - -").is_err());
+}
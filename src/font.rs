@@ -0,0 +1,125 @@
+//! A small built-in 5x7 bitmap font, enough for move lists and clocks, so the
+//! GUI doesn't depend on a font file.
+use crate::graphics::{Drawable, ShaderProgram, Sprite, Texture2D};
+use nalgebra_glm as glm;
+use stb_image::image::Image;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+// one pixel of padding keeps neighbouring glyphs from bleeding into each other
+const CELL_WIDTH: usize = GLYPH_WIDTH + 1;
+const CELL_HEIGHT: usize = GLYPH_HEIGHT + 1;
+
+// rows top to bottom, the lowest five bits of each row are the pixels
+const GLYPHS: &[(char, [u8; GLYPH_HEIGHT])] = &[
+    (' ', [0, 0, 0, 0, 0, 0, 0]),
+    ('0', [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e]),
+    ('1', [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e]),
+    ('2', [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f]),
+    ('3', [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e]),
+    ('4', [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02]),
+    ('5', [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e]),
+    ('6', [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e]),
+    ('7', [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e]),
+    ('9', [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c]),
+    ('a', [0x00, 0x00, 0x0e, 0x01, 0x0f, 0x11, 0x0f]),
+    ('b', [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1e]),
+    ('c', [0x00, 0x00, 0x0e, 0x10, 0x10, 0x11, 0x0e]),
+    ('d', [0x01, 0x01, 0x0d, 0x13, 0x11, 0x11, 0x0f]),
+    ('e', [0x00, 0x00, 0x0e, 0x11, 0x1f, 0x10, 0x0e]),
+    ('f', [0x06, 0x09, 0x08, 0x1c, 0x08, 0x08, 0x08]),
+    ('g', [0x00, 0x0f, 0x11, 0x11, 0x0f, 0x01, 0x0e]),
+    ('h', [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11]),
+    ('k', [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12]),
+    ('q', [0x00, 0x00, 0x0d, 0x13, 0x0f, 0x01, 0x01]),
+    ('w', [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0a]),
+    ('B', [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e]),
+    ('K', [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11]),
+    ('N', [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11]),
+    ('O', [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e]),
+    ('Q', [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d]),
+    ('R', [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11]),
+    ('x', [0x00, 0x00, 0x11, 0x0a, 0x04, 0x0a, 0x11]),
+    ('+', [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00]),
+    ('#', [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a]),
+    ('=', [0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00]),
+    ('-', [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c]),
+    (':', [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00]),
+];
+
+pub struct BitmapFont {
+    texture: Rc<Texture2D>,
+    glyphs: HashMap<char, glm::Vec4>,
+}
+
+impl BitmapFont {
+    /// Rasterizes the glyph table into a single-row white RGBA atlas.
+    pub fn new() -> Self {
+        let width = GLYPHS.len() * CELL_WIDTH;
+        let mut data = vec![0u8; width * CELL_HEIGHT * 4];
+        let mut glyphs = HashMap::new();
+        for (i, (c, rows)) in GLYPHS.iter().enumerate() {
+            for (y, row) in rows.iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if row & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+                        let offset = (y * width + i * CELL_WIDTH + x) * 4;
+                        data[offset..offset + 4].copy_from_slice(&[255; 4]);
+                    }
+                }
+            }
+            glyphs.insert(
+                *c,
+                glm::vec4(
+                    (i * CELL_WIDTH) as f32,
+                    0.0,
+                    GLYPH_WIDTH as f32,
+                    GLYPH_HEIGHT as f32,
+                ),
+            );
+        }
+        let atlas = Image::new(width, CELL_HEIGHT, 4, data);
+        Self {
+            texture: Rc::new(Texture2D::new_default(Rc::new(atlas))),
+            glyphs,
+        }
+    }
+
+    /// Height of one line of text at `scale`, spacing included.
+    pub fn line_height(scale: f32) -> f32 {
+        CELL_HEIGHT as f32 * scale
+    }
+
+    /// Draws `text` with its bottom-left corner at `origin`, each font pixel
+    /// `scale` screen pixels wide. Characters missing from the font are left
+    /// blank.
+    pub fn draw_text(
+        &self,
+        shader: Rc<ShaderProgram>,
+        text: &str,
+        origin: glm::Vec2,
+        scale: f32,
+        projection: &glm::Mat4,
+    ) {
+        for (i, c) in text.chars().enumerate() {
+            let Some(&glyph) = self.glyphs.get(&c) else {
+                continue;
+            };
+            Sprite::new(
+                shader.clone(),
+                self.texture.clone(),
+                glyph,
+                glm::vec4(
+                    origin.x + (i * CELL_WIDTH) as f32 * scale,
+                    origin.y,
+                    GLYPH_WIDTH as f32 * scale,
+                    GLYPH_HEIGHT as f32 * scale,
+                ),
+            )
+            .draw(projection);
+        }
+    }
+}
@@ -0,0 +1,754 @@
+#![allow(dead_code)]
+use crate::chess::{
+    apply_move, game_status, generate_moves, is_in_check, promotes, promotion_fits, Board,
+    GameData, GameStatus, Move, Moves, PieceColor, PieceType,
+};
+use crate::engine::{best_move, evaluate};
+use crate::fen::position_key;
+use crate::san::move_to_san;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawReason {
+    Stalemate,
+    Agreement,
+    /// A flag fell, but the opponent had no material left to mate with.
+    TimeoutVsInsufficientMaterial,
+    /// Neither side has the material to mate.
+    InsufficientMaterial,
+    /// Called by `self_play` after the evaluation stayed level for long.
+    Adjudicated,
+    /// Fifty moves each without a capture or a pawn move.
+    FiftyMove,
+    /// The same position for the third time, same side to move.
+    ThreefoldRepetition,
+}
+
+/// Plies in a row without a capture or a pawn move for the fifty-move rule.
+const FIFTY_MOVE_PLIES: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw(DrawReason),
+    Ongoing,
+}
+
+impl GameResult {
+    pub fn win_for(color: PieceColor) -> GameResult {
+        match color {
+            PieceColor::White => GameResult::WhiteWins,
+            PieceColor::Black => GameResult::BlackWins,
+        }
+    }
+}
+
+/// Whether `color` has anything besides the king that could ever deliver
+/// mate: a pawn, rook or queen, or at least two minor pieces.
+fn has_mating_material(board: &Board, color: PieceColor) -> bool {
+    let mut minors = 0;
+    for &piece in board.values().filter(|piece| piece.get_color() == color) {
+        match piece {
+            PieceType::Pawn(_) | PieceType::Rook(_) | PieceType::Queen(_) => return true,
+            PieceType::Bishop(_) | PieceType::Knight(_) => minors += 1,
+            PieceType::King(_) => {}
+        }
+    }
+    minors >= 2
+}
+
+/// The result when `flagged` runs out of time: a loss, unless no sequence of
+/// legal moves could have let the opponent mate. A lone minor piece can still
+/// mate when the flagged side has something to block its own king with.
+pub fn timeout_result(board: &Board, flagged: PieceColor) -> GameResult {
+    let opponent = flagged.get_opposite();
+    let pieces = |color: PieceColor| {
+        board
+            .values()
+            .filter(move |piece| piece.get_color() == color)
+    };
+    let has_minor =
+        pieces(opponent).any(|piece| matches!(piece, PieceType::Bishop(_) | PieceType::Knight(_)));
+    let flagged_has_more = pieces(flagged).any(|piece| !matches!(piece, PieceType::King(_)));
+    if has_mating_material(board, opponent) || has_minor && flagged_has_more {
+        GameResult::win_for(opponent)
+    } else {
+        GameResult::Draw(DrawReason::TimeoutVsInsufficientMaterial)
+    }
+}
+
+/// Remaining thinking time for both sides with a Fischer increment.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    remaining: HashMap<PieceColor, Duration>,
+    increment: Duration,
+}
+
+impl Clock {
+    pub fn new(initial: Duration, increment: Duration) -> Self {
+        Self {
+            remaining: HashMap::from([(PieceColor::White, initial), (PieceColor::Black, initial)]),
+            increment,
+        }
+    }
+    pub fn remaining(&self, who: PieceColor) -> Duration {
+        self.remaining[&who]
+    }
+
+    /// Runs `who`'s clock down by `elapsed` without completing a move.
+    pub fn tick(&mut self, who: PieceColor, elapsed: Duration) {
+        let remaining = self.remaining.get_mut(&who).unwrap();
+        *remaining = remaining.saturating_sub(elapsed);
+    }
+
+    /// Charges `who` for a move that took `elapsed` and adds the increment,
+    /// unless the flag already fell.
+    pub fn on_move(&mut self, who: PieceColor, elapsed: Duration) {
+        self.tick(who, elapsed);
+        if self.flag() != Some(who) {
+            *self.remaining.get_mut(&who).unwrap() += self.increment;
+        }
+    }
+
+    /// The side whose time has run out, if any.
+    pub fn flag(&self) -> Option<PieceColor> {
+        [PieceColor::White, PieceColor::Black]
+            .into_iter()
+            .find(|who| self.remaining[who].is_zero())
+    }
+}
+
+/// Why the game in its current position is a draw, if it is one: stalemate,
+/// a dead position, threefold repetition or the fifty-move rule, in that
+/// order. Checkmate isn't a draw even on the hundredth quiet ply. The
+/// fifty-move count goes on from a FEN's halfmove clock, while repetitions
+/// only count positions reached in this game.
+pub fn draw_reason(game: &Game) -> Option<DrawReason> {
+    let position = game.position();
+    match game_status(position) {
+        GameStatus::Checkmate => return None,
+        GameStatus::Stalemate => return Some(DrawReason::Stalemate),
+        GameStatus::Ongoing => {}
+    }
+    if !has_mating_material(&position.board, PieceColor::White)
+        && !has_mating_material(&position.board, PieceColor::Black)
+    {
+        return Some(DrawReason::InsufficientMaterial);
+    }
+    let quiet = position.halfmove_clock as usize;
+    if repetitions(game, quiet) >= 3 {
+        return Some(DrawReason::ThreefoldRepetition);
+    }
+    if quiet >= FIFTY_MOVE_PLIES {
+        return Some(DrawReason::FiftyMove);
+    }
+    None
+}
+
+/// How often the current position has occurred, itself included. Only the
+/// last `quiet` plies can hold a repeat, as nothing before a capture or pawn
+/// move comes back.
+fn repetitions(game: &Game, quiet: usize) -> usize {
+    let current = game.position();
+    let key = position_key(current);
+    game.positions[game.positions.len() - 1 - quiet.min(game.moves.len())..]
+        .iter()
+        .filter(|&earlier| earlier.hash == current.hash && position_key(earlier) == key)
+        .count()
+}
+
+/// What `Game::on_move` callbacks are told about a move just played.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveRecord {
+    pub mv: Move,
+    pub san: String,
+    /// The status of the position the move led to.
+    pub status: GameStatus,
+}
+
+type MoveCallback = Box<dyn FnMut(&MoveRecord)>;
+
+/// What a PGN file says about a move besides the move itself: the comment
+/// after it and its numeric annotation glyphs, `$1` being "good move".
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MoveAnnotation {
+    pub comment: Option<String>,
+    pub nags: Vec<u8>,
+}
+
+/// A game in progress: the position before and after every move played (the
+/// moves also kept in SAN) and any result agreed on outside the board
+/// (resignation, draw offer).
+pub struct Game {
+    // the starting position first, then one per move
+    positions: Vec<GameData>,
+    moves: Vec<Move>,
+    san: Vec<String>,
+    // one per move, empty unless someone annotated it
+    annotations: Vec<MoveAnnotation>,
+    declared_result: Option<GameResult>,
+    // the legal moves of the current position, regenerated when it changes
+    legal: Moves,
+    on_move: Option<MoveCallback>,
+}
+
+/// A clone is not watched by the original's `on_move` callback.
+impl Clone for Game {
+    fn clone(&self) -> Self {
+        Self {
+            positions: self.positions.clone(),
+            moves: self.moves.clone(),
+            san: self.san.clone(),
+            annotations: self.annotations.clone(),
+            declared_result: self.declared_result,
+            legal: self.legal.clone(),
+            on_move: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for Game {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Game")
+            .field("positions", &self.positions)
+            .field("moves", &self.moves)
+            .field("san", &self.san)
+            .field("annotations", &self.annotations)
+            .field("declared_result", &self.declared_result)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::from_position(GameData::default())
+    }
+}
+
+impl Game {
+    pub fn from_position(start: GameData) -> Self {
+        Self {
+            legal: generate_moves(&start),
+            positions: vec![start],
+            moves: Vec::new(),
+            san: Vec::new(),
+            annotations: Vec::new(),
+            declared_result: None,
+            on_move: None,
+        }
+    }
+    pub fn start_position(&self) -> &GameData {
+        &self.positions[0]
+    }
+    pub fn position(&self) -> &GameData {
+        self.positions.last().unwrap()
+    }
+    /// The position after the first `ply` moves, so ply 0 is the start.
+    pub fn position_at(&self, ply: usize) -> Option<&GameData> {
+        self.positions.get(ply)
+    }
+    /// The positions before the current one, oldest first, as the engine
+    /// takes them to spot repetitions.
+    pub fn history(&self) -> &[GameData] {
+        &self.positions[..self.positions.len() - 1]
+    }
+    pub fn ply_count(&self) -> usize {
+        self.moves.len()
+    }
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+    pub fn san_moves(&self) -> &[String] {
+        &self.san
+    }
+    /// The annotations of the moves, one for each in `moves`.
+    pub fn annotations(&self) -> &[MoveAnnotation] {
+        &self.annotations
+    }
+    /// The annotation of `moves()[index]`, to comment on the move.
+    pub fn annotation_mut(&mut self, index: usize) -> Option<&mut MoveAnnotation> {
+        self.annotations.get_mut(index)
+    }
+    /// The legal moves of the current position by origin square, kept from
+    /// one move to the next rather than generated on every call.
+    pub fn legal_moves(&self) -> &Moves {
+        &self.legal
+    }
+
+    /// The SAN log grouped by move number, one row per number such as
+    /// `"12. Nf3 Nc6"`. A game starting with Black to move opens with `"1... e5"`.
+    pub fn move_list(&self) -> Vec<String> {
+        let mut rows = Vec::new();
+        let mut sans = self.san.iter();
+        if self.start_position().to_move == PieceColor::Black {
+            if let Some(san) = sans.next() {
+                rows.push(format!("1... {san}"));
+            }
+        }
+        let first_number = rows.len() + 1;
+        let remaining: Vec<&String> = sans.collect();
+        for (i, pair) in remaining.chunks(2).enumerate() {
+            let row = pair
+                .iter()
+                .map(|san| san.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            rows.push(format!("{}. {row}", first_number + i));
+        }
+        rows
+    }
+
+    /// Calls `f` after every move `play` accepts from now on, replacing any
+    /// earlier callback.
+    pub fn on_move(&mut self, f: impl FnMut(&MoveRecord) + 'static) {
+        self.on_move = Some(Box::new(f));
+    }
+
+    /// Plays `mv` if it is legal and the game is not over yet.
+    pub fn play(&mut self, mv: Move) -> bool {
+        if self.result() != GameResult::Ongoing {
+            return false;
+        }
+        if !self
+            .legal
+            .get(&mv.from)
+            .is_some_and(|destinations| destinations.contains(&mv.to))
+        {
+            return false;
+        }
+        let position = self.position();
+        if !promotion_fits(position, &mv) {
+            return false;
+        }
+        let mv = Move {
+            promotion: mv
+                .promotion
+                .or(promotes(position, &mv).then_some(PieceType::Queen(position.to_move))),
+            ..mv
+        };
+        let next = apply_move(position, &mv);
+        self.san.push(move_to_san(position, &mv));
+        self.legal = generate_moves(&next);
+        self.positions.push(next);
+        self.moves.push(mv);
+        self.annotations.push(MoveAnnotation::default());
+        let status = self.status();
+        if let Some(on_move) = &mut self.on_move {
+            on_move(&MoveRecord {
+                mv,
+                san: self.san.last().unwrap().clone(),
+                status,
+            });
+        }
+        true
+    }
+
+    pub fn resign(&mut self, who: PieceColor) {
+        if self.result() == GameResult::Ongoing {
+            self.declared_result = Some(GameResult::win_for(who.get_opposite()));
+        }
+    }
+    /// Ends the game on time, see `timeout_result`.
+    pub fn flag(&mut self, who: PieceColor) {
+        if self.result() == GameResult::Ongoing {
+            self.declared_result = Some(timeout_result(&self.position().board, who));
+        }
+    }
+    pub fn agree_draw(&mut self) {
+        if self.result() == GameResult::Ongoing {
+            self.declared_result = Some(GameResult::Draw(DrawReason::Agreement));
+        }
+    }
+    /// Ends the game as a draw if `draw_reason` finds one, returning it.
+    pub fn claim_draw(&mut self) -> Option<DrawReason> {
+        if self.result() != GameResult::Ongoing {
+            return None;
+        }
+        let reason = draw_reason(self)?;
+        self.declared_result = Some(GameResult::Draw(reason));
+        Some(reason)
+    }
+
+    /// `game_status` of the current position, from the cached legal moves.
+    fn status(&self) -> GameStatus {
+        if !self.legal.is_empty() {
+            GameStatus::Ongoing
+        } else if is_in_check(self.position()) {
+            GameStatus::Checkmate
+        } else {
+            GameStatus::Stalemate
+        }
+    }
+
+    /// The declared result if there is one, otherwise whatever the board says.
+    pub fn result(&self) -> GameResult {
+        if let Some(result) = self.declared_result {
+            return result;
+        }
+        match self.status() {
+            GameStatus::Checkmate => GameResult::win_for(self.position().to_move.get_opposite()),
+            GameStatus::Stalemate => GameResult::Draw(DrawReason::Stalemate),
+            GameStatus::Ongoing => GameResult::Ongoing,
+        }
+    }
+}
+
+/// When `self_play` calls a game drawn before the board does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adjudication {
+    /// The evaluation counts as level while its size is below this, in
+    /// centipawns.
+    pub window: i32,
+    /// How many plies in a row it has to stay level for a draw, counting the
+    /// position the game stands at as one; 0 never draws on the evaluation.
+    pub plies: usize,
+    /// Also draw as soon as neither side can mate.
+    pub insufficient_material: bool,
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Self {
+            window: 20,
+            plies: 40,
+            insufficient_material: true,
+        }
+    }
+}
+
+/// The engine playing both sides from `start` at `depth` until the game ends,
+/// `max_plies` moves are made or `adjudication`, if any, calls it a draw.
+pub fn self_play(
+    start: GameData,
+    depth: u32,
+    max_plies: usize,
+    adjudication: Option<Adjudication>,
+) -> Game {
+    let mut game = Game::from_position(start);
+    let mut level_plies = 0;
+    while game.result() == GameResult::Ongoing && game.ply_count() < max_plies {
+        if let Some(adjudication) = adjudication {
+            let board = &game.position().board;
+            if adjudication.insufficient_material
+                && !has_mating_material(board, PieceColor::White)
+                && !has_mating_material(board, PieceColor::Black)
+            {
+                game.declared_result = Some(GameResult::Draw(DrawReason::InsufficientMaterial));
+                break;
+            }
+            if evaluate(game.position()).abs() < adjudication.window {
+                level_plies += 1;
+            } else {
+                level_plies = 0;
+            }
+            if adjudication.plies > 0 && level_plies >= adjudication.plies {
+                game.declared_result = Some(GameResult::Draw(DrawReason::Adjudicated));
+                break;
+            }
+        }
+        let Some(mv) = best_move(game.position(), depth, game.history()) else {
+            break;
+        };
+        game.play(mv);
+    }
+    game
+}
+
+#[test]
+fn resignation_wins_for_opponent() {
+    let mut game = Game::default();
+    assert!(game.play(crate::chess::parse_uci_move("e2e4").unwrap()));
+    game.resign(PieceColor::Black);
+    assert_eq!(game.result(), GameResult::WhiteWins);
+    assert!(!game.play(crate::chess::parse_uci_move("e7e5").unwrap()));
+
+    // a finished game can't be resigned again
+    game.resign(PieceColor::White);
+    assert_eq!(game.result(), GameResult::WhiteWins);
+}
+
+#[test]
+fn legal_moves_follow_the_position() {
+    let mut game = Game::default();
+    assert_eq!(game.legal_moves(), &generate_moves(game.position()));
+    let before = game.legal_moves().clone();
+    assert_eq!(game.legal_moves(), &before);
+
+    assert!(game.play(crate::chess::parse_uci_move("e2e4").unwrap()));
+    assert_ne!(game.legal_moves(), &before);
+    assert_eq!(game.legal_moves(), &generate_moves(game.position()));
+    let e7 = crate::chess::Position { x: 4, y: 6 };
+    assert!(game.legal_moves().contains_key(&e7));
+
+    // a rejected move leaves the position and its moves alone
+    let after = game.legal_moves().clone();
+    assert!(!game.play(crate::chess::parse_uci_move("e4e5").unwrap()));
+    assert_eq!(game.legal_moves(), &after);
+    assert_eq!(game.clone().legal_moves(), &after);
+}
+
+#[test]
+fn play_refuses_promotions_that_dont_fit() {
+    use crate::chess::parse_uci_move;
+    let with = |uci, piece| Move {
+        promotion: Some(piece),
+        ..parse_uci_move(uci).unwrap()
+    };
+    let mut game = Game::default();
+    assert!(!game.play(with("e2e4", PieceType::Queen(PieceColor::White))));
+    assert_eq!(game.ply_count(), 0);
+
+    let start = crate::fen::from_fen("8/P6k/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let mut game = Game::from_position(start);
+    for piece in [
+        PieceType::King(PieceColor::Black),
+        PieceType::King(PieceColor::White),
+        PieceType::Pawn(PieceColor::White),
+        PieceType::Queen(PieceColor::Black),
+    ] {
+        assert!(!game.play(with("a7a8", piece)), "{piece:?}");
+    }
+    assert_eq!(game.ply_count(), 0);
+    assert!(game.play(with("a7a8", PieceType::Knight(PieceColor::White))));
+    assert_eq!(game.san_moves(), ["a8=N"]);
+    // no piece named makes a queen
+    let mut game = Game::from_position(game.start_position().clone());
+    assert!(game.play(parse_uci_move("a7a8").unwrap()));
+    assert_eq!(game.san_moves(), ["a8=Q"]);
+}
+
+#[test]
+fn checkmate_and_agreed_draw_results() {
+    let mut game = Game::default();
+    for mv in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+        assert!(game.play(crate::chess::parse_uci_move(mv).unwrap()));
+    }
+    assert_eq!(game.result(), GameResult::BlackWins);
+    game.agree_draw();
+    assert_eq!(game.result(), GameResult::BlackWins);
+
+    let mut game = Game::default();
+    game.agree_draw();
+    assert_eq!(game.result(), GameResult::Draw(DrawReason::Agreement));
+}
+
+#[test]
+fn clock_applies_increment_and_flags() {
+    let mut clock = Clock::new(Duration::from_secs(60), Duration::from_secs(2));
+    clock.on_move(PieceColor::White, Duration::from_secs(10));
+    assert_eq!(clock.remaining(PieceColor::White), Duration::from_secs(52));
+    assert_eq!(clock.remaining(PieceColor::Black), Duration::from_secs(60));
+    assert_eq!(clock.flag(), None);
+
+    clock.tick(PieceColor::Black, Duration::from_secs(59));
+    assert_eq!(clock.flag(), None);
+    clock.on_move(PieceColor::Black, Duration::from_secs(5));
+    assert_eq!(clock.remaining(PieceColor::Black), Duration::ZERO);
+    assert_eq!(clock.flag(), Some(PieceColor::Black));
+}
+
+#[test]
+fn flag_fall_against_bare_king_is_a_draw() {
+    let mut game = Game::default();
+    game.flag(PieceColor::White);
+    assert_eq!(game.result(), GameResult::BlackWins);
+
+    let mut game =
+        Game::from_position(crate::fen::from_fen("4k3/8/8/8/8/8/3N4/4K3 w - - 0 1").unwrap());
+    game.flag(PieceColor::Black);
+    assert_eq!(
+        game.result(),
+        GameResult::Draw(DrawReason::TimeoutVsInsufficientMaterial)
+    );
+
+    // the black pawn can wall in its own king for a knight mate
+    let mut game =
+        Game::from_position(crate::fen::from_fen("4k3/4p3/8/8/8/8/3N4/4K3 w - - 0 1").unwrap());
+    game.flag(PieceColor::Black);
+    assert_eq!(game.result(), GameResult::WhiteWins);
+    // but the pawn alone doesn't help a bare king
+    let mut game =
+        Game::from_position(crate::fen::from_fen("4k3/4p3/8/8/8/8/8/4K3 w - - 0 1").unwrap());
+    game.flag(PieceColor::Black);
+    assert_eq!(
+        game.result(),
+        GameResult::Draw(DrawReason::TimeoutVsInsufficientMaterial)
+    );
+}
+
+#[test]
+fn move_list_pairs_moves_by_number() {
+    let mut game = Game::default();
+    for mv in ["e2e4", "e7e5", "g1f3"] {
+        assert!(game.play(crate::chess::parse_uci_move(mv).unwrap()));
+    }
+    assert_eq!(game.san_moves(), ["e4", "e5", "Nf3"]);
+    assert_eq!(game.move_list(), ["1. e4 e5", "2. Nf3"]);
+
+    let start = crate::fen::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1")
+        .unwrap();
+    let mut game = Game::from_position(start);
+    for mv in ["e7e5", "g1f3", "b8c6"] {
+        assert!(game.play(crate::chess::parse_uci_move(mv).unwrap()));
+    }
+    assert_eq!(game.move_list(), ["1... e5", "2. Nf3 Nc6"]);
+}
+
+#[test]
+fn positions_by_ply() {
+    let mut game = Game::default();
+    for mv in ["e2e4", "e7e5", "g1f3"] {
+        assert!(game.play(crate::chess::parse_uci_move(mv).unwrap()));
+    }
+    assert_eq!(game.ply_count(), 3);
+    let fen_at = |ply| crate::fen::to_fen(game.position_at(ply).unwrap());
+    assert_eq!(fen_at(0), crate::fen::to_fen(game.start_position()));
+    let after_e5 = game.position_at(2).unwrap();
+    assert_eq!(after_e5.to_move, PieceColor::White);
+    assert_eq!(
+        after_e5.board.get(&crate::chess::Position { x: 4, y: 4 }),
+        Some(&PieceType::Pawn(PieceColor::Black))
+    );
+    assert_eq!(fen_at(3), crate::fen::to_fen(game.position()));
+    assert!(game.position_at(4).is_none());
+}
+
+#[test]
+fn on_move_reports_each_move() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let mut game = Game::default();
+    let log = Rc::clone(&seen);
+    game.on_move(move |record| log.borrow_mut().push((record.san.clone(), record.status)));
+    for mv in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+        assert!(game.play(crate::chess::parse_uci_move(mv).unwrap()));
+    }
+    // refused moves are not reported
+    assert!(!game.play(crate::chess::parse_uci_move("e2e4").unwrap()));
+    assert_eq!(
+        *seen.borrow(),
+        [
+            ("f3".to_string(), GameStatus::Ongoing),
+            ("e5".to_string(), GameStatus::Ongoing),
+            ("g4".to_string(), GameStatus::Ongoing),
+            ("Qh4#".to_string(), GameStatus::Checkmate),
+        ]
+    );
+}
+
+#[test]
+fn self_play_adjudicates_dead_draws() {
+    use crate::fen::from_fen;
+    let adjudication = Adjudication {
+        plies: 6,
+        ..Adjudication::default()
+    };
+    let bare_kings = from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let game = self_play(bare_kings.clone(), 1, 100, Some(adjudication));
+    assert_eq!(
+        game.result(),
+        GameResult::Draw(DrawReason::InsufficientMaterial)
+    );
+    assert_eq!(game.ply_count(), 0);
+    assert_eq!(self_play(bare_kings, 1, 10, None).ply_count(), 10);
+
+    // blocked pawns, nothing for either king to do
+    let locked = from_fen("4k3/8/8/3p4/3P4/8/8/4K3 w - - 0 1").unwrap();
+    let game = self_play(locked.clone(), 1, 100, Some(adjudication));
+    assert_eq!(game.result(), GameResult::Draw(DrawReason::Adjudicated));
+    // called on the sixth level position in a row, not the seventh
+    let level: Vec<bool> = (0..=game.ply_count())
+        .map(|ply| evaluate(game.position_at(ply).unwrap()).abs() < adjudication.window)
+        .collect();
+    assert!(level[level.len() - adjudication.plies..]
+        .iter()
+        .all(|&level| level));
+    assert!(level[..level.len() - 1]
+        .windows(adjudication.plies)
+        .all(|window| window.contains(&false)));
+
+    // no plies, no adjudication on the evaluation
+    let off = Adjudication {
+        plies: 0,
+        ..adjudication
+    };
+    let game = self_play(locked, 1, 4, Some(off));
+    assert_eq!(game.result(), GameResult::Ongoing);
+    assert_eq!(game.ply_count(), 4);
+}
+
+#[test]
+fn draw_reason_for_each_rule() {
+    use crate::chess::parse_uci_move;
+    use crate::fen::from_fen;
+    let play = |game: &mut Game, moves: &str| {
+        for mv in moves.split_whitespace() {
+            assert!(game.play(parse_uci_move(mv).unwrap()), "{mv}");
+        }
+    };
+
+    assert_eq!(draw_reason(&Game::default()), None);
+    let stalemate = from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+    assert_eq!(
+        draw_reason(&Game::from_position(stalemate)),
+        Some(DrawReason::Stalemate)
+    );
+    let mated = from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+    assert_eq!(draw_reason(&Game::from_position(mated)), None);
+
+    // the knight takes the rook and is all that is left
+    let mut game = Game::from_position(from_fen("4k3/8/8/8/8/5r2/8/4K1N1 w - - 0 1").unwrap());
+    assert_eq!(draw_reason(&game), None);
+    play(&mut game, "g1f3");
+    assert_eq!(draw_reason(&game), Some(DrawReason::InsufficientMaterial));
+
+    // the knights go out and back twice: the start position for the third time
+    let mut game = Game::default();
+    play(&mut game, "g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1");
+    assert_eq!(draw_reason(&game), None);
+    play(&mut game, "f6g8");
+    assert_eq!(draw_reason(&game), Some(DrawReason::ThreefoldRepetition));
+    assert_eq!(game.result(), GameResult::Ongoing);
+    assert_eq!(game.claim_draw(), Some(DrawReason::ThreefoldRepetition));
+    assert_eq!(
+        game.result(),
+        GameResult::Draw(DrawReason::ThreefoldRepetition)
+    );
+    assert_eq!(game.claim_draw(), None);
+
+    // kings and rooks wander for fifty moves each, never visiting a
+    // position a third time
+    let start = from_fen("r6k/8/8/8/8/8/8/R6K w - - 0 1").unwrap();
+    let mut game = Game::from_position(start);
+    let mut seen = vec![position_key(game.position())];
+    while game.ply_count() < FIFTY_MOVE_PLIES {
+        assert_eq!(draw_reason(&game), None, "{:?}", game.san_moves());
+        let position = game.position();
+        let mut moves: Vec<Move> = crate::chess::legal_moves_flat(position)
+            .into_iter()
+            .filter(|mv| !position.board.contains_key(&mv.to))
+            .collect();
+        moves.sort_by_key(crate::chess::move_to_uci);
+        let mv = moves
+            .into_iter()
+            .find(|mv| {
+                let next = apply_move(position, mv);
+                let key = position_key(&next);
+                game_status(&next) == GameStatus::Ongoing
+                    && seen.iter().filter(|&seen| *seen == key).count() < 2
+            })
+            .expect("a quiet move that repeats nothing thrice");
+        assert!(game.play(mv));
+        seen.push(position_key(game.position()));
+    }
+    assert_eq!(draw_reason(&game), Some(DrawReason::FiftyMove));
+
+    // the count carries over from the FEN clock
+    let mut game = Game::from_position(from_fen("r6k/8/8/8/8/8/8/R6K w - - 99 80").unwrap());
+    assert_eq!(draw_reason(&game), None);
+    play(&mut game, "a1a2");
+    assert_eq!(draw_reason(&game), Some(DrawReason::FiftyMove));
+}
@@ -8,14 +8,98 @@ use std::ffi::{c_void, CStr, CString};
 use std::fs;
 use std::rc::Rc;
 
+/// Why the window or one of the files it draws with couldn't be set up.
+/// Resource paths are relative to the working directory, so they are named
+/// in full.
+#[derive(Debug)]
+pub enum GraphicsInitError {
+    /// SDL, the window or the GL context.
+    Sdl(String),
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+    Compile {
+        path: String,
+        log: String,
+    },
+    Link(String),
+    Image {
+        path: String,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for GraphicsInitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphicsInitError::Sdl(e) => write!(f, "couldn't open the window: {e}"),
+            GraphicsInitError::Read { path, source } => write!(
+                f,
+                "couldn't read {path}: {source} (run the game from the directory holding `resources`)"
+            ),
+            GraphicsInitError::Compile { path, log } => {
+                write!(f, "couldn't compile {path}: {}", log.trim())
+            }
+            GraphicsInitError::Link(log) => write!(f, "couldn't link shaders: {}", log.trim()),
+            GraphicsInitError::Image { path, reason } => {
+                write!(f, "couldn't decode {path}: {reason}")
+            }
+        }
+    }
+}
+
+impl Error for GraphicsInitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GraphicsInitError::Read { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+fn read_resource(path: &str) -> Result<Vec<u8>, GraphicsInitError> {
+    fs::read(path).map_err(|source| GraphicsInitError::Read {
+        path: path.to_string(),
+        source,
+    })
+}
+
+/// Loads an 8-bit image such as the spritesheet, checking that it has a
+/// channel count [`Texture2D`] can upload.
+pub fn load_image(path: &str) -> Result<Image<u8>, GraphicsInitError> {
+    let bytes = read_resource(path)?;
+    let reason = match stb_image::image::load_from_memory(&bytes) {
+        stb_image::image::LoadResult::ImageU8(image) if channel_format(image.depth).is_some() => {
+            return Ok(image)
+        }
+        stb_image::image::LoadResult::ImageU8(image) => {
+            format!("unsupported channel count {}", image.depth)
+        }
+        stb_image::image::LoadResult::ImageF32(_) => {
+            "floating point (HDR) images aren't supported".to_string()
+        }
+        stb_image::image::LoadResult::Error(e) => e,
+    };
+    Err(GraphicsInitError::Image {
+        path: path.to_string(),
+        reason,
+    })
+}
+
 pub struct Shader {
     id: GLuint,
 }
 impl Shader {
-    pub fn from_file(filename: &str, kind: gl::types::GLenum) -> Result<Shader, Box<dyn Error>> {
-        let buf = fs::read(filename)?;
+    pub fn from_file(filename: &str, kind: gl::types::GLenum) -> Result<Shader, GraphicsInitError> {
+        let buf = read_resource(filename)?;
         let shader_source = unsafe { CString::from_vec_unchecked(buf) };
-        Shader::from_source(shader_source.as_c_str(), kind)
+        Shader::from_source(shader_source.as_c_str(), kind).map_err(|e| {
+            GraphicsInitError::Compile {
+                path: filename.to_string(),
+                log: e.to_string(),
+            }
+        })
     }
     pub fn from_source(source: &CStr, kind: gl::types::GLenum) -> Result<Shader, Box<dyn Error>> {
         let id = shader_from_source(source, kind)?;
@@ -89,14 +173,34 @@ impl ShaderProgram {
             gl::LinkProgram(program_id);
         }
 
-        // continue with error handling here
-
         for shader in shaders {
             unsafe {
                 gl::DetachShader(program_id, shader.id);
             }
         }
 
+        let mut success: gl::types::GLint = 1;
+        unsafe {
+            gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut success);
+        }
+        if success == 0 {
+            let mut len: gl::types::GLint = 0;
+            unsafe {
+                gl::GetProgramiv(program_id, gl::INFO_LOG_LENGTH, &mut len);
+            }
+            let error = create_whitespace_cstring_with_len(len as usize);
+            unsafe {
+                gl::GetProgramInfoLog(
+                    program_id,
+                    len,
+                    std::ptr::null_mut(),
+                    error.as_ptr() as *mut gl::types::GLchar,
+                );
+                gl::DeleteProgram(program_id);
+            }
+            return Err(error.to_string_lossy().into_owned().into());
+        }
+
         Ok(ShaderProgram {
             id: program_id,
             uniform_locations: HashMap::new(),
@@ -130,6 +234,7 @@ impl ShaderProgram {
         }
     }
 
+    #[allow(dead_code)]
     pub fn set_uniform_int(&self, name: &str, value: i32) {
         let location = self.retrieve_uniform_location(name);
         unsafe {
@@ -142,6 +247,12 @@ impl ShaderProgram {
             gl::Uniform1f(location, value);
         }
     }
+    pub fn set_uniform_vec2f(&self, name: &str, value: glm::Vec2) {
+        let location = self.retrieve_uniform_location(name);
+        unsafe {
+            gl::Uniform2fv(location, 1, value.as_ptr());
+        }
+    }
     pub fn set_uniform_vec3f(&self, name: &str, value: glm::Vec3) {
         let location = self.retrieve_uniform_location(name);
         unsafe {
@@ -163,16 +274,68 @@ impl Drop for ShaderProgram {
         }
     }
 }
+/// How a texture is sampled when drawn bigger or smaller than its pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFilter {
+    /// Blocky but crisp; no mipmaps are generated.
+    Nearest,
+    /// Smooth, with trilinear mipmapping when minified.
+    Linear,
+}
+
+impl TextureFilter {
+    fn min_filter(self) -> GLenum {
+        match self {
+            TextureFilter::Nearest => gl::NEAREST,
+            TextureFilter::Linear => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+    fn mag_filter(self) -> GLenum {
+        match self {
+            TextureFilter::Nearest => gl::NEAREST,
+            TextureFilter::Linear => gl::LINEAR,
+        }
+    }
+}
+
+/// The GL pixel format for an image with `channels` 8-bit channels.
+pub fn channel_format(channels: usize) -> Option<GLenum> {
+    match channels {
+        1 => Some(gl::RED),
+        3 => Some(gl::RGB),
+        4 => Some(gl::RGBA),
+        _ => None,
+    }
+}
+
 pub struct Texture2D {
     id: GLuint,
     img: Rc<Image<u8>>,
 }
 impl Texture2D {
-    pub fn new(img: Rc<Image<u8>>, img_format: GLenum) -> Self {
+    /// A texture with [`TextureFilter::Linear`] filtering.
+    pub fn new_default(img: Rc<Image<u8>>) -> Self {
+        Self::new(img, TextureFilter::Linear)
+    }
+    /// Uploads `img`, taking the pixel format from its channel count.
+    ///
+    /// # Panics
+    ///
+    /// If the image doesn't have 1, 3 or 4 channels; [`load_image`] rejects
+    /// those up front.
+    pub fn new(img: Rc<Image<u8>>, filter: TextureFilter) -> Self {
+        let img_format = channel_format(img.depth)
+            .unwrap_or_else(|| panic!("unsupported channel count {}", img.depth));
+        // Rows are 4-byte aligned by default, which 1- and 3-channel images
+        // of odd widths aren't.
+        let row_aligned = (img.width * img.depth).is_multiple_of(4);
         let mut id: GLuint = 0;
         unsafe {
             gl::GenTextures(1, &mut id);
             gl::BindTexture(gl::TEXTURE_2D, id);
+            if !row_aligned {
+                gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            }
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
@@ -184,16 +347,21 @@ impl Texture2D {
                 gl::UNSIGNED_BYTE,
                 img.data.as_ptr() as *const c_void,
             );
-            gl::GenerateMipmap(gl::TEXTURE_2D);
+            if !row_aligned {
+                gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            }
+            if filter == TextureFilter::Linear {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
             gl::TexParameteri(
                 gl::TEXTURE_2D,
                 gl::TEXTURE_MAG_FILTER,
-                gl::LINEAR_MIPMAP_LINEAR.try_into().unwrap(),
+                filter.mag_filter().try_into().unwrap(),
             );
             gl::TexParameteri(
                 gl::TEXTURE_2D,
                 gl::TEXTURE_MIN_FILTER,
-                gl::LINEAR_MIPMAP_LINEAR.try_into().unwrap(),
+                filter.min_filter().try_into().unwrap(),
             );
         }
         Self { id, img }
@@ -212,6 +380,19 @@ impl Drop for Texture2D {
         }
     }
 }
+/// Maps the unit square onto `rect`, turned `angle` degrees counterclockwise
+/// about its center.
+fn model_transform(rect: &glm::Vec4, angle: f32) -> glm::Mat4 {
+    let mut model = glm::translation(&glm::vec3(rect.x, rect.y, 0.0));
+    if angle.is_normal() {
+        model = glm::translate(&model, &glm::vec3::<f32>(0.5 * rect.z, 0.5 * rect.w, 0.0));
+        model = glm::rotate(&model, f32::to_radians(angle), &glm::vec3(0.0, 0.0, 1.0));
+        model = glm::translate(&model, &glm::vec3::<f32>(-0.5 * rect.z, -0.5 * rect.w, 0.0));
+    }
+    model = glm::scale(&model, &glm::vec3::<f32>(rect.z, rect.w, 0.0));
+    model
+}
+
 pub trait Drawable {
     fn draw(&self, projection: &glm::Mat4);
 }
@@ -294,25 +475,13 @@ impl Sprite {
             vao,
         }
     }
+    /// Turns the sprite `angle` degrees counterclockwise about its center.
+    pub fn with_angle(mut self, angle: f32) -> Sprite {
+        self.angle = angle;
+        self
+    }
     fn get_transform(&self) -> glm::Mat4 {
-        let mut model = glm::translation(&glm::vec3(self.rect.x, self.rect.y, 0.0));
-        if self.angle.is_normal() {
-            model = glm::translate(
-                &model,
-                &glm::vec3::<f32>(0.5 * self.rect.z, 0.5 * self.rect.w, 0.0),
-            );
-            model = glm::rotate(
-                &model,
-                f32::to_radians(self.angle),
-                &glm::vec3(0.0, 0.0, 1.0),
-            );
-            model = glm::translate(
-                &model,
-                &glm::vec3::<f32>(-0.5 * self.rect.z, -0.5 * self.rect.w, 0.0),
-            );
-        }
-        model = glm::scale(&model, &glm::vec3::<f32>(self.rect.z, self.rect.w, 0.0));
-        model
+        model_transform(&self.rect, self.angle)
     }
 }
 impl Drop for Sprite {
@@ -374,24 +543,7 @@ impl Rect {
         }
     }
     fn get_transform(&self) -> glm::Mat4 {
-        let mut model = glm::translation(&glm::vec3(self.rect.x, self.rect.y, 0.0));
-        if self.angle.is_normal() {
-            model = glm::translate(
-                &model,
-                &glm::vec3::<f32>(0.5 * self.rect.z, 0.5 * self.rect.w, 0.0),
-            );
-            model = glm::rotate(
-                &model,
-                f32::to_radians(self.angle),
-                &glm::vec3(0.0, 0.0, 1.0),
-            );
-            model = glm::translate(
-                &model,
-                &glm::vec3::<f32>(-0.5 * self.rect.z, -0.5 * self.rect.w, 0.0),
-            );
-        }
-        model = glm::scale(&model, &glm::vec3::<f32>(self.rect.z, self.rect.w, 0.0));
-        model
+        model_transform(&self.rect, self.angle)
     }
 }
 
@@ -418,3 +570,161 @@ impl Drop for Rect {
         }
     }
 }
+
+/// Translucent rectangles such as square highlights, batched into a single
+/// draw call. Each entry is a rectangle `(x, y, width, height)` and an RGBA
+/// color; the shader takes the color per vertex.
+pub struct Overlay {
+    entries: Vec<(glm::Vec4, glm::Vec4)>,
+    pub shader: Rc<ShaderProgram>,
+    // opengl stuff
+    vbo: GLuint,
+    vao: GLuint,
+}
+
+/// Floats per overlay vertex: a position and a color.
+const OVERLAY_VERTEX_LEN: usize = 2 + 4;
+
+impl Overlay {
+    pub fn new(shader: Rc<ShaderProgram>) -> Overlay {
+        let mut vao: gl::types::GLuint = 0;
+        let mut vbo: gl::types::GLuint = 0;
+        let stride = (OVERLAY_VERTEX_LEN * std::mem::size_of::<f32>()) as gl::types::GLsizei;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::BindVertexArray(vao);
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(
+                1,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (2 * std::mem::size_of::<f32>()) as *const c_void,
+            );
+            gl::EnableVertexAttribArray(1);
+            gl::BindVertexArray(0);
+        }
+        Self {
+            entries: Vec::new(),
+            shader,
+            vbo,
+            vao,
+        }
+    }
+    pub fn push(&mut self, rect: glm::Vec4, color: glm::Vec4) {
+        self.entries.push((rect, color));
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Two triangles per entry, wound like [`Rect`]'s.
+fn overlay_vertices(entries: &[(glm::Vec4, glm::Vec4)]) -> Vec<f32> {
+    let mut vertices = Vec::with_capacity(entries.len() * 6 * OVERLAY_VERTEX_LEN);
+    for (rect, color) in entries {
+        let (left, bottom) = (rect.x, rect.y);
+        let (right, top) = (rect.x + rect.z, rect.y + rect.w);
+        for (x, y) in [
+            (left, top),
+            (right, top),
+            (right, bottom),
+            (left, top),
+            (left, bottom),
+            (right, bottom),
+        ] {
+            vertices.extend_from_slice(&[x, y, color.x, color.y, color.z, color.w]);
+        }
+    }
+    vertices
+}
+
+impl Drawable for Overlay {
+    fn draw(&self, projection: &glm::Mat4) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let vertices = overlay_vertices(&self.entries);
+        self.shader.bind();
+        self.shader.set_uniform_mat4f("mvp", projection);
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (vertices.len() * std::mem::size_of::<f32>())
+                    .try_into()
+                    .unwrap(),
+                vertices.as_ptr() as *const c_void,
+                gl::STREAM_DRAW,
+            );
+            gl::DrawArrays(
+                gl::TRIANGLES,
+                0,
+                (vertices.len() / OVERLAY_VERTEX_LEN) as gl::types::GLsizei,
+            );
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+impl Drop for Overlay {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.vbo);
+        }
+    }
+}
+
+#[test]
+fn half_turn_swaps_opposite_corners() {
+    let rect = glm::vec4(10.0, 20.0, 30.0, 40.0);
+    let corner = |angle: f32, x: f32, y: f32| {
+        let p = model_transform(&rect, angle) * glm::vec4(x, y, 0.0, 1.0);
+        (p.x.round(), p.y.round())
+    };
+    assert_eq!(corner(0.0, 0.0, 0.0), (10.0, 20.0));
+    assert_eq!(corner(180.0, 0.0, 0.0), (40.0, 60.0));
+    assert_eq!(corner(180.0, 1.0, 1.0), (10.0, 20.0));
+    assert_eq!(corner(180.0, 1.0, 0.0), (10.0, 60.0));
+}
+
+#[test]
+fn overlay_vertices_cover_each_rect() {
+    let red = glm::vec4(1.0, 0.0, 0.0, 0.5);
+    let vertices = overlay_vertices(&[(glm::vec4(10.0, 20.0, 30.0, 40.0), red)]);
+    assert_eq!(vertices.len(), 6 * OVERLAY_VERTEX_LEN);
+    let corners: Vec<(f32, f32)> = vertices
+        .chunks(OVERLAY_VERTEX_LEN)
+        .map(|v| (v[0], v[1]))
+        .collect();
+    assert_eq!(
+        corners,
+        [
+            (10.0, 60.0),
+            (40.0, 60.0),
+            (40.0, 20.0),
+            (10.0, 60.0),
+            (10.0, 20.0),
+            (40.0, 20.0)
+        ]
+    );
+    assert!(vertices
+        .chunks(OVERLAY_VERTEX_LEN)
+        .all(|v| v[2..] == [1.0, 0.0, 0.0, 0.5]));
+    assert!(overlay_vertices(&[]).is_empty());
+}
+
+#[test]
+fn channel_count_picks_the_pixel_format() {
+    assert_eq!(channel_format(1), Some(gl::RED));
+    assert_eq!(channel_format(3), Some(gl::RGB));
+    assert_eq!(channel_format(4), Some(gl::RGBA));
+    assert_eq!(channel_format(2), None);
+    assert_eq!(channel_format(0), None);
+}
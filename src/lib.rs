@@ -0,0 +1,60 @@
+//! Chess rules, a small alpha-beta engine and the notations around them.
+//!
+//! Most users only need the [`prelude`]: build a position with
+//! [`GameData::default`](chess::GameData) or [`fen::from_fen`], list the legal
+//! moves with [`generate_moves`](chess::generate_moves), play one with
+//! [`apply_move`](chess::apply_move) and check for the end of the game with
+//! [`game_status`](chess::game_status).
+//!
+//! The remaining modules cover the rest: [`engine`] for search and
+//! evaluation, [`worker`] to run it on a background thread, [`game`] for a
+//! game record with results, [`san`] and [`pgn`] for notation, [`opening`]
+//! for the opening book, [`uci`] for the engine protocol, [`text`] for
+//! playing on the terminal, [`zobrist`] for position hashing, [`random`] for
+//! seedable random numbers and [`theme`] for the GUI's board colors.
+//! [`Error`] gathers the errors of the steps that read outside input.
+pub mod chess;
+pub mod engine;
+pub mod error;
+pub mod fen;
+pub mod game;
+pub mod opening;
+pub mod pgn;
+pub mod random;
+pub mod san;
+pub mod text;
+pub mod theme;
+pub mod uci;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod worker;
+pub mod zobrist;
+
+pub use error::Error;
+
+pub mod prelude {
+    pub use crate::chess::{
+        apply_move, game_status, generate_moves, Board, Castling, GameData, GameStatus, Move,
+        Moves, PieceColor, PieceType, Position,
+    };
+}
+
+#[test]
+fn prelude_plays_a_move() {
+    use crate::prelude::*;
+    let game_data = GameData::default();
+    let moves: Moves = generate_moves(&game_data);
+    let from = Position { x: 4, y: 1 };
+    let to = Position { x: 4, y: 3 };
+    assert!(moves[&from].contains(&to));
+    let game_data = apply_move(
+        &game_data,
+        &Move {
+            from,
+            to,
+            promotion: None,
+        },
+    );
+    assert_eq!(game_data.to_move, PieceColor::Black);
+    assert_eq!(game_status(&game_data), GameStatus::Ongoing);
+}
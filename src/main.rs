@@ -1,7 +1,310 @@
-mod chess;
+#[cfg(feature = "gui")]
+mod editor;
+#[cfg(feature = "gui")]
+mod font;
+#[cfg(feature = "gui")]
 mod graphics;
+#[cfg(feature = "gui")]
 mod ui;
 
-fn main() {
-    let _ = ui::run();
+use rust_chess::chess::{move_to_uci, perft_stats, GameData};
+use rust_chess::engine::best_move;
+use rust_chess::fen::{from_fen_checked, normalize_fen, to_fen};
+use rust_chess::game::{self_play, Adjudication};
+use rust_chess::pgn::export_pgn;
+use rust_chess::san::move_to_san;
+#[cfg(feature = "gui")]
+use rust_chess::{chess, engine, fen, game, opening, theme, worker};
+use rust_chess::{text, uci};
+use std::error::Error;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+usage: chess [command] [arguments]
+
+commands:
+  play [--fps <n>] [--vsync]          play in a window (the default)
+  replay <game.pgn> [seconds per move] [--fps <n>] [--vsync]
+                                      watch a saved game
+  uci                                 speak UCI on stdin and stdout
+  text [<fen>]                        play on the terminal
+  perft <fen> <depth>                 count the positions <depth> plies ahead
+  bestmove <fen> <depth>              search for the best move
+  fen validate <fen>                  check that a position is playable
+  fen normalize <fen>                 print the canonical spelling of a FEN
+  selfplay <depth> [max plies]        let the engine play itself, as PGN
+  help, --help, -h                    show this message
+
+<fen> may be spread over several arguments or be `startpos`.";
+
+/// Moves a self-played game stops at unless told otherwise.
+const SELF_PLAY_MAX_PLIES: usize = 300;
+
+/// A subcommand and its arguments.
+#[derive(Debug, PartialEq)]
+enum Command<'a> {
+    /// The GUI, with the render flags still to be parsed.
+    Play(&'a [String]),
+    Replay(&'a [String]),
+    Uci,
+    /// A game on the terminal from the given FEN, if any.
+    Text(Option<String>),
+    Perft {
+        fen: String,
+        depth: u32,
+    },
+    BestMove {
+        fen: String,
+        depth: u32,
+    },
+    ValidateFen(String),
+    NormalizeFen(String),
+    SelfPlay {
+        depth: u32,
+        max_plies: usize,
+    },
+    Help,
+}
+
+impl<'a> Command<'a> {
+    /// Reads the arguments after the program name. Bare render flags still
+    /// start the GUI.
+    fn parse(args: &'a [String]) -> Result<Self, String> {
+        let Some(name) = args.first() else {
+            return Ok(Command::Play(args));
+        };
+        let rest = &args[1..];
+        Ok(match name.as_str() {
+            "play" => Command::Play(rest),
+            flag if flag.starts_with("--") && flag != "--help" => Command::Play(args),
+            "replay" => Command::Replay(rest),
+            "uci" => Command::Uci,
+            "text" => Command::Text((!rest.is_empty()).then(|| joined_fen(rest))),
+            "perft" => {
+                let (fen, depth) = fen_and_number(rest, "perft <fen> <depth>")?;
+                Command::Perft { fen, depth }
+            }
+            "bestmove" => {
+                let (fen, depth) = fen_and_number(rest, "bestmove <fen> <depth>")?;
+                Command::BestMove { fen, depth }
+            }
+            "fen" => match rest.split_first() {
+                Some((action, fen)) if !fen.is_empty() => match action.as_str() {
+                    "validate" => Command::ValidateFen(joined_fen(fen)),
+                    "normalize" => Command::NormalizeFen(joined_fen(fen)),
+                    _ => return Err("usage: chess fen <validate|normalize> <fen>".to_string()),
+                },
+                _ => return Err("usage: chess fen <validate|normalize> <fen>".to_string()),
+            },
+            "selfplay" => {
+                let usage = || "usage: chess selfplay <depth> [max plies]".to_string();
+                let depth = rest
+                    .first()
+                    .and_then(|d| d.parse().ok())
+                    .ok_or_else(usage)?;
+                let max_plies = match rest.get(1) {
+                    None => SELF_PLAY_MAX_PLIES,
+                    Some(plies) => plies.parse().map_err(|_| usage())?,
+                };
+                Command::SelfPlay { depth, max_plies }
+            }
+            "help" | "--help" | "-h" => Command::Help,
+            other => return Err(format!("unknown command `{other}`; see `chess --help`")),
+        })
+    }
+}
+
+/// A FEN given as one argument or as its six fields, `startpos` for the
+/// initial position.
+fn joined_fen(args: &[String]) -> String {
+    match args {
+        [only] if only == "startpos" => to_fen(&GameData::default()),
+        _ => args.join(" "),
+    }
+}
+
+/// A FEN followed by a number, as `perft` and `bestmove` take them.
+fn fen_and_number(args: &[String], usage: &str) -> Result<(String, u32), String> {
+    match args.split_last() {
+        Some((number, fen)) if !fen.is_empty() => number
+            .parse()
+            .map(|number| (joined_fen(fen), number))
+            .map_err(|_| format!("usage: chess {usage}")),
+        _ => Err(format!("usage: chess {usage}")),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = Command::parse(&args)
+        .map_err(Box::<dyn Error>::from)
+        .and_then(run_command);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("chess: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_command(command: Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Play(args) => run_gui(args),
+        Command::Replay(args) => run_replay(args),
+        Command::Uci => uci::run().map_err(|e| format!("uci: {e}").into()),
+        Command::Text(fen) => {
+            let start = match fen {
+                Some(fen) => from_fen_checked(&fen)?,
+                None => GameData::default(),
+            };
+            text::run(start)?;
+            Ok(())
+        }
+        Command::Perft { fen, depth } => {
+            let stats = perft_stats(&from_fen_checked(&fen)?, depth);
+            println!("nodes {}", stats.nodes);
+            println!("captures {}", stats.captures);
+            println!("en passant {}", stats.en_passant);
+            println!("castles {}", stats.castles);
+            println!("promotions {}", stats.promotions);
+            println!("checks {}", stats.checks);
+            println!("checkmates {}", stats.checkmates);
+            Ok(())
+        }
+        Command::BestMove { fen, depth } => {
+            let game_data = from_fen_checked(&fen)?;
+            match best_move(&game_data, depth, &[]) {
+                Some(mv) => println!(
+                    "bestmove {} ({})",
+                    move_to_uci(&mv),
+                    move_to_san(&game_data, &mv)
+                ),
+                None => println!("no legal moves"),
+            }
+            Ok(())
+        }
+        Command::ValidateFen(fen) => {
+            from_fen_checked(&fen)?;
+            println!("ok");
+            Ok(())
+        }
+        Command::NormalizeFen(fen) => {
+            println!("{}", normalize_fen(&fen)?);
+            Ok(())
+        }
+        Command::SelfPlay { depth, max_plies } => {
+            let game = self_play(
+                GameData::default(),
+                depth,
+                max_plies,
+                Some(Adjudication::default()),
+            );
+            print!("{}", export_pgn(&game));
+            Ok(())
+        }
+        Command::Help => {
+            println!("{USAGE}");
+            Ok(())
+        }
+    }
+}
+
+/// Splits the render flags `--fps <frames per second>` and `--vsync` off the
+/// other arguments.
+#[cfg(feature = "gui")]
+fn render_settings(args: &[String]) -> Result<(ui::RenderSettings, Vec<&str>), String> {
+    let mut settings = ui::RenderSettings::default();
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--vsync" => settings.vsync = true,
+            "--fps" => {
+                settings.fps = args
+                    .next()
+                    .and_then(|fps| fps.parse().ok())
+                    .filter(|&fps| fps > 0)
+                    .ok_or("--fps needs a positive number of frames per second")?;
+            }
+            arg => rest.push(arg),
+        }
+    }
+    Ok((settings, rest))
+}
+
+/// `chess [play] [--fps <n>] [--vsync]`
+#[cfg(feature = "gui")]
+fn run_gui(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (settings, _) = render_settings(args)?;
+    ui::run(settings)?;
+    Ok(())
+}
+
+/// `chess replay <game.pgn> [seconds per move] [--fps <n>] [--vsync]`
+#[cfg(feature = "gui")]
+fn run_replay(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (settings, args) = render_settings(args)?;
+    let path = args
+        .first()
+        .ok_or("usage: chess replay <game.pgn> [seconds per move] [--fps <n>] [--vsync]")?;
+    let interval = match args.get(1).map(|secs| secs.parse::<f32>()) {
+        None => ui::REPLAY_INTERVAL,
+        Some(Ok(secs)) if secs > 0.0 => std::time::Duration::from_secs_f32(secs),
+        Some(_) => return Err("seconds per move must be a positive number".into()),
+    };
+    let pgn = std::fs::read_to_string(path).map_err(|e| format!("couldn't read {path}: {e}"))?;
+    let game = rust_chess::pgn::import_pgn(&pgn).map_err(|e| format!("{path}: {e}"))?;
+    ui::replay(game, interval, settings)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "gui"))]
+fn run_replay(_args: &[String]) -> Result<(), Box<dyn Error>> {
+    Err("built without the `gui` feature; replays need the GUI".into())
+}
+
+#[cfg(not(feature = "gui"))]
+fn run_gui(_args: &[String]) -> Result<(), Box<dyn Error>> {
+    Err("built without the `gui` feature; run `chess uci` for the engine".into())
+}
+
+#[test]
+fn command_line_picks_the_subcommand() {
+    let args = |line: &str| -> Vec<String> { line.split_whitespace().map(String::from).collect() };
+    let start = to_fen(&GameData::default());
+
+    assert_eq!(Command::parse(&[]), Ok(Command::Play(&[])));
+    let vsync = args("--vsync");
+    assert_eq!(Command::parse(&vsync), Ok(Command::Play(&vsync)));
+    assert_eq!(Command::parse(&args("--help")), Ok(Command::Help));
+    assert_eq!(
+        Command::parse(&args("perft startpos 3")),
+        Ok(Command::Perft {
+            fen: start.clone(),
+            depth: 3
+        })
+    );
+    assert_eq!(
+        Command::parse(&args("bestmove 8/8/8/8/8/8/8/K6k w - - 0 1 2")),
+        Ok(Command::BestMove {
+            fen: "8/8/8/8/8/8/8/K6k w - - 0 1".to_string(),
+            depth: 2
+        })
+    );
+    assert_eq!(
+        Command::parse(&args("fen normalize startpos")),
+        Ok(Command::NormalizeFen(start))
+    );
+    assert_eq!(
+        Command::parse(&args("selfplay 2")),
+        Ok(Command::SelfPlay {
+            depth: 2,
+            max_plies: SELF_PLAY_MAX_PLIES
+        })
+    );
+    assert_eq!(Command::parse(&args("text")), Ok(Command::Text(None)));
+    assert!(Command::parse(&args("perft startpos")).is_err());
+    assert!(Command::parse(&args("fen tidy startpos")).is_err());
+    assert!(Command::parse(&args("castle")).is_err());
 }
@@ -0,0 +1,269 @@
+#![allow(dead_code)]
+use crate::chess::{apply_move, legal_moves_flat, parse_uci_move, GameData, Move, Position};
+use crate::fen::{from_fen, position_key, FenError};
+use crate::san::move_to_san;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::error::Error;
+use std::hash::{BuildHasher, Hasher};
+
+pub const DEFAULT_BOOK_PATH: &str = "./resources/books/default.txt";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BookError {
+    InvalidFen(usize, FenError),
+    InvalidMove(usize, String),
+}
+
+impl std::fmt::Display for BookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BookError::InvalidFen(line, e) => write!(f, "line {line}: {e}"),
+            BookError::InvalidMove(line, mv) => write!(f, "line {line}: illegal move '{mv}'"),
+        }
+    }
+}
+
+impl Error for BookError {}
+
+/// Opening replies by position. Each line of the text format holds a FEN
+/// (clocks optional) followed by space-separated UCI moves, each with an
+/// optional `:weight` (default 1). Blank lines and `#` comments are skipped:
+///
+/// ```text
+/// rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - e2e4:3 d2d4:2 c2c4
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OpeningBook {
+    entries: HashMap<String, Vec<(Move, u32)>>,
+}
+
+impl OpeningBook {
+    pub fn parse(text: &str) -> Result<Self, BookError> {
+        let mut book = OpeningBook::default();
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut tokens = line.split_whitespace().peekable();
+            let fen: Vec<&str> = tokens.by_ref().take(4).collect();
+            while tokens.peek().is_some_and(|t| t.parse::<u32>().is_ok()) {
+                tokens.next();
+            }
+            let game_data =
+                from_fen(&fen.join(" ")).map_err(|e| BookError::InvalidFen(line_number, e))?;
+            let legal_moves = legal_moves_flat(&game_data);
+            let entry = book.entries.entry(position_key(&game_data)).or_default();
+            for token in tokens {
+                let (notation, weight) = match token.split_once(':') {
+                    Some((notation, weight)) => (notation, weight.parse::<u32>().ok()),
+                    None => (token, Some(1)),
+                };
+                let invalid = || BookError::InvalidMove(line_number, token.to_string());
+                let weight = weight.ok_or_else(invalid)?;
+                let mv = parse_uci_move(notation)
+                    .and_then(|parsed| {
+                        legal_moves
+                            .iter()
+                            .find(|legal| legal.from == parsed.from && legal.to == parsed.to)
+                            .map(|legal| Move {
+                                promotion: parsed.promotion.or(legal.promotion),
+                                ..*legal
+                            })
+                    })
+                    .ok_or_else(invalid)?;
+                entry.push((mv, weight));
+            }
+        }
+        Ok(book)
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Self::parse(&text)?)
+    }
+
+    pub fn entries(&self, game_data: &GameData) -> &[(Move, u32)] {
+        self.entries
+            .get(&position_key(game_data))
+            .map_or(&[], |entries| entries.as_slice())
+    }
+
+    /// A book reply for the position, chosen at random in proportion to the
+    /// entry weights.
+    pub fn book_move(&self, game_data: &GameData) -> Option<Move> {
+        let roll = RandomState::new().build_hasher().finish();
+        pick_weighted(self.entries(game_data), roll)
+    }
+}
+
+fn pick_weighted(entries: &[(Move, u32)], roll: u64) -> Option<Move> {
+    let total: u64 = entries.iter().map(|&(_, weight)| weight as u64).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut roll = roll % total;
+    for &(mv, weight) in entries {
+        if roll < weight as u64 {
+            return Some(mv);
+        }
+        roll -= weight as u64;
+    }
+    None
+}
+
+/// An opening from the ECO table, such as `C20 King's Pawn Game`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EcoEntry {
+    pub code: &'static str,
+    pub name: &'static str,
+}
+
+impl std::fmt::Display for EcoEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.code, self.name)
+    }
+}
+
+/// The common openings by ECO code, each with the SAN moves that reach it
+/// from the start position.
+const ECO_TABLE: &[(&str, &str, &str)] = &[
+    ("A04", "Réti Opening", "Nf3"),
+    ("A10", "English Opening", "c4"),
+    ("A40", "Queen's Pawn Game", "d4"),
+    ("A45", "Indian Defence", "d4 Nf6"),
+    ("A80", "Dutch Defence", "d4 f5"),
+    ("B00", "King's Pawn Opening", "e4"),
+    ("B01", "Scandinavian Defence", "e4 d5"),
+    ("B02", "Alekhine's Defence", "e4 Nf6"),
+    ("B06", "Modern Defence", "e4 g6"),
+    ("B07", "Pirc Defence", "e4 d6 d4 Nf6"),
+    ("B10", "Caro-Kann Defence", "e4 c6"),
+    ("B20", "Sicilian Defence", "e4 c5"),
+    ("C00", "French Defence", "e4 e6"),
+    ("C20", "King's Pawn Game", "e4 e5"),
+    ("C23", "Bishop's Opening", "e4 e5 Bc4"),
+    ("C25", "Vienna Game", "e4 e5 Nc3"),
+    ("C30", "King's Gambit", "e4 e5 f4"),
+    ("C40", "King's Knight Opening", "e4 e5 Nf3"),
+    ("C41", "Philidor Defence", "e4 e5 Nf3 d6"),
+    ("C42", "Petrov's Defence", "e4 e5 Nf3 Nf6"),
+    ("C44", "King's Pawn Game", "e4 e5 Nf3 Nc6"),
+    ("C44", "Scotch Game", "e4 e5 Nf3 Nc6 d4"),
+    ("C46", "Three Knights Game", "e4 e5 Nf3 Nc6 Nc3"),
+    ("C47", "Four Knights Game", "e4 e5 Nf3 Nc6 Nc3 Nf6"),
+    ("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+    ("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+    ("D00", "Queen's Pawn Game", "d4 d5"),
+    ("D06", "Queen's Gambit", "d4 d5 c4"),
+    ("D10", "Slav Defence", "d4 d5 c4 c6"),
+    ("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+    ("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+    ("E12", "Queen's Indian Defence", "d4 Nf6 c4 e6 Nf3 b6"),
+    ("E20", "Nimzo-Indian Defence", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    ("E60", "King's Indian Defence", "d4 Nf6 c4 g6"),
+];
+
+/// The most specific opening in the ECO table whose moves start the game
+/// `moves` played from the start position. An illegal move ends the game
+/// there, so only the moves before it are classified.
+pub fn classify_opening(moves: &[(Position, Position)]) -> Option<EcoEntry> {
+    let mut game_data = GameData::default();
+    let mut sans = Vec::new();
+    for &(from, to) in moves {
+        let Some(mv) = legal_moves_flat(&game_data)
+            .into_iter()
+            .find(|mv| mv.from == from && mv.to == to)
+        else {
+            break;
+        };
+        sans.push(move_to_san(&game_data, &mv));
+        game_data = apply_move(&game_data, &mv);
+    }
+    ECO_TABLE
+        .iter()
+        .map(|&(code, name, line)| (code, name, line.split(' ').collect::<Vec<_>>()))
+        .filter(|(_, _, line)| {
+            line.len() <= sans.len() && sans.iter().zip(line).all(|(a, b)| a == b)
+        })
+        .max_by_key(|(_, _, line)| line.len())
+        .map(|(code, name, _)| EcoEntry { code, name })
+}
+
+#[test]
+fn book_replies_to_e4() {
+    let book = OpeningBook::parse(
+        "# tiny book\n\
+         rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 e2e4\n\
+         rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 c7c5:3\n",
+    )
+    .unwrap();
+    let game_data =
+        crate::chess::apply_move(&GameData::default(), &parse_uci_move("e2e4").unwrap());
+    let reply = book.book_move(&game_data).unwrap();
+    assert_eq!(crate::chess::move_to_uci(&reply), "c7c5");
+    assert_eq!(book.entries(&GameData::default()).len(), 1);
+
+    let game_data =
+        crate::chess::apply_move(&GameData::default(), &parse_uci_move("d2d4").unwrap());
+    assert_eq!(book.book_move(&game_data), None);
+}
+
+#[test]
+fn book_weighted_pick_and_errors() {
+    let book =
+        OpeningBook::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - e2e4:3 d2d4:1")
+            .unwrap();
+    let entries = book.entries(&GameData::default());
+    let picks: Vec<String> = (0..4)
+        .map(|roll| crate::chess::move_to_uci(&pick_weighted(entries, roll).unwrap()))
+        .collect();
+    assert_eq!(picks, ["e2e4", "e2e4", "e2e4", "d2d4"]);
+
+    assert_eq!(
+        OpeningBook::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - e2e5")
+            .unwrap_err(),
+        BookError::InvalidMove(1, "e2e5".to_string())
+    );
+    assert!(matches!(
+        OpeningBook::parse("\nnot a fen e2e4").unwrap_err(),
+        BookError::InvalidFen(2, _)
+    ));
+}
+
+#[test]
+fn default_book_loads() {
+    let book = OpeningBook::load(DEFAULT_BOOK_PATH).unwrap();
+    assert!(book.book_move(&GameData::default()).is_some());
+}
+
+#[test]
+fn classify_opening_by_longest_prefix() {
+    let moves = |uci: &str| -> Vec<(Position, Position)> {
+        uci.split(' ')
+            .map(|mv| {
+                let mv = parse_uci_move(mv).unwrap();
+                (mv.from, mv.to)
+            })
+            .collect()
+    };
+    let entry = classify_opening(&moves("e2e4 e7e5 g1f3")).unwrap();
+    assert_eq!(entry.to_string(), "C40 King's Knight Opening");
+    // moves past the table's line keep its name
+    let entry = classify_opening(&moves("e2e4 e7e5 g1f3 b8c6 f1b5 a7a6")).unwrap();
+    assert_eq!(entry.code, "C60");
+    assert_eq!(
+        classify_opening(&moves("e2e4 e7e5")).unwrap().name,
+        "King's Pawn Game"
+    );
+    assert_eq!(
+        classify_opening(&moves("d2d4 d7d5 c2c4 d5c4"))
+            .unwrap()
+            .code,
+        "D20"
+    );
+    assert_eq!(classify_opening(&moves("a2a3")), None);
+    assert_eq!(classify_opening(&[]), None);
+}
@@ -0,0 +1,464 @@
+#![allow(dead_code)]
+use crate::chess::{validate_position, PieceColor, PositionError};
+use crate::error::write_position_errors;
+use crate::fen::{from_fen, FenError};
+use crate::game::{Game, GameResult};
+use crate::san::san_to_move;
+
+const MAX_LINE_LENGTH: usize = 80;
+
+pub fn result_token(result: GameResult) -> &'static str {
+    match result {
+        GameResult::WhiteWins => "1-0",
+        GameResult::BlackWins => "0-1",
+        GameResult::Draw(_) => "1/2-1/2",
+        GameResult::Ongoing => "*",
+    }
+}
+
+/// Exports the game with the Seven Tag Roster and SAN movetext, each move
+/// followed by its annotation glyphs and comment.
+pub fn export_pgn(game: &Game) -> String {
+    let result = result_token(game.result());
+    let mut pgn = String::new();
+    for (tag, value) in [
+        ("Event", "?"),
+        ("Site", "?"),
+        ("Date", "????.??.??"),
+        ("Round", "?"),
+        ("White", "?"),
+        ("Black", "?"),
+        ("Result", result),
+    ] {
+        pgn.push_str(&format!("[{tag} \"{value}\"]\n"));
+    }
+    pgn.push('\n');
+
+    let black_first = usize::from(game.start_position().to_move == PieceColor::Black);
+    let mut tokens = Vec::new();
+    // a Black move gets its number again after a comment
+    let mut after_comment = false;
+    for (i, (san, annotation)) in game.san_moves().iter().zip(game.annotations()).enumerate() {
+        let number = (i + black_first) / 2 + 1;
+        if (i + black_first).is_multiple_of(2) {
+            tokens.push(format!("{number}."));
+        } else if i == 0 || after_comment {
+            tokens.push(format!("{number}..."));
+        }
+        tokens.push(san.clone());
+        tokens.extend(annotation.nags.iter().map(|nag| format!("${nag}")));
+        after_comment = annotation.comment.is_some();
+        if let Some(comment) = &annotation.comment {
+            tokens.extend(
+                format!("{{{comment}}}")
+                    .split_whitespace()
+                    .map(str::to_string),
+            );
+        }
+    }
+    tokens.push(result.to_string());
+
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+    pgn
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnError {
+    InvalidFen(FenError),
+    /// The `FEN` tag parses but can't be played from, see `validate_position`.
+    InvalidPosition(Vec<PositionError>),
+    /// The move `san` at `ply`, counted from 1, isn't legal in its position.
+    IllegalMove {
+        ply: usize,
+        san: String,
+    },
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnError::InvalidFen(e) => write!(f, "invalid FEN tag: {e}"),
+            PgnError::InvalidPosition(errors) => {
+                write!(f, "invalid FEN tag position: ")?;
+                write_position_errors(f, errors)
+            }
+            PgnError::IllegalMove { ply, san } => write!(f, "illegal move {san} at ply {ply}"),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// The movetext without variations or tag pairs, split into tokens. Comments
+/// stay as their words between `{` and `}` tokens.
+fn movetext_tokens(pgn: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    // a comment may run over a line that looks like a tag pair
+    let mut in_comment = false;
+    for line in pgn.lines() {
+        if !in_comment && line.trim_start().starts_with('[') {
+            continue;
+        }
+        // a `;` starts a rest-of-line comment, but not inside a brace comment
+        let mut end = line.len();
+        for (i, c) in line.char_indices() {
+            match c {
+                '{' => in_comment = true,
+                '}' => in_comment = false,
+                ';' if !in_comment => {
+                    end = i;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let line = &line[..end];
+        tokens.extend(line.split_inclusive(['{', '}', '(', ')']).flat_map(|part| {
+            let (text, delimiter) = match part.char_indices().last() {
+                Some((i, '{' | '}' | '(' | ')')) => (&part[..i], Some(&part[i..i + 1])),
+                _ => (part, None),
+            };
+            text.split_whitespace().chain(delimiter)
+        }));
+    }
+    let mut depth = 0;
+    let mut in_comment = false;
+    tokens
+        .into_iter()
+        .filter(|&token| {
+            match token {
+                "{" => in_comment = true,
+                "}" => in_comment = false,
+                "(" if !in_comment => depth += 1,
+                ")" if !in_comment => depth -= 1,
+                _ => return depth == 0,
+            }
+            depth == 0 && matches!(token, "{" | "}")
+        })
+        .collect()
+}
+
+/// Reads the first game of `pgn`: the moves of its main line, from the `FEN`
+/// tag if it has one, with the comments and annotation glyphs that follow
+/// them. Variations and a comment before the first move are skipped; the
+/// result token isn't checked against the board.
+pub fn import_pgn(pgn: &str) -> Result<Game, PgnError> {
+    let fen = pgn
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("[FEN \"")?.strip_suffix("\"]"));
+    let mut game = match fen {
+        Some(fen) => {
+            let start = from_fen(fen).map_err(PgnError::InvalidFen)?;
+            validate_position(&start).map_err(PgnError::InvalidPosition)?;
+            Game::from_position(start)
+        }
+        None => Game::default(),
+    };
+    let mut comment: Option<Vec<&str>> = None;
+    for token in movetext_tokens(pgn) {
+        if let Some(words) = &mut comment {
+            if token != "}" {
+                words.push(token);
+                continue;
+            }
+            let text = words.join(" ");
+            comment = None;
+            let last = game.ply_count().checked_sub(1);
+            let Some(annotation) = last.and_then(|last| game.annotation_mut(last)) else {
+                continue;
+            };
+            if !text.is_empty() {
+                annotation.comment = Some(match annotation.comment.take() {
+                    Some(earlier) => format!("{earlier} {text}"),
+                    None => text,
+                });
+            }
+            continue;
+        }
+        if token == "{" {
+            comment = Some(Vec::new());
+            continue;
+        }
+        if let Some(nag) = token.strip_prefix('$') {
+            let last = game.ply_count().checked_sub(1);
+            if let (Ok(nag), Some(annotation)) =
+                (nag.parse(), last.and_then(|last| game.annotation_mut(last)))
+            {
+                annotation.nags.push(nag);
+            }
+            continue;
+        }
+        // move numbers may be glued to the move, as in `12.e4`
+        let san = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        if san.is_empty() {
+            continue;
+        }
+        if matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+            break;
+        }
+        let ply = game.ply_count() + 1;
+        let illegal = || PgnError::IllegalMove {
+            ply,
+            san: san.to_string(),
+        };
+        let mv = san_to_move(game.position(), san).ok_or_else(illegal)?;
+        if !game.play(mv) {
+            return Err(illegal());
+        }
+    }
+    Ok(game)
+}
+
+/// A game read from a PGN database, with its tag pairs in file order.
+#[derive(Debug, Clone)]
+pub struct ParsedGame {
+    pub tags: Vec<(String, String)>,
+    pub game: Game,
+}
+
+impl ParsedGame {
+    /// The value of the tag `name`, if the game has it.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The tag pairs of one game, as `[Name "value"]` lines.
+fn parse_tags(pgn: &str) -> Vec<(String, String)> {
+    pgn.lines()
+        .filter_map(|line| {
+            let pair = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+            let (name, value) = pair.split_once(char::is_whitespace)?;
+            let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+            Some((name.to_string(), value.replace("\\\"", "\"")))
+        })
+        .collect()
+}
+
+/// Splits a PGN file into its games. A game ends at its result token or where
+/// the tag section of the next one starts; a result inside a comment or a
+/// variation doesn't count.
+fn split_games(pgn: &str) -> Vec<&str> {
+    let mut games = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    let mut in_movetext = false;
+    let mut finished = false;
+    let mut in_comment = false;
+    let mut depth = 0;
+    for line in pgn.split_inclusive('\n') {
+        let trimmed = line.trim();
+        let is_tag = !in_comment && trimmed.starts_with('[');
+        if (is_tag && in_movetext) || (finished && !trimmed.is_empty()) {
+            games.push(&pgn[start..offset]);
+            start = offset;
+            in_movetext = false;
+            finished = false;
+        }
+        offset += line.len();
+        if is_tag || trimmed.is_empty() {
+            continue;
+        }
+        in_movetext = true;
+        // the text of the line outside comments and variations
+        let mut text = String::new();
+        for c in line.chars() {
+            match c {
+                '{' => in_comment = true,
+                '}' => in_comment = false,
+                ';' if !in_comment => break,
+                '(' if !in_comment => depth += 1,
+                ')' if !in_comment => depth -= 1,
+                _ if !in_comment && depth == 0 => text.push(c),
+                _ => {}
+            }
+            if matches!(c, '{' | '}' | '(' | ')') {
+                text.push(' ');
+            }
+        }
+        finished |= text
+            .split_whitespace()
+            .any(|token| matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*"));
+    }
+    if !pgn[start..].trim().is_empty() {
+        games.push(&pgn[start..]);
+    }
+    games
+}
+
+/// Reads every game of a PGN database the way `import_pgn` reads one, with
+/// its tags. Fails on the first game that can't be read, with its index.
+pub fn import_pgn_database(pgn: &str) -> Result<Vec<ParsedGame>, (usize, PgnError)> {
+    split_games(pgn)
+        .into_iter()
+        .enumerate()
+        .map(|(i, text)| {
+            Ok(ParsedGame {
+                tags: parse_tags(text),
+                game: import_pgn(text).map_err(|e| (i, e))?,
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn export_pgn_reflects_resignation() {
+    let mut game = Game::default();
+    for mv in ["e2e4", "e7e5", "g1f3"] {
+        assert!(game.play(crate::chess::parse_uci_move(mv).unwrap()));
+    }
+    game.resign(crate::chess::PieceColor::Black);
+    let pgn = export_pgn(&game);
+    assert!(pgn.contains("[Result \"1-0\"]"));
+    assert!(pgn.ends_with("\n1. e4 e5 2. Nf3 1-0\n"));
+}
+
+#[test]
+fn export_pgn_draw_and_ongoing() {
+    let mut game = Game::default();
+    assert!(export_pgn(&game).ends_with("\n*\n"));
+    game.agree_draw();
+    let pgn = export_pgn(&game);
+    assert!(pgn.contains("[Result \"1/2-1/2\"]"));
+    assert!(pgn.ends_with("\n1/2-1/2\n"));
+}
+
+#[test]
+fn import_pgn_reads_the_main_line() {
+    let pgn = "[Event \"?\"]\n[White \"?\"]\n\n\
+        1. e4 e5 {a comment (with parentheses)} 2. Nf3 (2. f4 exf4) Nc6 $1\n\
+        3.Bb5 a6 ; the Morphy defence\n4. O-O 1-0\n";
+    let game = import_pgn(pgn).unwrap();
+    assert_eq!(
+        game.san_moves(),
+        ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "O-O"]
+    );
+
+    // exported games come back the same
+    assert_eq!(
+        import_pgn(&export_pgn(&game)).unwrap().moves(),
+        game.moves()
+    );
+}
+
+#[test]
+fn pgn_keeps_comments_and_glyphs() {
+    let pgn = "{before the game} 1. e4 {good move} e5 $1 2. Nf3 $2 $14 {a comment\n\
+        over two lines} {and another} Nc6 (2... d6 {Philidor}) 3. Bb5 {} *\n";
+    let game = import_pgn(pgn).unwrap();
+    assert_eq!(game.san_moves(), ["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+    let annotation = |comment: Option<&str>, nags: &[u8]| crate::game::MoveAnnotation {
+        comment: comment.map(str::to_string),
+        nags: nags.to_vec(),
+    };
+    let expected = [
+        annotation(Some("good move"), &[]),
+        annotation(None, &[1]),
+        annotation(Some("a comment over two lines and another"), &[2, 14]),
+        annotation(None, &[]),
+        annotation(None, &[]),
+    ];
+    assert_eq!(game.annotations(), expected);
+
+    let exported = export_pgn(&game);
+    // the comment wraps like the moves do
+    assert!(exported.ends_with(
+        "\n1. e4 {good move} 1... e5 $1 2. Nf3 $2 $14 {a comment over two lines and\n\
+         another} 2... Nc6 3. Bb5 *\n"
+    ));
+    let reimported = import_pgn(&exported).unwrap();
+    assert_eq!(reimported.moves(), game.moves());
+    assert_eq!(reimported.annotations(), game.annotations());
+}
+
+#[test]
+fn semicolons_inside_comments_are_text() {
+    let game = import_pgn("1. e4 {good; strong} e5 2. Nf3 ; the rest is ignored {\n*").unwrap();
+    assert_eq!(game.san_moves(), ["e4", "e5", "Nf3"]);
+    assert_eq!(
+        game.annotations()[0].comment.as_deref(),
+        Some("good; strong")
+    );
+
+    let mut game = import_pgn("1. d4 d5 2. c4 *").unwrap();
+    game.annotation_mut(1).unwrap().comment = Some("solid; the usual".to_string());
+    let reimported = import_pgn(&export_pgn(&game)).unwrap();
+    assert_eq!(reimported.moves(), game.moves());
+    assert_eq!(reimported.annotations(), game.annotations());
+}
+
+#[test]
+fn import_pgn_from_a_fen_tag_and_errors() {
+    let pgn = "[FEN \"4k3/1P6/8/8/8/8/8/4K3 w - - 0 1\"]\n\n1. b8=Q+ Kd7 *\n";
+    let game = import_pgn(pgn).unwrap();
+    assert_eq!(game.san_moves(), ["b8=Q+", "Kd7"]);
+
+    assert_eq!(
+        import_pgn("1. e4 e5 2. Ke3").unwrap_err(),
+        PgnError::IllegalMove {
+            ply: 3,
+            san: "Ke3".to_string()
+        }
+    );
+    assert!(matches!(
+        import_pgn("[FEN \"8/8 w\"]\n1. e4"),
+        Err(PgnError::InvalidFen(_))
+    ));
+    // a position without kings is refused before any move is generated
+    assert_eq!(
+        import_pgn("[FEN \"8/8/8/8/8/8/4P3/8 w - - 0 1\"]\n\n1. e4 *").unwrap_err(),
+        PgnError::InvalidPosition(vec![
+            PositionError::KingCount(PieceColor::White, 0),
+            PositionError::KingCount(PieceColor::Black, 0),
+        ])
+    );
+}
+
+#[test]
+fn import_pgn_database_reads_every_game() {
+    let pgn = "[Event \"Casual\"]\n[White \"A\"]\n[Result \"1-0\"]\n\n\
+        1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 $4 {missing the threat,\n\
+        [a bracket in a comment] or 0-1 here} 4. Qxf7# 1-0\n\n\
+        [Event \"Casual\"]\n[White \"B\"]\n[Result \"1/2-1/2\"]\n\n\
+        1. d4 d5 2. c4 (2. Nf3 Nf6 1-0) dxc4 ; accepted\n1/2-1/2\n\n\
+        1. Nf3 *\n";
+    let games = import_pgn_database(pgn).unwrap();
+    assert_eq!(games.len(), 3);
+
+    assert_eq!(games[0].tag("White"), Some("A"));
+    assert_eq!(games[0].tags.len(), 3);
+    assert_eq!(games[0].game.ply_count(), 7);
+    assert_eq!(games[0].game.result(), GameResult::WhiteWins);
+
+    assert_eq!(games[1].tag("White"), Some("B"));
+    assert_eq!(games[1].game.san_moves(), ["d4", "d5", "c4", "dxc4"]);
+    let accepted =
+        crate::fen::from_fen("rnbqkbnr/ppp1pppp/8/8/2pP4/8/PP2PPPP/RNBQKBNR w KQkq - 0 3").unwrap();
+    assert_eq!(games[1].game.position().board, accepted.board);
+
+    assert!(games[2].tags.is_empty());
+    assert_eq!(games[2].game.san_moves(), ["Nf3"]);
+
+    let broken = pgn.replace("Qxf7#", "Qxf8");
+    assert!(matches!(
+        import_pgn_database(&broken),
+        Err((0, PgnError::IllegalMove { ply: 7, .. }))
+    ));
+    assert!(import_pgn_database("").unwrap().is_empty());
+}
@@ -0,0 +1,56 @@
+//! Seedable random numbers, for whatever should play out the same way twice
+//! given the same seed, such as random games in tests.
+
+/// A source of random numbers.
+pub trait Rng {
+    fn next_u64(&mut self) -> u64;
+
+    /// A number in `0..n`, every one equally likely; `n` must not be zero.
+    fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+}
+
+/// SplitMix64, the generator behind the Zobrist keys: fast, and good enough
+/// for anything but cryptography.
+#[derive(Debug, Clone)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+}
+
+impl Rng for SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        let (state, value) = split_mix64(self.state);
+        self.state = state;
+        value
+    }
+}
+
+/// One SplitMix64 step: the next state and the number drawn from it. A
+/// `const fn` so the Zobrist keys can be drawn at compile time.
+pub(crate) const fn split_mix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, z ^ (z >> 31))
+}
+
+#[test]
+fn split_mix_repeats_for_a_seed() {
+    let draw = |seed| {
+        let mut rng = SplitMix64::new(seed);
+        (0..8).map(|_| rng.below(10)).collect::<Vec<_>>()
+    };
+    assert_eq!(draw(7), draw(7));
+    assert_ne!(draw(7), draw(8));
+    assert!(draw(7).iter().all(|&n| n < 10));
+    let mut rng = SplitMix64::new(0);
+    assert!((0..100).all(|_| rng.below(1) == 0));
+}
@@ -0,0 +1,125 @@
+#![allow(dead_code)]
+use crate::chess::{
+    apply_move, game_status, generate_moves_detailed, movers_to, GameData, GameStatus, Move,
+    PieceType,
+};
+
+/// SAN names pieces with uppercase letters whatever their color.
+fn piece_letter(piece: PieceType) -> char {
+    piece.letter().to_ascii_uppercase()
+}
+
+/// Standard Algebraic Notation for a legal move in `game_data`, including the
+/// check (`+`) or mate (`#`) suffix.
+pub fn move_to_san(game_data: &GameData, mv: &Move) -> String {
+    let piece = *game_data.board.get(&mv.from).unwrap();
+    let mut san = String::new();
+    if matches!(piece, PieceType::King(_)) && (mv.from.x - mv.to.x).abs() == 2 {
+        san.push_str(if mv.to.x == 6 { "O-O" } else { "O-O-O" });
+    } else {
+        let is_capture = game_data.board.contains_key(&mv.to)
+            || matches!(piece, PieceType::Pawn(_)) && mv.from.x != mv.to.x;
+        let from = mv.from.to_algebraic();
+        if let PieceType::Pawn(_) = piece {
+            if is_capture {
+                san.push_str(&from[0..1]);
+            }
+        } else {
+            san.push(piece_letter(piece));
+            let rivals: Vec<_> = movers_to(game_data, mv.to)
+                .into_iter()
+                .filter(|pos| *pos != mv.from && game_data.board.get(pos) == Some(&piece))
+                .collect();
+            if !rivals.is_empty() {
+                if rivals.iter().all(|pos| pos.x != mv.from.x) {
+                    san.push_str(&from[0..1]);
+                } else if rivals.iter().all(|pos| pos.y != mv.from.y) {
+                    san.push_str(&from[1..2]);
+                } else {
+                    san.push_str(&from);
+                }
+            }
+        }
+        if is_capture {
+            san.push('x');
+        }
+        san.push_str(&mv.to.to_algebraic());
+        if let Some(promotion) = mv.promotion {
+            san.push('=');
+            san.push(piece_letter(promotion));
+        }
+    }
+    let after = apply_move(game_data, mv);
+    match game_status(&after) {
+        GameStatus::Checkmate => san.push('#'),
+        _ if crate::chess::is_in_check(&after) => san.push('+'),
+        _ => {}
+    }
+    san
+}
+
+/// A SAN move without its check, mate and annotation suffixes, castling
+/// spelled with letters.
+fn bare_san(san: &str) -> String {
+    san.trim_end_matches(['+', '#', '!', '?']).replace('0', "O")
+}
+
+/// The legal move in `game_data` that `san` names. The check or mate suffix
+/// may be left out and castling may be written with zeros, as some PGN
+/// writers do.
+pub fn san_to_move(game_data: &GameData, san: &str) -> Option<Move> {
+    let wanted = bare_san(san);
+    generate_moves_detailed(game_data)
+        .into_iter()
+        .find(|mv| bare_san(&move_to_san(game_data, mv)) == wanted)
+}
+
+#[test]
+fn san_pieces_captures_and_checks() {
+    let game_data =
+        crate::fen::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - 2 3")
+            .unwrap();
+    let mv = crate::chess::parse_uci_move("f3f7").unwrap();
+    assert_eq!(move_to_san(&game_data, &mv), "Qxf7#");
+    let mv = crate::chess::parse_uci_move("c4f7").unwrap();
+    assert_eq!(move_to_san(&game_data, &mv), "Bxf7+");
+    let mv = crate::chess::parse_uci_move("g1e2").unwrap();
+    assert_eq!(move_to_san(&game_data, &mv), "Ne2");
+    let mv = crate::chess::parse_uci_move("d2d4").unwrap();
+    assert_eq!(move_to_san(&game_data, &mv), "d4");
+}
+
+#[test]
+fn san_disambiguation_castling_and_promotion() {
+    let game_data = crate::fen::from_fen("4k3/1P6/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+    let mv = crate::chess::parse_uci_move("e1g1").unwrap();
+    assert_eq!(move_to_san(&game_data, &mv), "O-O");
+    let mv = crate::chess::parse_uci_move("e1c1").unwrap();
+    assert_eq!(move_to_san(&game_data, &mv), "O-O-O");
+    let mv = crate::chess::parse_uci_move("a1d1").unwrap();
+    assert_eq!(move_to_san(&game_data, &mv), "Rd1");
+    let mv = crate::chess::parse_uci_move("b7b8q").unwrap();
+    assert_eq!(move_to_san(&game_data, &mv), "b8=Q+");
+
+    let game_data = crate::fen::from_fen("4k3/8/8/8/8/8/4K3/R6R w - - 0 1").unwrap();
+    let mv = crate::chess::parse_uci_move("a1d1").unwrap();
+    assert_eq!(move_to_san(&game_data, &mv), "Rad1");
+
+    let game_data = crate::fen::from_fen("4k3/8/8/8/R7/8/8/R3K3 w - - 0 1").unwrap();
+    let mv = crate::chess::parse_uci_move("a1a2").unwrap();
+    assert_eq!(move_to_san(&game_data, &mv), "R1a2");
+}
+
+#[test]
+fn san_to_move_reads_what_move_to_san_writes() {
+    let game_data = crate::fen::from_fen("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    for mv in crate::chess::generate_moves_detailed(&game_data) {
+        let san = move_to_san(&game_data, &mv);
+        assert_eq!(san_to_move(&game_data, &san), Some(mv), "{san}");
+    }
+    let castle = crate::chess::parse_uci_move("e1c1").unwrap();
+    assert_eq!(san_to_move(&game_data, "0-0-0"), Some(castle));
+    let promotion = crate::chess::parse_uci_move("b7a8n").unwrap();
+    assert_eq!(san_to_move(&game_data, "bxa8=N!?"), Some(promotion));
+    assert_eq!(san_to_move(&game_data, "Nf3"), None);
+}
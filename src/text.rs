@@ -0,0 +1,93 @@
+//! Playing on the terminal: the board printed as text after every move and
+//! the moves read from a line each, in long algebraic notation or SAN.
+use crate::chess::{GameData, Move};
+use crate::game::{Game, GameResult};
+use crate::pgn::result_token;
+use crate::san::san_to_move;
+use crate::uci::parse_move;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "moves as e2e4, e7e8q or SAN such as Nf3 and O-O; \
+                    `resign` gives up, `quit` leaves the game unfinished";
+
+/// A legal move from `input`, long algebraic first, then SAN.
+fn read_move(game_data: &GameData, input: &str) -> Option<Move> {
+    parse_move(game_data, input).or_else(|| san_to_move(game_data, input))
+}
+
+/// Plays a game from `start` with both sides' moves read from `input` until
+/// it ends, the input runs out or a player quits. Lines that aren't legal
+/// moves are answered and asked again.
+pub fn run_with(
+    start: GameData,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<GameResult> {
+    let mut game = Game::from_position(start);
+    let mut lines = input.lines();
+    let mut show_board = true;
+    loop {
+        if show_board {
+            write!(output, "{}", game.position().to_ascii())?;
+            show_board = false;
+        }
+        let result = game.result();
+        if result != GameResult::Ongoing {
+            writeln!(output, "{} {result:?}", result_token(result))?;
+            return Ok(result);
+        }
+        let side = game.position().to_move;
+        write!(output, "{side:?} to move: ")?;
+        output.flush()?;
+        let Some(line) = lines.next() else {
+            writeln!(output)?;
+            return Ok(result);
+        };
+        let line = line?;
+        match line.trim() {
+            "" => {}
+            "quit" => return Ok(result),
+            "resign" => game.resign(side),
+            "help" => writeln!(output, "{HELP}")?,
+            token => match read_move(game.position(), token) {
+                Some(mv) => {
+                    game.play(mv);
+                    game.claim_draw();
+                    writeln!(output, "{}", game.move_list().last().unwrap())?;
+                    show_board = true;
+                }
+                None => writeln!(output, "illegal move `{token}`; type `help` for help")?,
+            },
+        }
+    }
+}
+
+pub fn run(start: GameData) -> io::Result<GameResult> {
+    run_with(start, io::stdin().lock(), io::stdout())
+}
+
+#[test]
+fn text_game_reprompts_and_ends_in_mate() {
+    let script = "f2f3\ne5\nKe2e4\n\ng2g4\nQh4#\ne2e4\n";
+    let mut output = Vec::new();
+    let result = run_with(GameData::default(), script.as_bytes(), &mut output).unwrap();
+    assert_eq!(result, GameResult::BlackWins);
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("illegal move `Ke2e4`"));
+    assert!(output.contains("2. g4 Qh4#"));
+    assert!(output.trim_end().ends_with("0-1 BlackWins"));
+    // the board is shown once to start and once after each of the 4 moves
+    assert_eq!(output.matches("a b c d e f g h").count(), 5);
+}
+
+#[test]
+fn text_game_stops_on_resignation_or_end_of_input() {
+    let mut output = Vec::new();
+    let result = run_with(GameData::default(), "e4\nresign\n".as_bytes(), &mut output).unwrap();
+    assert_eq!(result, GameResult::WhiteWins);
+
+    let result = run_with(GameData::default(), "e4\n".as_bytes(), io::sink()).unwrap();
+    assert_eq!(result, GameResult::Ongoing);
+    let result = run_with(GameData::default(), "quit\ne4\n".as_bytes(), io::sink()).unwrap();
+    assert_eq!(result, GameResult::Ongoing);
+}
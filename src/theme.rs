@@ -0,0 +1,162 @@
+use std::error::Error;
+
+pub const DEFAULT_THEME_PATH: &str = "./resources/themes/default.toml";
+
+/// An RGB color with components in `0.0..=1.0`, as the shaders take it.
+pub type Color = [f32; 3];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThemeError {
+    InvalidLine(usize),
+    UnknownKey(usize, String),
+    InvalidColor(usize, String),
+}
+
+impl std::fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThemeError::InvalidLine(line) => write!(f, "line {line}: expected key = \"#rrggbb\""),
+            ThemeError::UnknownKey(line, key) => write!(f, "line {line}: unknown key '{key}'"),
+            ThemeError::InvalidColor(line, color) => {
+                write!(f, "line {line}: invalid color '{color}'")
+            }
+        }
+    }
+}
+
+impl Error for ThemeError {}
+
+/// Board colors. The file format is the flat subset of TOML the theme needs,
+/// one `key = "#rrggbb"` per line with `#` comments; keys left out keep their
+/// default:
+///
+/// ```text
+/// light_square = "#faf5e3"
+/// dark_square = "#ff6161"
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub light_square: Color,
+    pub dark_square: Color,
+    /// Overlay on the selected piece's square.
+    pub highlight: Color,
+    /// Overlay on the squares the last move left and reached.
+    pub last_move: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            light_square: [0.98, 0.96, 0.89],
+            dark_square: [1.0, 0.38, 0.38],
+            highlight: [0.4, 0.7, 1.0],
+            last_move: [1.0, 0.85, 0.3],
+        }
+    }
+}
+
+impl Theme {
+    pub fn parse(text: &str) -> Result<Self, ThemeError> {
+        let mut theme = Theme::default();
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(ThemeError::InvalidLine(line_number))?;
+            let value = value.trim();
+            // a trailing comment starts after the closing quote
+            let value = match value.strip_prefix('"').and_then(|v| v.split_once('"')) {
+                Some((color, rest)) if rest.trim().is_empty() || rest.trim().starts_with('#') => {
+                    color
+                }
+                _ => return Err(ThemeError::InvalidLine(line_number)),
+            };
+            let slot = match key.trim() {
+                "light_square" => &mut theme.light_square,
+                "dark_square" => &mut theme.dark_square,
+                "highlight" => &mut theme.highlight,
+                "last_move" => &mut theme.last_move,
+                key => return Err(ThemeError::UnknownKey(line_number, key.to_string())),
+            };
+            *slot = parse_color(value)
+                .ok_or_else(|| ThemeError::InvalidColor(line_number, value.to_string()))?;
+        }
+        Ok(theme)
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Self::parse(&text)?)
+    }
+}
+
+/// Parses `#rrggbb`.
+fn parse_color(text: &str) -> Option<Color> {
+    let hex = text.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16)
+            .ok()
+            .map(|c| c as f32 / 255.0)
+    };
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+#[test]
+fn theme_parses_sample_file() {
+    let theme = Theme::parse(
+        "# greens\n\
+         light_square = \"#eeeed2\"\n\
+         dark_square = \"#769656\" # classic\n\
+         \n\
+         highlight = \"#FFFF00\"\n",
+    )
+    .unwrap();
+    assert_eq!(
+        theme.light_square,
+        [238.0 / 255.0, 238.0 / 255.0, 210.0 / 255.0]
+    );
+    assert_eq!(
+        theme.dark_square,
+        [118.0 / 255.0, 150.0 / 255.0, 86.0 / 255.0]
+    );
+    assert_eq!(theme.highlight, [1.0, 1.0, 0.0]);
+    assert_eq!(theme.last_move, Theme::default().last_move);
+
+    assert_eq!(
+        Theme::parse("light_square = \"#12345\""),
+        Err(ThemeError::InvalidColor(1, "#12345".to_string()))
+    );
+    assert_eq!(
+        Theme::parse("\nborder = \"#000000\""),
+        Err(ThemeError::UnknownKey(2, "border".to_string()))
+    );
+    assert_eq!(
+        Theme::parse("light_square #000000"),
+        Err(ThemeError::InvalidLine(1))
+    );
+}
+
+#[test]
+fn bundled_theme_matches_defaults() {
+    let bundled = Theme::load(DEFAULT_THEME_PATH).unwrap();
+    let default = Theme::default();
+    let pairs = [
+        (bundled.light_square, default.light_square),
+        (bundled.dark_square, default.dark_square),
+        (bundled.highlight, default.highlight),
+        (bundled.last_move, default.last_move),
+    ];
+    for (loaded, expected) in pairs {
+        for (a, b) in loaded.into_iter().zip(expected) {
+            // within the precision of a hex channel
+            assert!((a - b).abs() <= 1.0 / 255.0, "{loaded:?} vs {expected:?}");
+        }
+    }
+}
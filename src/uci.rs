@@ -0,0 +1,295 @@
+use crate::chess::{
+    apply_move, is_legal_move, move_to_uci, parse_uci_move, promotion_fits, GameData, Move,
+    PieceType,
+};
+use crate::engine::{evaluate_explain, extract_pv, Search, MAX_DEPTH};
+use crate::fen::from_fen_checked;
+use crate::opening::{OpeningBook, DEFAULT_BOOK_PATH};
+use std::io::{self, BufRead, Write};
+use std::time::{Duration, Instant};
+
+const DEFAULT_DEPTH: u32 = 3;
+/// The range the `Contempt` option accepts, in centipawns.
+const CONTEMPT_LIMIT: i32 = 1000;
+
+/// Resolves a long algebraic move against the legal moves of the position. A
+/// promotion suffix is only accepted on a pawn reaching the last rank.
+pub fn parse_move(game_data: &GameData, token: &str) -> Option<Move> {
+    let parsed = parse_uci_move(token)?;
+    if !is_legal_move(game_data, parsed.from, parsed.to) || !promotion_fits(game_data, &parsed) {
+        return None;
+    }
+    let promotion = match game_data.board.get(&parsed.from) {
+        Some(&PieceType::Pawn(color)) if parsed.to.y == 0 || parsed.to.y == 7 => {
+            parsed.promotion.or(Some(PieceType::Queen(color)))
+        }
+        _ => parsed.promotion,
+    };
+    Some(Move {
+        promotion,
+        ..parsed
+    })
+}
+
+/// The position described by a `position` command, together with the
+/// positions that preceded it through the `moves` list.
+fn parse_position<'a>(
+    mut tokens: impl Iterator<Item = &'a str>,
+) -> Option<(GameData, Vec<GameData>)> {
+    let game_data = match tokens.next()? {
+        "startpos" => {
+            if tokens.next() != Some("moves") {
+                return Some((GameData::default(), Vec::new()));
+            }
+            GameData::default()
+        }
+        "fen" => {
+            let fen: Vec<&str> = tokens.by_ref().take_while(|&t| t != "moves").collect();
+            from_fen_checked(&fen.join(" ")).ok()?
+        }
+        _ => return None,
+    };
+    apply_moves(game_data, tokens)
+}
+
+fn apply_moves<'a>(
+    mut game_data: GameData,
+    tokens: impl Iterator<Item = &'a str>,
+) -> Option<(GameData, Vec<GameData>)> {
+    let mut history = Vec::new();
+    for token in tokens {
+        let mv = parse_move(&game_data, token)?;
+        let next = apply_move(&game_data, &mv);
+        history.push(std::mem::replace(&mut game_data, next));
+    }
+    Some((game_data, history))
+}
+
+/// Engine settings changed through `setoption`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Options {
+    contempt: i32,
+}
+
+/// Applies a `setoption name <id> value <x>` command, returning false for an
+/// unknown option or an unusable value.
+fn set_option(options: &mut Options, tokens: std::str::SplitWhitespace) -> bool {
+    let tokens: Vec<&str> = tokens.collect();
+    let Some(value_at) = tokens.iter().position(|&token| token == "value") else {
+        return false;
+    };
+    if tokens.first() != Some(&"name") {
+        return false;
+    }
+    let name = tokens[1..value_at].join(" ");
+    let value = tokens[value_at + 1..].join(" ");
+    match name.to_ascii_lowercase().as_str() {
+        "contempt" => match value.parse::<i32>() {
+            Ok(contempt) if contempt.abs() <= CONTEMPT_LIMIT => options.contempt = contempt,
+            _ => return false,
+        },
+        _ => return false,
+    }
+    true
+}
+
+fn go(
+    game_data: &GameData,
+    history: &[GameData],
+    book: Option<&OpeningBook>,
+    options: Options,
+    mut tokens: std::str::SplitWhitespace,
+) -> Option<(Move, Vec<Move>)> {
+    if let Some(mv) = book.and_then(|book| book.book_move(game_data)) {
+        return Some((mv, Vec::new()));
+    }
+    let mut depth = None;
+    let mut movetime = None;
+    while let Some(token) = tokens.next() {
+        match token {
+            "depth" => depth = tokens.next().and_then(|d| d.parse::<u32>().ok()),
+            "movetime" => movetime = tokens.next().and_then(|m| m.parse::<u64>().ok()),
+            _ => {}
+        }
+    }
+    let (deadline, depth) = match (depth, movetime) {
+        (None, Some(movetime)) => (
+            Some(Instant::now() + Duration::from_millis(movetime)),
+            MAX_DEPTH,
+        ),
+        (depth, _) => (None, depth.unwrap_or(DEFAULT_DEPTH).max(1)),
+    };
+    let mut search = Search::new(deadline)
+        .with_history(history)
+        .with_contempt(options.contempt);
+    let result = match deadline {
+        Some(_) => search.deepen(game_data, depth),
+        None => search.search_root(game_data, depth),
+    };
+    let (mv, _) = result?;
+    Some((mv, extract_pv(game_data, &search.tt, depth as usize)))
+}
+
+/// Runs the UCI loop until `quit` or end of input, answering from `book` while
+/// the game is still in it. The search is synchronous, so `stop` has nothing
+/// to interrupt and is accepted as a no-op. The one option is `Contempt`, see
+/// `Search::contempt`. Outside the protocol, `eval` prints the static
+/// evaluation of the current position term by term.
+pub fn run_with(
+    input: impl BufRead,
+    mut output: impl Write,
+    book: Option<&OpeningBook>,
+) -> io::Result<()> {
+    let mut game_data = GameData::default();
+    let mut history = Vec::new();
+    let mut options = Options::default();
+    for line in input.lines() {
+        let line = line?;
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("uci") => {
+                writeln!(output, "id name rust_chess")?;
+                writeln!(output, "id author t1h0n")?;
+                writeln!(
+                    output,
+                    "option name Contempt type spin default 0 min {} max {CONTEMPT_LIMIT}",
+                    -CONTEMPT_LIMIT
+                )?;
+                writeln!(output, "uciok")?;
+            }
+            Some("isready") => writeln!(output, "readyok")?,
+            Some("ucinewgame") => {
+                game_data = GameData::default();
+                history.clear();
+            }
+            Some("position") => match parse_position(tokens) {
+                Some((new_game_data, new_history)) => {
+                    game_data = new_game_data;
+                    history = new_history;
+                }
+                None => writeln!(output, "info string invalid position: {line}")?,
+            },
+            Some("setoption") if !set_option(&mut options, tokens.clone()) => {
+                writeln!(output, "info string unsupported option: {line}")?;
+            }
+            Some("go") => match go(&game_data, &history, book, options, tokens) {
+                Some((mv, pv)) => {
+                    if !pv.is_empty() {
+                        let pv: Vec<String> = pv.iter().map(move_to_uci).collect();
+                        writeln!(output, "info pv {}", pv.join(" "))?;
+                    }
+                    writeln!(output, "bestmove {}", move_to_uci(&mv))?;
+                }
+                None => writeln!(output, "bestmove 0000")?,
+            },
+            Some("eval") => writeln!(output, "{}", evaluate_explain(&game_data))?,
+            Some("quit") => break,
+            _ => {}
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
+
+pub fn run() -> io::Result<()> {
+    let book = OpeningBook::load(DEFAULT_BOOK_PATH).ok();
+    run_with(io::stdin().lock(), io::stdout(), book.as_ref())
+}
+
+#[test]
+fn uci_scripted_session_returns_legal_bestmove() {
+    let script = "uci\nisready\nucinewgame\nposition startpos moves e2e4 e7e5\ngo depth 2\nquit\n";
+    let mut output = Vec::new();
+    run_with(script.as_bytes(), &mut output, None).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("uciok"));
+    assert!(output.contains("readyok"));
+
+    let bestmove = output
+        .lines()
+        .find_map(|line| line.strip_prefix("bestmove "))
+        .unwrap();
+    let (position, history) =
+        parse_position("startpos moves e2e4 e7e5".split_whitespace()).unwrap();
+    assert_eq!(history.len(), 2);
+    assert!(parse_move(&position, bestmove).is_some());
+    let pv = output
+        .lines()
+        .find_map(|line| line.strip_prefix("info pv "))
+        .unwrap();
+    assert!(pv.starts_with(bestmove));
+    assert!(pv.split(' ').count() <= 2);
+}
+
+#[test]
+fn uci_position_fen_with_moves() {
+    let (position, _) =
+        parse_position("fen 4k3/P7/8/8/8/8/8/4K3 w - - 0 1 moves a7a8n".split_whitespace())
+            .unwrap();
+    assert_eq!(
+        position.board.get(&crate::chess::Position { x: 0, y: 7 }),
+        Some(&crate::chess::PieceType::Knight(
+            crate::chess::PieceColor::White
+        ))
+    );
+}
+
+#[test]
+fn uci_rejects_promotion_suffix_on_non_promoting_move() {
+    let position = from_fen_checked("4k3/P7/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+    assert!(parse_move(&position, "a1a6q").is_none());
+    assert_eq!(parse_move(&position, "a1a6").unwrap().promotion, None);
+    assert_eq!(
+        parse_move(&position, "a7a8r").unwrap().promotion,
+        Some(PieceType::Rook(crate::chess::PieceColor::White))
+    );
+}
+
+#[test]
+fn uci_answers_from_book() {
+    let book =
+        OpeningBook::parse("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 c7c5").unwrap();
+    let script = "position startpos moves e2e4\ngo depth 4\n";
+    let mut output = Vec::new();
+    run_with(script.as_bytes(), &mut output, Some(&book)).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "bestmove c7c5\n");
+}
+
+#[test]
+fn uci_setoption_sets_contempt() {
+    let mut options = Options::default();
+    assert!(set_option(
+        &mut options,
+        "name Contempt value 50".split_whitespace()
+    ));
+    assert_eq!(options.contempt, 50);
+    assert!(set_option(
+        &mut options,
+        "name contempt value -20".split_whitespace()
+    ));
+    assert_eq!(options.contempt, -20);
+    for bad in [
+        "name Contempt value 5000",
+        "name Contempt value x",
+        "name Hash value 16",
+    ] {
+        assert!(!set_option(&mut options, bad.split_whitespace()), "{bad}");
+    }
+    assert_eq!(options.contempt, -20);
+
+    let mut output = Vec::new();
+    let script = "uci\nsetoption name Contempt value 30\nsetoption name Ponder value true\n";
+    run_with(script.as_bytes(), &mut output, None).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("option name Contempt type spin"));
+    assert!(output.ends_with("info string unsupported option: setoption name Ponder value true\n"));
+}
+
+#[test]
+fn uci_eval_prints_breakdown() {
+    let mut output = Vec::new();
+    run_with("position startpos\neval\n".as_bytes(), &mut output, None).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("material"));
+    assert!(output.trim_end().ends_with("total              0"));
+}
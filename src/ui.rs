@@ -1,73 +1,545 @@
-use crate::chess::{generate_moves, postprocess_move, GameData, PieceColor, PieceType, Position};
-use crate::graphics::{Drawable, Rect, Shader, ShaderProgram, Sprite, Texture2D};
+use crate::chess::{check_info, Board, GameData, Move, PieceColor, PieceType, Position};
+use crate::editor::{Editor, PALETTE};
+use crate::engine::{captured_pieces, hanging_pieces};
+use crate::font::BitmapFont;
+use crate::game::{timeout_result, Clock, Game, GameResult};
+use crate::graphics::{
+    load_image, Drawable, GraphicsInitError, Overlay, Rect, Shader, ShaderProgram, Sprite,
+    Texture2D, TextureFilter,
+};
+use crate::opening::{classify_opening, EcoEntry};
+use crate::theme::{Color, Theme, DEFAULT_THEME_PATH};
+use crate::worker::{EngineWorker, SearchRequest};
 use nalgebra_glm as glm;
-use sdl2::{self, event::Event, mouse::MouseButton};
-use std::collections::HashMap;
+use sdl2::{
+    self,
+    event::{Event, WindowEvent},
+    keyboard::Keycode,
+    mouse::MouseButton,
+    video::SwapInterval,
+};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::time::{Duration, Instant};
 
-const FPS: u64 = 60;
-const FRAME_DURATION: Duration = Duration::from_millis(1000 / FPS);
+const WINDOW_TITLE: &str = "Chess2D";
+const DEFAULT_FPS: u32 = 60;
+const INITIAL_TIME: Duration = Duration::from_secs(5 * 60);
+const INCREMENT: Duration = Duration::from_secs(3);
+/// Search depth the engine starts at; `D` cycles through 1 to `MAX_ENGINE_DEPTH`.
+const ENGINE_DEPTH: u32 = 3;
+const MAX_ENGINE_DEPTH: u32 = 6;
+/// The most the engine thinks about one move, whatever the depth.
+const ENGINE_MOVE_TIME: Duration = Duration::from_secs(2);
+const BOARD_SIZE_PX: u32 = 768;
+const PANEL_WIDTH_PX: u32 = 256;
+/// Everything is laid out in these units and scaled to the window.
+const SCENE_WIDTH_PX: u32 = BOARD_SIZE_PX + PANEL_WIDTH_PX;
+const SQUARE_PX: f32 = BOARD_SIZE_PX as f32 / 8.0;
+/// The promotion picker is a column of half-size pieces on the a-file, its top
+/// three squares down from the top edge.
+const PROMOTION_CELL_PX: f32 = SQUARE_PX / 2.0;
+const PROMOTION_TOP_PX: f32 = 3.0 * SQUARE_PX;
+/// Size of one piece in the spritesheet.
+const SPRITE_CELL_PX: f32 = 480.0;
+const PANEL_MARGIN_PX: f32 = 12.0;
+const TEXT_SCALE: f32 = 2.0;
+/// Slide pieces to their destination instead of snapping them there.
+const ANIMATE_MOVES: bool = true;
+const ANIMATION_DURATION: Duration = Duration::from_millis(150);
+/// How long a replay shows each position unless told otherwise.
+pub const REPLAY_INTERVAL: Duration = Duration::from_secs(1);
+const ANNOTATION_COLOR: Color = [0.2, 0.65, 0.3];
+const CHECK_COLOR: Color = [0.9, 0.1, 0.1];
+const THREAT_COLOR: Color = [0.95, 0.55, 0.1];
+const ARROW_WIDTH_PX: f32 = 16.0;
+const ARROW_HEAD_PX: f32 = 36.0;
+const PALETTE_CELL_PX: f32 = 40.0;
+const CAPTURED_PX: f32 = 24.0;
+// captured pieces overlap so a full row of fifteen still fits the panel
+const CAPTURED_STEP_PX: f32 = 15.0;
 
-pub fn run() {
-    let sdl = sdl2::init().unwrap();
-    let video_subsystem = sdl.video().unwrap();
+/// A piece sliding from one square to another; the board already has it on
+/// `to`, so that copy is hidden until the slide is over.
+struct Slide {
+    piece: PieceType,
+    to: Position,
+    from_px: glm::Vec2,
+    to_px: glm::Vec2,
+    /// Degrees the piece turns on the way.
+    spin: f32,
+}
+
+impl Slide {
+    fn new(piece: PieceType, from: Position, to: Position) -> Self {
+        Self {
+            piece,
+            to,
+            from_px: square_rect(from, false, SQUARE_PX).xy(),
+            to_px: square_rect(to, false, SQUARE_PX).xy(),
+            spin: 0.0,
+        }
+    }
+}
+
+/// The slides of one move: the rook comes along when castling, and a
+/// promoting pawn spins once on its way and only turns into its new piece
+/// when it has arrived.
+struct AnimatingMove {
+    slides: Vec<Slide>,
+    start: Instant,
+}
+
+impl AnimatingMove {
+    /// `before` is the position `mv` was played in.
+    fn new(before: &GameData, mv: &Move) -> Option<Self> {
+        if !ANIMATE_MOVES {
+            return None;
+        }
+        let piece = *before.board.get(&mv.from)?;
+        let mut slides = vec![Slide::new(piece, mv.from, mv.to)];
+        if mv.promotion.is_some() {
+            slides[0].spin = 360.0;
+        }
+        if matches!(piece, PieceType::King(_)) && (mv.from.x - mv.to.x).abs() == 2 {
+            let (rook_x, rook_to_x) = if mv.to.x == 6 { (7, 5) } else { (0, 3) };
+            let on_rank = |x: i8| Position { x, ..mv.from };
+            if let Some(&rook) = before.board.get(&on_rank(rook_x)) {
+                slides.push(Slide::new(rook, on_rank(rook_x), on_rank(rook_to_x)));
+            }
+        }
+        Some(Self {
+            slides,
+            start: Instant::now(),
+        })
+    }
+    /// How far along the slide is, from 0 to 1.
+    fn progress(&self) -> f32 {
+        (self.start.elapsed().as_secs_f32() / ANIMATION_DURATION.as_secs_f32()).min(1.0)
+    }
+}
+
+/// How the render loop paces its frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderSettings {
+    /// The frame rate the loop sleeps down to when vsync is off.
+    pub fps: u32,
+    /// Let the buffer swap wait for the display instead of sleeping.
+    pub vsync: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            fps: DEFAULT_FPS,
+            vsync: false,
+        }
+    }
+}
+
+impl RenderSettings {
+    fn frame_duration(&self) -> Duration {
+        Duration::from_secs(1) / self.fps.max(1)
+    }
+}
+
+/// Spectator mode: a loaded game steps forward by itself, one move every
+/// `interval` of frame time, until it is paused or reaches the end.
+struct Replay {
+    interval: Duration,
+    paused: bool,
+    // frame time since the last step
+    waited: Duration,
+}
+
+impl Replay {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            paused: false,
+            waited: Duration::ZERO,
+        }
+    }
+    /// Counts `elapsed` towards the next step; true when it is due.
+    fn tick(&mut self, elapsed: Duration) -> bool {
+        if self.paused {
+            return false;
+        }
+        self.waited += elapsed;
+        if self.waited < self.interval {
+            return false;
+        }
+        self.waited = Duration::ZERO;
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Arrow {
+    from: Position,
+    to: Position,
+}
+
+/// Arrows and highlighted squares drawn with the right mouse button: a
+/// right-click marks a square, a right-drag draws an arrow, and repeating
+/// either takes it away again.
+#[derive(Default)]
+struct Annotations {
+    arrows: Vec<Arrow>,
+    highlights: HashSet<Position>,
+}
+
+impl Annotations {
+    fn toggle(&mut self, from: Position, to: Position) {
+        if from == to {
+            if !self.highlights.remove(&from) {
+                self.highlights.insert(from);
+            }
+            return;
+        }
+        let arrow = Arrow { from, to };
+        match self.arrows.iter().position(|&a| a == arrow) {
+            Some(i) => {
+                self.arrows.remove(i);
+            }
+            None => self.arrows.push(arrow),
+        }
+    }
+    fn clear(&mut self) {
+        self.arrows.clear();
+        self.highlights.clear();
+    }
+    fn mark(&self, overlay: &mut Overlay) {
+        for &square in &self.highlights {
+            mark_square(overlay, square, ANNOTATION_COLOR, 0.5);
+        }
+    }
+    fn draw_arrows(&self, board_program: Rc<ShaderProgram>, projection: &glm::Mat4) {
+        for arrow in &self.arrows {
+            draw_arrow(arrow, ANNOTATION_COLOR, board_program.clone(), projection);
+        }
+    }
+}
+
+/// An arrow between square centers: a shaft and two barbs, each a rotated
+/// rectangle.
+fn draw_arrow(
+    arrow: &Arrow,
+    color: Color,
+    board_program: Rc<ShaderProgram>,
+    projection: &glm::Mat4,
+) {
+    let from = square_center(arrow.from, false, SQUARE_PX);
+    let to = square_center(arrow.to, false, SQUARE_PX);
+    let angle = (to.y - from.y).atan2(to.x - from.x).to_degrees();
+    let bar = |center: glm::Vec2, length: f32, angle: f32| {
+        let mut rect = tinted_rect(
+            glm::vec4(
+                center.x - length / 2.0,
+                center.y - ARROW_WIDTH_PX / 2.0,
+                length,
+                ARROW_WIDTH_PX,
+            ),
+            color,
+            0.8,
+            board_program.clone(),
+        );
+        rect.angle = angle;
+        rect.draw(projection);
+    };
+    bar((from + to) / 2.0, glm::distance(&from, &to), angle);
+    for barb in [angle + 150.0, angle - 150.0] {
+        let direction = glm::vec2(barb.to_radians().cos(), barb.to_radians().sin());
+        bar(to + direction * ARROW_HEAD_PX / 2.0, ARROW_HEAD_PX, barb);
+    }
+}
+
+/// Where the scene lands in the window: scaled to fit without stretching the
+/// board and centered in the room left over. In window pixels, with `top`
+/// counted down from the top edge like SDL's mouse positions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Viewport {
+    left: i32,
+    top: i32,
+    width: i32,
+    height: i32,
+    window_height: i32,
+}
+
+impl Viewport {
+    fn fit(window_width: u32, window_height: u32) -> Self {
+        let scale = (window_width as f32 / SCENE_WIDTH_PX as f32)
+            .min(window_height as f32 / BOARD_SIZE_PX as f32);
+        let width = (SCENE_WIDTH_PX as f32 * scale).round() as i32;
+        let height = (BOARD_SIZE_PX as f32 * scale).round() as i32;
+        Self {
+            left: (window_width as i32 - width) / 2,
+            top: (window_height as i32 - height) / 2,
+            width,
+            height,
+            window_height: window_height as i32,
+        }
+    }
+    /// Window pixels per scene pixel.
+    fn scale(&self) -> f32 {
+        self.height as f32 / BOARD_SIZE_PX as f32
+    }
+    /// The size of a square on screen.
+    fn square_size(&self) -> f32 {
+        SQUARE_PX * self.scale()
+    }
+    /// The lower left corner in window pixels, as `gl_FragCoord` counts them.
+    fn origin(&self) -> glm::Vec2 {
+        glm::vec2(
+            self.left as f32,
+            (self.window_height - self.top - self.height) as f32,
+        )
+    }
+    fn apply(&self) {
+        let origin = self.origin();
+        unsafe {
+            gl::Viewport(origin.x as i32, origin.y as i32, self.width, self.height);
+        }
+    }
+    /// A mouse position in scene pixels, still counted from the top; `None`
+    /// in the margins around the scene.
+    fn scene_position(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        let x = ((x - self.left) as f32 / self.scale()).floor() as i32;
+        let y = ((y - self.top) as f32 / self.scale()).floor() as i32;
+        ((0..SCENE_WIDTH_PX as i32).contains(&x) && (0..BOARD_SIZE_PX as i32).contains(&y))
+            .then_some((x, y))
+    }
+    /// The window pixel showing a point of the scene given like
+    /// `scene_position` returns them.
+    #[cfg(test)]
+    fn window_position(&self, x: f32, y: f32) -> (i32, i32) {
+        (
+            self.left + (x * self.scale()).floor() as i32,
+            self.top + (y * self.scale()).floor() as i32,
+        )
+    }
+}
+
+/// Where `pos` is shown on a board of `square_px` squares: the lower left
+/// corner, with y counted up as GL does, then the width and height. Anything
+/// drawn through [`board_view`] is turned by it and passes `flipped: false`.
+fn square_rect(pos: Position, flipped: bool, square_px: f32) -> glm::Vec4 {
+    let pos = oriented(pos, flipped);
+    glm::vec4(
+        pos.x as f32 * square_px,
+        pos.y as f32 * square_px,
+        square_px,
+        square_px,
+    )
+}
+
+/// The middle of `pos` as [`square_rect`] places it.
+fn square_center(pos: Position, flipped: bool, square_px: f32) -> glm::Vec2 {
+    let rect = square_rect(pos, flipped, square_px);
+    glm::vec2(rect.x + rect.z / 2.0, rect.y + rect.w / 2.0)
+}
+
+/// Turns the board half a turn about its center when `flipped`, so Black
+/// plays up the screen. Pieces drawn through it are turned back upright with
+/// [`piece_angle`].
+fn board_view(flipped: bool) -> glm::Mat4 {
+    if !flipped {
+        return glm::identity();
+    }
+    let center = glm::vec3(BOARD_SIZE_PX as f32 / 2.0, BOARD_SIZE_PX as f32 / 2.0, 0.0);
+    let view = glm::translation(&center);
+    let view = glm::rotate(&view, std::f32::consts::PI, &glm::vec3(0.0, 0.0, 1.0));
+    glm::translate(&view, &-center)
+}
+
+/// The angle that keeps pieces upright under [`board_view`].
+fn piece_angle(flipped: bool) -> f32 {
+    if flipped {
+        180.0
+    } else {
+        0.0
+    }
+}
+
+/// The square shown at `pos` of the screen when the board may be flipped.
+fn oriented(pos: Position, flipped: bool) -> Position {
+    if !flipped {
+        return pos;
+    }
+    Position {
+        x: 7 - pos.x,
+        y: 7 - pos.y,
+    }
+}
+
+/// The square under a point of the board, in scene pixels counted from the
+/// top like mouse positions.
+fn square_at(x: i32, y: i32) -> Position {
+    Position {
+        x: (x as f32 / SQUARE_PX) as i8,
+        y: 7 - (y as f32 / SQUARE_PX) as i8,
+    }
+}
+
+/// Where a dragged piece is drawn so the cursor holds it by its middle.
+fn drag_px(x: i32, y: i32) -> glm::Vec2 {
+    glm::vec2(
+        x as f32 - SQUARE_PX / 2.0,
+        BOARD_SIZE_PX as f32 - y as f32 - SQUARE_PX / 2.0,
+    )
+}
+
+/// The pieces offered for a promotion, top to bottom.
+fn promotion_choices(color: PieceColor) -> [PieceType; 4] {
+    [
+        PieceType::Queen(color),
+        PieceType::Rook(color),
+        PieceType::Knight(color),
+        PieceType::Bishop(color),
+    ]
+}
+
+fn promotion_rect(index: usize) -> glm::Vec4 {
+    let top = PROMOTION_TOP_PX + index as f32 * PROMOTION_CELL_PX;
+    glm::vec4(
+        0.0,
+        BOARD_SIZE_PX as f32 - top - PROMOTION_CELL_PX,
+        PROMOTION_CELL_PX,
+        PROMOTION_CELL_PX,
+    )
+}
+
+/// Which of `promotion_choices` a click in scene pixels picks.
+fn promotion_choice_at(x: i32, y: i32) -> Option<usize> {
+    if !(0.0..PROMOTION_CELL_PX).contains(&(x as f32)) {
+        return None;
+    }
+    let row = (y as f32 - PROMOTION_TOP_PX) / PROMOTION_CELL_PX;
+    (0.0..4.0).contains(&row).then_some(row as usize)
+}
+
+pub fn run(settings: RenderSettings) -> Result<(), GraphicsInitError> {
+    run_game(Game::default(), None, settings)
+}
+
+/// Opens `game` at its first position and plays it back, one move per
+/// `interval`; space pauses and resumes, the arrow keys step.
+pub fn replay(
+    game: Game,
+    interval: Duration,
+    settings: RenderSettings,
+) -> Result<(), GraphicsInitError> {
+    run_game(game, Some(Replay::new(interval)), settings)
+}
+
+fn run_game(
+    mut game: Game,
+    mut replay: Option<Replay>,
+    settings: RenderSettings,
+) -> Result<(), GraphicsInitError> {
+    let sdl = sdl2::init().map_err(GraphicsInitError::Sdl)?;
+    let video_subsystem = sdl.video().map_err(GraphicsInitError::Sdl)?;
     let gl_attr = video_subsystem.gl_attr();
 
     gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
     gl_attr.set_context_version(3, 3);
 
-    let window = video_subsystem
-        .window("Chess2D", 768, 768)
+    let mut window = video_subsystem
+        .window(WINDOW_TITLE, SCENE_WIDTH_PX, BOARD_SIZE_PX)
         .opengl()
+        .resizable()
         .build()
-        .unwrap();
-    let _gl_context = window.gl_create_context().unwrap();
+        .map_err(|e| GraphicsInitError::Sdl(e.to_string()))?;
+    let _gl_context = window.gl_create_context().map_err(GraphicsInitError::Sdl)?;
+    let vsync = settings.vsync
+        && match video_subsystem.gl_set_swap_interval(SwapInterval::VSync) {
+            Ok(()) => true,
+            Err(e) => {
+                println!("no vsync, capping at {} fps instead: {e}", settings.fps);
+                false
+            }
+        };
+    let frame_duration = settings.frame_duration();
     let _gl =
         gl::load_with(|s| video_subsystem.gl_get_proc_address(s) as *const std::os::raw::c_void);
-    let projection = &glm::ortho::<f32>(0.0, 764.0, 0.0, 764.0, -1.0, 1.0);
+    let projection = &glm::ortho::<f32>(
+        0.0,
+        SCENE_WIDTH_PX as f32,
+        0.0,
+        BOARD_SIZE_PX as f32,
+        -1.0,
+        1.0,
+    );
 
+    let viewport = Rc::new(Cell::new(Viewport::fit(window.size().0, window.size().1)));
+    viewport.get().apply();
     unsafe {
-        gl::Viewport(
-            0,
-            0,
-            window.size().0.try_into().unwrap(),
-            window.size().1.try_into().unwrap(),
-        );
         gl::ClearColor(0.3, 0.3, 0.5, 1.0);
         gl::Enable(gl::BLEND);
         gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
     }
-    let texture_pack = match stb_image::image::load("./resources/textures/spritesheet.png") {
-        stb_image::image::LoadResult::ImageU8(img) => Rc::new(img),
-        _ => panic!("unsupported image"),
-    };
-    let (board_program, piece_program) = init_shaders();
-    let texture = Rc::new(Texture2D::new(texture_pack.clone(), gl::RGBA));
+    let (board_program, piece_program, overlay_program, texture) = init_graphics()?;
+    let mut overlay = Overlay::new(overlay_program);
     let piece_texture_map = create_piece_texture_map();
     let mut board = Rect::new(
-        glm::vec4::<f32>(0.0, 0.0, window.size().0 as f32, window.size().1 as f32),
+        glm::vec4::<f32>(0.0, 0.0, BOARD_SIZE_PX as f32, BOARD_SIZE_PX as f32),
         board_program.clone(),
     );
-    board.uniform_setter = Some(Box::new(|shader: Rc<ShaderProgram>| {
+    let theme = Theme::load(DEFAULT_THEME_PATH).unwrap_or_else(|e| {
+        println!("using the default theme: {e}");
+        Theme::default()
+    });
+    let board_viewport = viewport.clone();
+    board.uniform_setter = Some(Box::new(move |shader: Rc<ShaderProgram>| {
+        let viewport = board_viewport.get();
         shader.set_uniform_bool("black_view", false);
-        shader.set_uniform_vec3f("white_color", glm::vec3(0.98, 0.96, 0.89));
-        shader.set_uniform_vec3f("black_color", glm::vec3(1.0, 0.38, 0.38));
+        shader.set_uniform_vec3f("white_color", glm::make_vec3(&theme.light_square));
+        shader.set_uniform_vec3f("black_color", glm::make_vec3(&theme.dark_square));
         shader.set_uniform_float("opacity", 1.0);
-        shader.set_uniform_int("side_size", 96);
+        shader.set_uniform_float("side_size", viewport.square_size());
+        shader.set_uniform_vec2f("origin", viewport.origin());
     }));
-    let mut game_data = GameData::default();
-    let mut valid_moves = generate_moves(&game_data);
+    let font = BitmapFont::new();
     let mut selected = None;
-    let mut to_be_promoted: Option<Position> = None;
+    let mut to_be_promoted: Option<Move> = None;
+    let mut animating: Option<AnimatingMove> = None;
+    let mut annotations = Annotations::default();
+    let mut annotation_start: Option<Position> = None;
+    // set while the position is being edited, the game is paused meanwhile
+    let mut editing: Option<Editor> = None;
+    // the ply on display while stepping through earlier positions; moves are
+    // only made from the live position
+    let mut reviewing: Option<usize> = None;
+    if replay.is_some() {
+        println!("replaying; space pauses and resumes, the arrow keys step");
+        reviewing = Some(0).filter(|&ply| ply < game.ply_count());
+    }
     let mut selected_pos = glm::vec2::<f32>(0.0, 0.0);
-    let mut event_pump = sdl.event_pump().unwrap();
+    let mut event_pump = sdl.event_pump().map_err(GraphicsInitError::Sdl)?;
     let mut last_frame_time = Instant::now();
+    let started = Instant::now();
+    let mut clock = Clock::new(INITIAL_TIME, INCREMENT);
+    // the side the computer plays, toggled with `P`
+    let mut engine_side: Option<PieceColor> = None;
+    let mut engine_depth = ENGINE_DEPTH;
+    let mut engine = EngineWorker::spawn();
+    // flash the side to move's hanging pieces, toggled with `T`
+    let mut show_threats = false;
+    // Black at the bottom, toggled with `F`
+    let mut flipped = false;
 
     'main: loop {
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. } => break 'main,
+                Event::Window {
+                    win_event: WindowEvent::Resized(width, height),
+                    ..
+                } => {
+                    viewport.set(Viewport::fit(width as u32, height as u32));
+                    viewport.get().apply();
+                }
                 Event::MouseButtonDown {
                     timestamp: _,
                     window_id: _,
@@ -77,66 +549,89 @@ pub fn run() {
                     x,
                     y,
                 } => {
-                    if to_be_promoted.is_some() {
-                        let pos = Position {
-                            x: (x / 48) as i8,
-                            y: (y / 48) as i8,
-                        };
-                        if pos.x != 0 || !(6..10).contains(&pos.y) {
+                    let Some((x, y)) = viewport.get().scene_position(x, y) else {
+                        continue;
+                    };
+                    if animating.is_some() {
+                        // the slide is over in a few frames, clicks meanwhile are dropped
+                        continue;
+                    }
+                    if let Some(editor) = &mut editing {
+                        if x >= BOARD_SIZE_PX as i32 {
+                            let at = glm::vec2(x as f32, (BOARD_SIZE_PX as i32 - y) as f32);
+                            if let Some(piece) = palette_piece_at(at) {
+                                editor.piece = piece;
+                            }
                             continue;
                         }
-                        game_data.board.remove(&to_be_promoted.unwrap());
-                        let opposite = game_data.to_move.get_opposite();
-                        game_data.board.insert(
-                            to_be_promoted.unwrap(),
-                            match pos.y {
-                                6 => PieceType::Queen(opposite),
-                                7 => PieceType::Rook(opposite),
-                                8 => PieceType::Knight(opposite),
-                                9 => PieceType::Bishop(opposite),
-                                _ => panic!("cant happen"),
-                            },
-                        );
-                        valid_moves = generate_moves(&game_data);
-                        if valid_moves.is_empty() {
-                            println!("the end; winner is {:?}", game_data.to_move.get_opposite());
-                            break 'main;
+                        let pos = oriented(square_at(x, y), flipped);
+                        match mouse_btn {
+                            MouseButton::Left => editor.place(pos),
+                            MouseButton::Right => editor.clear(pos),
+                            _ => {}
                         }
-                        println!("{game_data}");
-                        for (pos, avail) in valid_moves.iter() {
-                            println!("{pos:?} [{avail:?}]");
+                        continue;
+                    }
+                    if (reviewing.is_some()
+                        || replay.is_some()
+                        || engine_side == Some(game.position().to_move))
+                        && mouse_btn == MouseButton::Left
+                    {
+                        continue;
+                    }
+                    if let Some(pending) = to_be_promoted {
+                        let Some(choice) = promotion_choice_at(x, y) else {
+                            continue;
+                        };
+                        let promotion = promotion_choices(game.position().to_move)[choice];
+                        let mv = Move {
+                            promotion: Some(promotion),
+                            ..pending
+                        };
+                        if !finish_move(&mut game, &mut clock, mv) {
+                            break 'main;
                         }
+                        animating = AnimatingMove::new(game.history().last().unwrap(), &mv);
+                        annotations.clear();
                         to_be_promoted = None;
                         continue;
                     }
-                    let pos = Position {
-                        x: (x / 96) as i8,
-                        y: 7 - (y / 96) as i8,
-                    };
+                    if x >= BOARD_SIZE_PX as i32 {
+                        // the move list panel is read-only
+                        selected = None;
+                        continue;
+                    }
+                    let pos = oriented(square_at(x, y), flipped);
+                    if mouse_btn == MouseButton::Right {
+                        annotation_start = Some(pos);
+                        continue;
+                    }
                     if let Some(start_pos) = selected {
-                        if valid_moves
+                        if game
+                            .legal_moves()
                             .get(&start_pos)
                             .and_then(|valid_positions| Some(valid_positions.contains(&pos)))
                             .unwrap_or(false)
                         {
-                            (game_data, to_be_promoted) =
-                                postprocess_move(&game_data, start_pos, pos);
-                            if to_be_promoted.is_some() {
+                            let mv = Move {
+                                from: start_pos,
+                                to: pos,
+                                promotion: None,
+                            };
+                            let promotes = matches!(
+                                game.position().board.get(&start_pos),
+                                Some(PieceType::Pawn(_))
+                            ) && (pos.y == 0 || pos.y == 7);
+                            if promotes {
+                                to_be_promoted = Some(mv);
                                 selected = None;
                                 continue;
                             }
-                            valid_moves = generate_moves(&game_data);
-                            if valid_moves.is_empty() {
-                                println!(
-                                    "the end; winner is {:?}",
-                                    game_data.to_move.get_opposite()
-                                );
+                            if !finish_move(&mut game, &mut clock, mv) {
                                 break 'main;
                             }
-                            println!("{game_data}");
-                            for (pos, avail) in valid_moves.iter() {
-                                println!("{pos:?} [{avail:?}]");
-                            }
+                            animating = AnimatingMove::new(game.history().last().unwrap(), &mv);
+                            annotations.clear();
                         } else {
                             println!("cant go from {:?} to {:?}", start_pos, pos);
                         }
@@ -145,8 +640,8 @@ pub fn run() {
                         selected = None;
                         continue;
                     }
-                    if let Some(&piece) = game_data.board.get(&pos) {
-                        if piece.get_color() != game_data.to_move {
+                    if let Some(&piece) = game.position().board.get(&pos) {
+                        if piece.get_color() != game.position().to_move {
                             selected = None;
                             continue;
                         }
@@ -159,9 +654,126 @@ pub fn run() {
                         None => Some(pos),
                         Some(_) => None,
                     };
-                    selected_pos = glm::vec2(x as f32 - 48.0, 768.0 - y as f32 - 48.0);
+                    selected_pos = drag_px(x, y);
                     println!("Selected pos {:?}", selected);
                 }
+                Event::MouseButtonUp {
+                    mouse_btn: MouseButton::Right,
+                    x,
+                    y,
+                    ..
+                } => {
+                    let Some(start) = annotation_start.take() else {
+                        continue;
+                    };
+                    let Some((x, y)) = viewport.get().scene_position(x, y) else {
+                        continue;
+                    };
+                    if x >= BOARD_SIZE_PX as i32 {
+                        continue;
+                    }
+                    let pos = oriented(square_at(x, y), flipped);
+                    annotations.toggle(start, pos);
+                }
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } => {
+                    annotations.clear();
+                    match (&mut editing, key) {
+                        (None, Keycode::E) if to_be_promoted.is_none() => {
+                            println!("editing; W/B side to move, 1-4 castling, C copies the FEN, E plays");
+                            editing = Some(Editor::new(game.position().clone()));
+                            engine.cancel();
+                            selected = None;
+                        }
+                        (Some(editor), Keycode::E) => match editor.finish() {
+                            Ok(game_data) => {
+                                println!("{}", editor.fen());
+                                game = Game::from_position(game_data);
+                                clock = Clock::new(INITIAL_TIME, INCREMENT);
+                                editing = None;
+                                reviewing = None;
+                                replay = None;
+                            }
+                            Err(errors) => {
+                                for error in errors {
+                                    println!("can't play this position: {error}");
+                                }
+                            }
+                        },
+                        (Some(editor), Keycode::C) => {
+                            let fen = editor.fen();
+                            println!("{fen}");
+                            if let Err(e) = video_subsystem.clipboard().set_clipboard_text(&fen) {
+                                println!("couldn't copy the FEN: {e}");
+                            }
+                        }
+                        (_, Keycode::F) => {
+                            flipped = !flipped;
+                        }
+                        (Some(editor), key) => editor.key(key),
+                        (None, Keycode::P) => {
+                            // the computer takes the side that isn't to move
+                            engine_side = match engine_side {
+                                None => Some(game.position().to_move.get_opposite()),
+                                Some(_) => None,
+                            };
+                            match engine_side {
+                                Some(side) => println!("the computer plays {side:?}"),
+                                None => {
+                                    engine.cancel();
+                                    println!("the computer stopped playing");
+                                }
+                            }
+                            selected = None;
+                        }
+                        (None, Keycode::Space) if replay.is_some() => {
+                            let replay = replay.as_mut().unwrap();
+                            replay.paused = !replay.paused;
+                            replay.waited = Duration::ZERO;
+                            // resuming at the end starts over
+                            if !replay.paused && reviewing.is_none() {
+                                reviewing = Some(0).filter(|&ply| ply < game.ply_count());
+                            }
+                        }
+                        (None, Keycode::T) => {
+                            show_threats = !show_threats;
+                        }
+                        (None, Keycode::D) => {
+                            engine_depth = engine_depth % MAX_ENGINE_DEPTH + 1;
+                            println!("engine depth {engine_depth}");
+                        }
+                        (None, Keycode::Left | Keycode::Right | Keycode::Home | Keycode::End)
+                            if to_be_promoted.is_none() =>
+                        {
+                            let current = reviewing.unwrap_or(game.ply_count());
+                            let ply = match key {
+                                Keycode::Left => current.saturating_sub(1),
+                                Keycode::Right => current + 1,
+                                Keycode::Home => 0,
+                                _ => game.ply_count(),
+                            };
+                            // stepping past the last move is back to the live game
+                            reviewing = Some(ply).filter(|&ply| ply < game.ply_count());
+                            selected = None;
+                            if let Some(replay) = &mut replay {
+                                replay.waited = Duration::ZERO;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Event::MouseWheel { y: scroll, .. }
+                    if editing.is_none() && to_be_promoted.is_none() =>
+                {
+                    // scrolling up steps back through the game, down forward
+                    let current = reviewing.unwrap_or(game.ply_count()) as i64;
+                    let ply = (current - scroll as i64).max(0) as usize;
+                    reviewing = Some(ply).filter(|&ply| ply < game.ply_count());
+                    selected = None;
+                }
                 Event::MouseMotion {
                     timestamp: _,
                     window_id: _,
@@ -175,113 +787,458 @@ pub fn run() {
                     if selected.is_none() {
                         continue;
                     }
-                    selected_pos = glm::vec2(x as f32 - 48.0, 768.0 - y as f32 - 48.0);
+                    let Some((x, y)) = viewport.get().scene_position(x, y) else {
+                        continue;
+                    };
+                    selected_pos = drag_px(x, y);
                 }
                 _ => {}
             }
         }
+        // the search runs on the worker thread, frames go on meanwhile
+        if let Some(result) = engine.poll() {
+            if let Some((mv, score)) = result {
+                if !finish_move(&mut game, &mut clock, mv) {
+                    break 'main;
+                }
+                // the score is for the engine's side, print it for White
+                let score = match game.position().to_move {
+                    PieceColor::Black => score,
+                    PieceColor::White => -score,
+                };
+                let san = game.san_moves().last().unwrap();
+                println!("engine plays {san} ({:+.2})", score as f32 / 100.0);
+                if reviewing.is_none() {
+                    animating = AnimatingMove::new(game.history().last().unwrap(), &mv);
+                }
+                annotations.clear();
+                selected = None;
+            }
+        } else if !engine.is_thinking()
+            && engine_side == Some(game.position().to_move)
+            && editing.is_none()
+            && reviewing.is_none()
+            && replay.is_none()
+            && animating.is_none()
+        {
+            engine.request(SearchRequest {
+                position: game.position().clone(),
+                history: game.history().to_vec(),
+                depth: engine_depth,
+                limit: ENGINE_MOVE_TIME,
+            });
+        }
+        let title = if engine.is_thinking() {
+            format!("{WINDOW_TITLE} - thinking")
+        } else if replay.as_ref().is_some_and(|replay| replay.paused) {
+            format!("{WINDOW_TITLE} - paused")
+        } else {
+            WINDOW_TITLE.to_string()
+        };
+        if window.title() != title {
+            window.set_title(&title).unwrap();
+        }
         unsafe {
             gl::ClearColor(0.3, 0.3, 0.5, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
         }
-        board.draw(&projection);
+        let board_projection = projection * board_view(flipped);
+        board.draw(&board_projection);
+        let ply = reviewing.unwrap_or(game.ply_count());
+        let shown = match &editing {
+            Some(editor) => editor.position(),
+            None => game.position_at(ply).unwrap(),
+        };
+        let last_move = ply.checked_sub(1).map(|i| game.moves()[i]);
+        overlay.clear();
+        if let Some(last) = last_move.filter(|_| editing.is_none()) {
+            for square in [last.from, last.to] {
+                mark_square(&mut overlay, square, theme.last_move, 0.5);
+            }
+        }
+        if let Some(square) = selected {
+            mark_square(&mut overlay, square, theme.highlight, 0.5);
+            for &target in game.legal_moves().get(&square).into_iter().flatten() {
+                mark_target(&mut overlay, &shown.board, target, theme.highlight);
+            }
+        }
+        let check = check_info(shown);
+        let checked_king = check.king.filter(|_| check.is_check());
+        if let Some(king) = checked_king {
+            // a double check pulses instead of sitting still
+            let opacity = if check.is_double_check() {
+                0.45 + 0.35 * (started.elapsed().as_secs_f32() * 8.0).sin()
+            } else {
+                0.5
+            };
+            mark_square(&mut overlay, king, CHECK_COLOR, opacity);
+        }
+        if show_threats && editing.is_none() {
+            let opacity = 0.3 + 0.25 * (started.elapsed().as_secs_f32() * 4.0).sin();
+            for square in hanging_pieces(shown) {
+                mark_square(&mut overlay, square, THREAT_COLOR, opacity);
+            }
+        }
+        annotations.mark(&mut overlay);
+        overlay.draw(&board_projection);
+        if let Some(king) = checked_king {
+            // a line from each checker, knights included
+            for &checker in &check.checkers {
+                let arrow = Arrow {
+                    from: checker,
+                    to: king,
+                };
+                draw_arrow(
+                    &arrow,
+                    CHECK_COLOR,
+                    board_program.clone(),
+                    &board_projection,
+                );
+            }
+        }
+        annotations.draw_arrows(board_program.clone(), &board_projection);
         draw(
-            &game_data,
-            selected,
+            shown,
+            &selected
+                .into_iter()
+                .chain(
+                    animating
+                        .iter()
+                        .flat_map(|anim| anim.slides.iter().map(|slide| slide.to)),
+                )
+                .collect::<Vec<_>>(),
             piece_program.clone(),
             &piece_texture_map,
             texture.clone(),
-            &projection,
+            piece_angle(flipped),
+            &board_projection,
         );
         if selected.is_some() {
             Sprite::new(
                 piece_program.clone(),
                 texture.clone(),
                 *piece_texture_map
-                    .get(game_data.board.get(&selected.unwrap()).unwrap())
+                    .get(game.position().board.get(&selected.unwrap()).unwrap())
                     .unwrap(),
-                glm::vec4::<f32>(selected_pos.x, selected_pos.y, 96.0, 96.0),
+                glm::vec4::<f32>(selected_pos.x, selected_pos.y, SQUARE_PX, SQUARE_PX),
             )
             .draw(projection);
         }
-        if to_be_promoted.is_some() {
-            let opposite = game_data.to_move.get_opposite();
-            Sprite::new(
-                piece_program.clone(),
-                texture.clone(),
-                *piece_texture_map.get(&PieceType::Bishop(opposite)).unwrap(),
-                glm::vec4::<f32>(0.0, 96.0 * 3.0, 48.0, 48.0),
-            )
-            .draw(projection);
-            Sprite::new(
-                piece_program.clone(),
-                texture.clone(),
-                *piece_texture_map
-                    .get(&&PieceType::Knight(opposite))
-                    .unwrap(),
-                glm::vec4::<f32>(0.0, 96.0 * 3.5, 48.0, 48.0),
-            )
-            .draw(projection);
-            Sprite::new(
+        if let Some(anim) = &animating {
+            for slide in &anim.slides {
+                let at = glm::lerp(&slide.from_px, &slide.to_px, anim.progress());
+                Sprite::new(
+                    piece_program.clone(),
+                    texture.clone(),
+                    *piece_texture_map.get(&slide.piece).unwrap(),
+                    glm::vec4::<f32>(at.x, at.y, SQUARE_PX, SQUARE_PX),
+                )
+                .with_angle(piece_angle(flipped) + slide.spin * anim.progress())
+                .draw(&board_projection);
+            }
+            if anim.progress() >= 1.0 {
+                animating = None;
+            }
+        }
+        match &editing {
+            Some(editor) => draw_palette(
+                editor,
+                &font,
+                board_program.clone(),
                 piece_program.clone(),
+                &piece_texture_map,
                 texture.clone(),
-                *piece_texture_map.get(&&&PieceType::Rook(opposite)).unwrap(),
-                glm::vec4::<f32>(0.0, 96.0 * 4.0, 48.0, 48.0),
-            )
-            .draw(projection);
+                theme.highlight,
+                projection,
+            ),
+            None => {
+                draw_move_list(&game, &font, piece_program.clone(), projection);
+                draw_captured(
+                    &game.start_position().board,
+                    &shown.board,
+                    piece_program.clone(),
+                    &piece_texture_map,
+                    texture.clone(),
+                    projection,
+                );
+            }
+        }
+        if to_be_promoted.is_some() {
+            let choices = promotion_choices(game.position().to_move);
+            for (i, piece) in choices.iter().enumerate() {
+                Sprite::new(
+                    piece_program.clone(),
+                    texture.clone(),
+                    *piece_texture_map.get(piece).unwrap(),
+                    promotion_rect(i),
+                )
+                .draw(projection);
+            }
+        }
+        window.gl_swap_window();
+        // fps; with vsync the swap above already waited for the display
+        let frame_time = last_frame_time.elapsed();
+        if !vsync && frame_time < frame_duration {
+            std::thread::sleep(frame_duration - frame_time);
+        }
+        // the side to move pays for the whole frame, sleep included; a pending
+        // promotion hasn't been played yet, so that is still the player picking
+        match &mut replay {
+            Some(replay) => {
+                if let Some(ply) = reviewing.filter(|_| replay.tick(last_frame_time.elapsed())) {
+                    let mv = game.moves()[ply];
+                    animating = AnimatingMove::new(game.position_at(ply).unwrap(), &mv);
+                    reviewing = Some(ply + 1).filter(|&ply| ply < game.ply_count());
+                }
+            }
+            None if editing.is_none() => {
+                clock.tick(game.position().to_move, last_frame_time.elapsed())
+            }
+            None => {}
+        }
+        if let Some(flagged) = clock.flag() {
+            println!(
+                "{flagged:?} ran out of time; {:?}",
+                timeout_result(&game.position().board, flagged)
+            );
+            break 'main;
+        }
+        // Update last_frame_time to measure the next frame's duration
+        last_frame_time = Instant::now();
+    }
+    Ok(())
+}
+
+/// Tints `square` in `color`.
+fn mark_square(overlay: &mut Overlay, square: Position, color: Color, opacity: f32) {
+    overlay.push(
+        square_rect(square, false, SQUARE_PX),
+        glm::vec4(color[0], color[1], color[2], opacity),
+    );
+}
+
+/// Marks a square the selected piece can move to: a dot on an empty square,
+/// a faint tint under a piece it could take.
+fn mark_target(overlay: &mut Overlay, board: &Board, square: Position, color: Color) {
+    if board.contains_key(&square) {
+        mark_square(overlay, square, color, 0.3);
+        return;
+    }
+    let center = square_center(square, false, SQUARE_PX);
+    let dot = SQUARE_PX / 3.0;
+    overlay.push(
+        glm::vec4(center.x - dot / 2.0, center.y - dot / 2.0, dot, dot),
+        glm::vec4(color[0], color[1], color[2], 0.6),
+    );
+}
+
+/// A flat `color` rectangle, drawn with the board shader by giving it the same
+/// color for both square shades.
+fn tinted_rect(
+    rect: glm::Vec4,
+    color: Color,
+    opacity: f32,
+    board_program: Rc<ShaderProgram>,
+) -> Rect {
+    let mut tinted = Rect::new(rect, board_program);
+    tinted.uniform_setter = Some(Box::new(move |shader: Rc<ShaderProgram>| {
+        shader.set_uniform_bool("black_view", false);
+        shader.set_uniform_vec3f("white_color", glm::make_vec3(&color));
+        shader.set_uniform_vec3f("black_color", glm::make_vec3(&color));
+        shader.set_uniform_float("opacity", opacity);
+        shader.set_uniform_float("side_size", SQUARE_PX);
+    }));
+    tinted
+}
+
+/// The opening the game has reached so far.
+fn game_opening(game: &Game) -> Option<EcoEntry> {
+    if game.start_position().hash != GameData::default().hash {
+        return None;
+    }
+    let moves: Vec<(Position, Position)> = game.moves().iter().map(|mv| (mv.from, mv.to)).collect();
+    classify_opening(&moves)
+}
+
+/// Plays `mv`, charges the mover's clock and tells whether the game goes on.
+fn finish_move(game: &mut Game, clock: &mut Clock, mv: Move) -> bool {
+    let mover = game.position().to_move;
+    let opening = game_opening(game);
+    game.play(mv);
+    if let Some(entry) = game_opening(game).filter(|&entry| Some(entry) != opening) {
+        println!("opening: {entry}");
+    }
+    clock.on_move(mover, Duration::ZERO);
+    // repetitions and the fifty-move rule are claimed for the players
+    game.claim_draw();
+    if game.result() != GameResult::Ongoing {
+        println!("the end; {:?}", game.result());
+        return false;
+    }
+    println!("{}", game.position());
+    print!("{}", game.position().moves_report());
+    true
+}
+
+/// Pieces each side has lost at the bottom of the panel, White's losses on the
+/// lower row.
+fn draw_captured(
+    start: &Board,
+    current: &Board,
+    piece_program: Rc<ShaderProgram>,
+    piece_texture_map: &HashMap<PieceType, glm::Vec4>,
+    texture: Rc<Texture2D>,
+    projection: &glm::Mat4,
+) {
+    let (white_lost, black_lost) = captured_pieces(start, current);
+    for (row, lost) in [white_lost, black_lost].iter().enumerate() {
+        for (i, piece) in lost.iter().enumerate() {
             Sprite::new(
                 piece_program.clone(),
                 texture.clone(),
-                *piece_texture_map
-                    .get(&&&&PieceType::Queen(opposite))
-                    .unwrap(),
-                glm::vec4::<f32>(0.0, 96.0 * 4.5, 48.0, 48.0),
+                piece_texture_map[piece],
+                glm::vec4(
+                    BOARD_SIZE_PX as f32 + PANEL_MARGIN_PX + i as f32 * CAPTURED_STEP_PX,
+                    PANEL_MARGIN_PX + row as f32 * CAPTURED_PX,
+                    CAPTURED_PX,
+                    CAPTURED_PX,
+                ),
             )
             .draw(projection);
         }
-        window.gl_swap_window();
-        // fps
-        let frame_time = last_frame_time.elapsed();
-        if frame_time < FRAME_DURATION {
-            std::thread::sleep(FRAME_DURATION - frame_time);
+    }
+}
+
+/// Lists the game so far in the panel right of the board, one move number per
+/// line, keeping the latest moves in view once the list outgrows the panel.
+fn draw_move_list(
+    game: &Game,
+    font: &BitmapFont,
+    text_program: Rc<ShaderProgram>,
+    projection: &glm::Mat4,
+) {
+    let line_height = BitmapFont::line_height(TEXT_SCALE);
+    // the bottom of the panel is kept for the captured pieces
+    let height = BOARD_SIZE_PX as f32 - 3.0 * PANEL_MARGIN_PX - 2.0 * CAPTURED_PX;
+    let visible = (height / line_height) as usize;
+    let rows = game.move_list();
+    let first = rows.len().saturating_sub(visible);
+    for (line, row) in rows[first..].iter().enumerate() {
+        let y = BOARD_SIZE_PX as f32 - PANEL_MARGIN_PX - (line + 1) as f32 * line_height;
+        font.draw_text(
+            text_program.clone(),
+            row,
+            glm::vec2(BOARD_SIZE_PX as f32 + PANEL_MARGIN_PX, y),
+            TEXT_SCALE,
+            projection,
+        );
+    }
+}
+
+/// Where palette entry `index` sits in the panel, white pieces on the upper
+/// row.
+fn palette_rect(index: usize) -> glm::Vec4 {
+    let (row, column) = (index / 6, index % 6);
+    glm::vec4(
+        BOARD_SIZE_PX as f32 + PANEL_MARGIN_PX + column as f32 * PALETTE_CELL_PX,
+        PANEL_MARGIN_PX + (1 - row) as f32 * PALETTE_CELL_PX,
+        PALETTE_CELL_PX,
+        PALETTE_CELL_PX,
+    )
+}
+
+fn palette_piece_at(at: glm::Vec2) -> Option<PieceType> {
+    (0..PALETTE.len())
+        .find(|&i| {
+            let rect = palette_rect(i);
+            (rect.x..rect.x + rect.z).contains(&at.x) && (rect.y..rect.y + rect.w).contains(&at.y)
+        })
+        .map(|i| PALETTE[i])
+}
+
+/// The editor's side panel: side to move and castling rights on top, the
+/// piece palette with the current choice highlighted at the bottom.
+#[allow(clippy::too_many_arguments)]
+fn draw_palette(
+    editor: &Editor,
+    font: &BitmapFont,
+    board_program: Rc<ShaderProgram>,
+    piece_program: Rc<ShaderProgram>,
+    piece_texture_map: &HashMap<PieceType, glm::Vec4>,
+    texture: Rc<Texture2D>,
+    highlight: Color,
+    projection: &glm::Mat4,
+) {
+    font.draw_text(
+        piece_program.clone(),
+        &editor.status(),
+        glm::vec2(
+            BOARD_SIZE_PX as f32 + PANEL_MARGIN_PX,
+            BOARD_SIZE_PX as f32 - PANEL_MARGIN_PX - BitmapFont::line_height(TEXT_SCALE),
+        ),
+        TEXT_SCALE,
+        projection,
+    );
+    for (i, piece) in PALETTE.iter().enumerate() {
+        let rect = palette_rect(i);
+        if *piece == editor.piece {
+            tinted_rect(rect, highlight, 0.5, board_program.clone()).draw(projection);
         }
-        // Update last_frame_time to measure the next frame's duration
-        last_frame_time = Instant::now();
+        Sprite::new(
+            piece_program.clone(),
+            texture.clone(),
+            piece_texture_map[piece],
+            rect,
+        )
+        .draw(projection);
     }
 }
 
 fn draw(
     game_data: &GameData,
-    selected: Option<Position>,
+    hidden: &[Position],
     piece_program: Rc<ShaderProgram>,
     piece_texture_map: &HashMap<PieceType, glm::Vec4>,
     texture: Rc<Texture2D>,
+    angle: f32,
     projection: &glm::Mat4,
 ) {
     for (&p_pos, &p_type) in game_data.board.iter() {
-        if selected.is_some() && selected.unwrap() == p_pos {
+        if hidden.contains(&p_pos) {
             continue;
         }
         Sprite::new(
             piece_program.clone(),
             texture.clone(),
             *piece_texture_map.get(&p_type).unwrap(),
-            glm::vec4::<f32>(p_pos.x as f32 * 96.0, p_pos.y as f32 * 96.0, 96.0, 96.0),
+            square_rect(p_pos, false, SQUARE_PX),
         )
+        .with_angle(angle)
         .draw(projection);
     }
 }
-fn init_shaders() -> (Rc<ShaderProgram>, Rc<ShaderProgram>) {
-    let board_vert =
-        Shader::from_file("./resources/shaders/simple.v.glsl", gl::VERTEX_SHADER).unwrap();
-    let board_frag =
-        Shader::from_file("./resources/shaders/board.f.glsl", gl::FRAGMENT_SHADER).unwrap();
-    let texture_vert =
-        Shader::from_file("./resources/shaders/texture.v.glsl", gl::VERTEX_SHADER).unwrap();
+/// The board, piece and overlay shaders and the spritesheet.
+type GraphicsResources = (
+    Rc<ShaderProgram>,
+    Rc<ShaderProgram>,
+    Rc<ShaderProgram>,
+    Rc<Texture2D>,
+);
+
+/// Loads the shaders and the spritesheet from `./resources`, naming the first
+/// file that is missing or broken.
+fn init_graphics() -> Result<GraphicsResources, GraphicsInitError> {
+    let board_vert = Shader::from_file("./resources/shaders/simple.v.glsl", gl::VERTEX_SHADER)?;
+    let board_frag = Shader::from_file("./resources/shaders/board.f.glsl", gl::FRAGMENT_SHADER)?;
+    let texture_vert = Shader::from_file("./resources/shaders/texture.v.glsl", gl::VERTEX_SHADER)?;
     let texture_frag =
-        Shader::from_file("./resources/shaders/texture.f.glsl", gl::FRAGMENT_SHADER).unwrap();
+        Shader::from_file("./resources/shaders/texture.f.glsl", gl::FRAGMENT_SHADER)?;
+    let overlay_vert = Shader::from_file("./resources/shaders/overlay.v.glsl", gl::VERTEX_SHADER)?;
+    let overlay_frag =
+        Shader::from_file("./resources/shaders/overlay.f.glsl", gl::FRAGMENT_SHADER)?;
+    let link = |e: Box<dyn std::error::Error>| GraphicsInitError::Link(e.to_string());
 
-    let mut board_program = ShaderProgram::from_shaders(&[board_vert, board_frag]).unwrap();
+    let mut board_program = ShaderProgram::from_shaders(&[board_vert, board_frag]).map_err(link)?;
     board_program.hash_uniform_locations(&[
         "black_view",
         "opacity",
@@ -290,14 +1247,25 @@ fn init_shaders() -> (Rc<ShaderProgram>, Rc<ShaderProgram>) {
         "white_color",
         "mvp",
     ]);
-    let mut piece_program = ShaderProgram::from_shaders(&[texture_vert, texture_frag]).unwrap();
+    let mut piece_program =
+        ShaderProgram::from_shaders(&[texture_vert, texture_frag]).map_err(link)?;
     piece_program.hash_uniform_locations(&["mvp"]);
-    (board_program.into(), piece_program.into())
+    let mut overlay_program =
+        ShaderProgram::from_shaders(&[overlay_vert, overlay_frag]).map_err(link)?;
+    overlay_program.hash_uniform_locations(&["mvp"]);
+    let spritesheet = load_image("./resources/textures/spritesheet.png")?;
+    let texture = Texture2D::new(Rc::new(spritesheet), TextureFilter::Nearest);
+    Ok((
+        board_program.into(),
+        piece_program.into(),
+        overlay_program.into(),
+        texture.into(),
+    ))
 }
 fn create_piece_texture_map() -> HashMap<PieceType, glm::Vec4> {
     let mut textures = HashMap::<PieceType, glm::Vec4>::new();
     generate_textures_for_side(0.0, PieceColor::Black, &mut textures);
-    generate_textures_for_side(480.0, PieceColor::White, &mut textures);
+    generate_textures_for_side(SPRITE_CELL_PX, PieceColor::White, &mut textures);
     textures
 }
 fn generate_textures_for_side(
@@ -307,26 +1275,127 @@ fn generate_textures_for_side(
 ) {
     textures.insert(
         PieceType::Bishop(color),
-        glm::vec4::<f32>(0.0, y, 480.0, 480.0),
+        glm::vec4::<f32>(0.0, y, SPRITE_CELL_PX, SPRITE_CELL_PX),
     );
     textures.insert(
         PieceType::King(color),
-        glm::vec4::<f32>(480.0, y, 480.0, 480.0),
+        glm::vec4::<f32>(SPRITE_CELL_PX, y, SPRITE_CELL_PX, SPRITE_CELL_PX),
     );
     textures.insert(
         PieceType::Knight(color),
-        glm::vec4::<f32>(2.0 * 480.0, y, 480.0, 480.0),
+        glm::vec4::<f32>(2.0 * SPRITE_CELL_PX, y, SPRITE_CELL_PX, SPRITE_CELL_PX),
     );
     textures.insert(
         PieceType::Pawn(color),
-        glm::vec4::<f32>(3.0 * 480.0, y, 480.0, 480.0),
+        glm::vec4::<f32>(3.0 * SPRITE_CELL_PX, y, SPRITE_CELL_PX, SPRITE_CELL_PX),
     );
     textures.insert(
         PieceType::Queen(color),
-        glm::vec4::<f32>(4.0 * 480.0, y, 480.0, 480.0),
+        glm::vec4::<f32>(4.0 * SPRITE_CELL_PX, y, SPRITE_CELL_PX, SPRITE_CELL_PX),
     );
     textures.insert(
         PieceType::Rook(color),
-        glm::vec4::<f32>(5.0 * 480.0, y, 480.0, 480.0),
+        glm::vec4::<f32>(5.0 * SPRITE_CELL_PX, y, SPRITE_CELL_PX, SPRITE_CELL_PX),
+    );
+}
+
+#[test]
+fn frame_duration_follows_the_frame_rate() {
+    let settings = |fps| RenderSettings {
+        fps,
+        ..RenderSettings::default()
+    };
+    assert_eq!(settings(50).frame_duration(), Duration::from_millis(20));
+    assert_eq!(settings(144).frame_duration(), Duration::from_secs(1) / 144);
+    assert_eq!(settings(0).frame_duration(), Duration::from_secs(1));
+}
+
+#[test]
+fn replay_steps_once_per_interval() {
+    let mut replay = Replay::new(Duration::from_millis(100));
+    assert!(!replay.tick(Duration::from_millis(60)));
+    assert!(replay.tick(Duration::from_millis(60)));
+    assert!(!replay.tick(Duration::from_millis(60)));
+    replay.paused = true;
+    assert!(!replay.tick(Duration::from_secs(1)));
+    replay.paused = false;
+    assert!(replay.tick(Duration::from_millis(40)));
+}
+
+#[test]
+fn squares_round_trip_through_the_window() {
+    for (width, height) in [
+        (1024, 768),
+        (800, 600),
+        (1920, 1080),
+        (500, 900),
+        (333, 257),
+    ] {
+        let viewport = Viewport::fit(width, height);
+        for x in 0..8 {
+            for y in 0..8 {
+                let square = Position { x, y };
+                let center = square_center(square, false, SQUARE_PX);
+                // counted from the top
+                let (window_x, window_y) =
+                    viewport.window_position(center.x, BOARD_SIZE_PX as f32 - center.y);
+                let (scene_x, scene_y) = viewport.scene_position(window_x, window_y).unwrap();
+                assert_eq!(square_at(scene_x, scene_y), square, "{width}x{height}");
+            }
+        }
+        // the margins around the scene hit nothing
+        assert_eq!(
+            viewport.scene_position(viewport.left - 1, viewport.top),
+            None
+        );
+        assert_eq!(
+            viewport.scene_position(viewport.left, viewport.top - 1),
+            None
+        );
+    }
+}
+
+#[test]
+fn square_rects_round_trip_through_the_mouse() {
+    for flipped in [false, true] {
+        for x in 0..8 {
+            for y in 0..8 {
+                let square = Position { x, y };
+                let rect = square_rect(square, flipped, SQUARE_PX);
+                assert_eq!((rect.z, rect.w), (SQUARE_PX, SQUARE_PX));
+                // the mouse counts from the top
+                let center = square_center(square, flipped, SQUARE_PX);
+                let clicked = square_at(center.x as i32, (BOARD_SIZE_PX as f32 - center.y) as i32);
+                assert_eq!(oriented(clicked, flipped), square);
+
+                // turning the board with the view lands where flipping puts it
+                let plain = square_center(square, false, SQUARE_PX);
+                let turned = board_view(true) * glm::vec4(plain.x, plain.y, 0.0, 1.0);
+                let expected = square_center(square, true, SQUARE_PX);
+                assert_eq!(
+                    (turned.x.round(), turned.y.round()),
+                    (expected.x, expected.y)
+                );
+            }
+        }
+    }
+    let small = square_rect(Position { x: 2, y: 1 }, false, 10.0);
+    assert_eq!(small, glm::vec4(20.0, 10.0, 10.0, 10.0));
+}
+
+#[test]
+fn flipped_board_turns_the_corners_around() {
+    let a1 = Position { x: 0, y: 0 };
+    let h8 = Position { x: 7, y: 7 };
+    let corner = board_view(true) * glm::vec4(0.0, 0.0, 0.0, 1.0);
+    let far = square_rect(h8, false, SQUARE_PX);
+    assert_eq!(
+        (corner.x.round(), corner.y.round()),
+        (far.x + far.z, far.y + far.w)
     );
+    assert_eq!(board_view(false), glm::Mat4::identity());
+    assert_eq!(oriented(h8, true), a1);
+    assert_eq!(oriented(h8, false), h8);
+    let e2 = Position { x: 4, y: 1 };
+    assert_eq!(oriented(oriented(e2, true), true), e2);
 }
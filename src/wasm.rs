@@ -0,0 +1,71 @@
+//! Browser bindings for the move generator, built with:
+//!
+//! ```text
+//! cargo build --lib --target wasm32-unknown-unknown --no-default-features --features wasm
+//! ```
+//!
+//! Positions cross the boundary as FEN strings and moves as long algebraic
+//! notation. See `examples/wasm/index.js` for a usage example.
+use crate::chess::{apply_move, generate_moves};
+use crate::fen::{from_fen_checked, to_fen};
+use crate::uci::parse_move;
+use wasm_bindgen::prelude::*;
+
+/// Legal moves of the position as a JSON object mapping each origin square
+/// to its destinations, e.g. `{"e2":["e3","e4"]}`. Keys and destinations are
+/// sorted so the output is stable.
+pub fn moves_json(fen: &str) -> Result<String, String> {
+    let game_data = from_fen_checked(fen).map_err(|e| e.to_string())?;
+    let mut moves: Vec<_> = generate_moves(&game_data).into_iter().collect();
+    moves.sort();
+    let entries: Vec<String> = moves
+        .into_iter()
+        .map(|(from, mut destinations)| {
+            destinations.sort();
+            let destinations: Vec<String> = destinations
+                .iter()
+                .map(|to| format!("\"{}\"", to.to_algebraic()))
+                .collect();
+            format!("\"{}\":[{}]", from.to_algebraic(), destinations.join(","))
+        })
+        .collect();
+    Ok(format!("{{{}}}", entries.join(",")))
+}
+
+/// FEN of the position after playing `mv` in the position `fen`.
+pub fn apply_move_fen(fen: &str, mv: &str) -> Result<String, String> {
+    let game_data = from_fen_checked(fen).map_err(|e| e.to_string())?;
+    let mv = parse_move(&game_data, mv).ok_or_else(|| format!("illegal move '{mv}'"))?;
+    Ok(to_fen(&apply_move(&game_data, &mv)))
+}
+
+#[wasm_bindgen]
+pub fn wasm_generate_moves(fen: &str) -> Result<JsValue, JsValue> {
+    let json = moves_json(fen).map_err(|e| JsValue::from_str(&e))?;
+    js_sys::JSON::parse(&json)
+}
+
+#[wasm_bindgen]
+pub fn wasm_apply_move(fen: &str, mv: &str) -> Result<String, JsValue> {
+    apply_move_fen(fen, mv).map_err(|e| JsValue::from_str(&e))
+}
+
+#[test]
+fn moves_json_lists_sorted_destinations() {
+    let json = moves_json("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+    assert_eq!(json, r#"{"e1":["d1","d2","f1","f2"],"e2":["e3","e4"]}"#);
+    assert!(moves_json("not a fen").is_err());
+}
+
+#[test]
+fn apply_move_fen_returns_new_position() {
+    assert_eq!(
+        apply_move_fen(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "e2e4"
+        )
+        .unwrap(),
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+    );
+    assert!(apply_move_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1", "e1e3").is_err());
+}
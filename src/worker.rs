@@ -0,0 +1,129 @@
+//! Engine searches on a background thread, so a GUI can keep drawing frames
+//! and handling input while the engine thinks.
+use crate::chess::{GameData, Move};
+use crate::engine::best_move_within;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+/// Everything a search needs, moved to the worker thread.
+#[derive(Debug, Clone)]
+pub struct SearchRequest {
+    pub position: GameData,
+    /// Earlier positions of the game, oldest first, for repetitions.
+    pub history: Vec<GameData>,
+    pub depth: u32,
+    pub limit: Duration,
+}
+
+/// A thread running one search at a time. Requests are answered in order;
+/// `poll` hands back the answer to the latest request once it is ready and
+/// drops answers to requests made stale by a newer one or by `cancel`.
+pub struct EngineWorker {
+    requests: Sender<(u64, SearchRequest)>,
+    results: Receiver<(u64, Option<(Move, i32)>)>,
+    next_id: u64,
+    // the request whose answer is still wanted
+    waiting_for: Option<u64>,
+}
+
+impl EngineWorker {
+    pub fn spawn() -> Self {
+        let (requests, incoming) = channel::<(u64, SearchRequest)>();
+        let (outgoing, results) = channel();
+        // the thread ends once the worker is dropped and `recv` fails
+        thread::spawn(move || {
+            while let Ok((id, request)) = incoming.recv() {
+                let result = best_move_within(
+                    &request.position,
+                    request.depth,
+                    request.limit,
+                    &request.history,
+                );
+                if outgoing.send((id, result)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            requests,
+            results,
+            next_id: 0,
+            waiting_for: None,
+        }
+    }
+
+    /// Starts searching `request`; any earlier answer still on its way is
+    /// dropped.
+    pub fn request(&mut self, request: SearchRequest) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.waiting_for = Some(id);
+        self.requests
+            .send((id, request))
+            .expect("engine thread is running");
+    }
+
+    /// Forgets the request in flight, if any.
+    pub fn cancel(&mut self) {
+        self.waiting_for = None;
+    }
+
+    pub fn is_thinking(&self) -> bool {
+        self.waiting_for.is_some()
+    }
+
+    /// The answer to the latest request once the search is done: the best
+    /// move with its score for the side to move, or `None` when the position
+    /// has no legal move. Never blocks.
+    pub fn poll(&mut self) -> Option<Option<(Move, i32)>> {
+        loop {
+            match self.results.try_recv() {
+                Ok((id, result)) if Some(id) == self.waiting_for => {
+                    self.waiting_for = None;
+                    return Some(result);
+                }
+                Ok(_) => continue,
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => panic!("engine thread stopped"),
+            }
+        }
+    }
+}
+
+#[test]
+fn worker_answers_search_requests() {
+    let request = |fen: &str| SearchRequest {
+        position: crate::fen::from_fen(fen).unwrap(),
+        history: Vec::new(),
+        depth: 2,
+        limit: Duration::from_secs(10),
+    };
+    let wait_for = |worker: &mut EngineWorker| {
+        let deadline = std::time::Instant::now() + Duration::from_secs(30);
+        while std::time::Instant::now() < deadline {
+            if let Some(result) = worker.poll() {
+                return result;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        panic!("no answer from the engine thread");
+    };
+    let mut worker = EngineWorker::spawn();
+    assert!(!worker.is_thinking());
+    worker.request(request("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"));
+    assert!(worker.is_thinking());
+    let (mv, _) = wait_for(&mut worker).unwrap();
+    assert_eq!(crate::chess::move_to_uci(&mv), "a1a8");
+    assert!(!worker.is_thinking());
+
+    // only the latest request is answered
+    worker.request(request("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"));
+    worker.request(request("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1"));
+    let (mv, _) = wait_for(&mut worker).unwrap();
+    assert_eq!(crate::chess::move_to_uci(&mv), "d1d5");
+
+    // no legal move at all
+    worker.request(request("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"));
+    assert_eq!(wait_for(&mut worker), None);
+}
@@ -0,0 +1,148 @@
+//! Zobrist keys: every piece on every square, each castling right, the en
+//! passant file and the side to move get a fixed random number, and a
+//! position hashes to the xor of the keys that apply to it. A move only
+//! changes a few of them, so [`GameData::hash`] is kept up to date by xoring
+//! those in and out instead of hashing the whole board again.
+use crate::chess::{Castling, GameData, PieceColor, PieceType, Position};
+use crate::random::split_mix64;
+use std::collections::HashMap;
+
+const PIECE_KEYS: usize = 0;
+const CASTLING_KEYS: usize = PIECE_KEYS + 12 * 64;
+const EN_PASSANT_KEYS: usize = CASTLING_KEYS + 4;
+const SIDE_KEY: usize = EN_PASSANT_KEYS + 8;
+
+/// SplitMix64 from a fixed seed, so hashes are the same on every run.
+const KEYS: [u64; SIDE_KEY + 1] = {
+    let mut keys = [0; SIDE_KEY + 1];
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut i = 0;
+    while i < keys.len() {
+        (state, keys[i]) = split_mix64(state);
+        i += 1;
+    }
+    keys
+};
+
+pub fn piece_key(piece: PieceType, square: Position) -> u64 {
+    let kind = match piece {
+        PieceType::King(_) => 0,
+        PieceType::Queen(_) => 1,
+        PieceType::Bishop(_) => 2,
+        PieceType::Knight(_) => 3,
+        PieceType::Rook(_) => 4,
+        PieceType::Pawn(_) => 5,
+    } + if piece.get_color() == PieceColor::Black {
+        6
+    } else {
+        0
+    };
+    KEYS[PIECE_KEYS + kind * 64 + (square.y * 8 + square.x) as usize]
+}
+
+pub fn castling_key(castling: &HashMap<PieceColor, Castling>) -> u64 {
+    let mut key = 0;
+    for (i, color) in [PieceColor::White, PieceColor::Black]
+        .into_iter()
+        .enumerate()
+    {
+        if let Some(rights) = castling.get(&color) {
+            if rights.king_side {
+                key ^= KEYS[CASTLING_KEYS + 2 * i];
+            }
+            if rights.queen_side {
+                key ^= KEYS[CASTLING_KEYS + 2 * i + 1];
+            }
+        }
+    }
+    key
+}
+
+/// The en passant file counts only when a pawn of the side to move stands
+/// next to the pawn that just moved two squares, so a double push nobody can
+/// answer doesn't tell otherwise equal positions apart.
+pub fn en_passant_key(game_data: &GameData) -> u64 {
+    let Some(landing) = game_data.moved_2_squares else {
+        return 0;
+    };
+    let capturer = PieceType::Pawn(game_data.to_move);
+    let can_capture = [-1, 1].into_iter().any(|dx| {
+        let neighbour = Position {
+            x: landing.x + dx,
+            ..landing
+        };
+        game_data.board.get(&neighbour) == Some(&capturer)
+    });
+    if can_capture {
+        KEYS[EN_PASSANT_KEYS + landing.x as usize]
+    } else {
+        0
+    }
+}
+
+pub fn side_key(to_move: PieceColor) -> u64 {
+    match to_move {
+        PieceColor::White => 0,
+        PieceColor::Black => KEYS[SIDE_KEY],
+    }
+}
+
+/// The hash of `game_data` computed from scratch.
+pub fn hash(game_data: &GameData) -> u64 {
+    game_data
+        .board
+        .iter()
+        .fold(0, |key, (&square, &piece)| key ^ piece_key(piece, square))
+        ^ castling_key(&game_data.castling)
+        ^ en_passant_key(game_data)
+        ^ side_key(game_data.to_move)
+}
+
+#[test]
+fn incremental_hash_matches_recompute() {
+    use crate::chess::{apply_move, generate_moves_detailed};
+    use crate::fen::from_fen;
+    // castling, en passant and promotions all come up from these
+    let starts = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+    ];
+    // xorshift, to pick the same "random" moves on every run
+    let mut seed: u64 = 0x1234_5678_9abc_def1;
+    for fen in starts {
+        let mut game_data = from_fen(fen).unwrap();
+        assert_eq!(game_data.hash, hash(&game_data));
+        for _ in 0..200 {
+            let moves = generate_moves_detailed(&game_data);
+            if moves.is_empty() {
+                game_data = from_fen(fen).unwrap();
+                continue;
+            }
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let mv = moves[(seed % moves.len() as u64) as usize];
+            game_data = apply_move(&game_data, &mv);
+            assert_eq!(game_data.hash, hash(&game_data), "after {mv:?}");
+        }
+    }
+}
+
+#[test]
+fn transpositions_hash_alike() {
+    use crate::chess::{apply_move, parse_uci_move, GameData};
+    let play = |moves: &[&str]| {
+        moves.iter().fold(GameData::default(), |game_data, mv| {
+            apply_move(&game_data, &parse_uci_move(mv).unwrap())
+        })
+    };
+    let a = play(&["g1f3", "g8f6", "b1c3", "b8c6"]);
+    let b = play(&["b1c3", "b8c6", "g1f3", "g8f6"]);
+    assert_eq!(a.hash, b.hash);
+    assert_ne!(a.hash, GameData::default().hash);
+    // knights out and back: the start position again
+    let back = play(&["g1f3", "g8f6", "f3g1", "f6g8"]);
+    assert_eq!(back.hash, GameData::default().hash);
+}